@@ -1,7 +1,38 @@
-use futures_util::StreamExt;
+use std::sync::OnceLock;
+
 use reqwest::Client;
-use serde_json::Value;
-use tauri::{command, Emitter, Window};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Emitter, Manager, Window};
+use tracing::{error, info, warn};
+
+use crate::attachments::AttachmentStore;
+use crate::caching::{apply_cache_control, PromptCacheConfig};
+use crate::images::{apply_image_modalities, save_generated_image, ImageOutputCollector};
+use crate::keys::{resolve_key, ResolvedKey};
+use crate::plugins::{
+    apply_file_parser, apply_web_search, AnnotationCollector, FileAnnotationStore, WebSearchConfig,
+};
+use crate::rag::{apply_retrieval, RetrievalOptions};
+use crate::request::validate_body;
+use crate::routing::{apply_provider_routing, ProviderPreferences};
+use crate::stream::{check_budget, execute, Endpoint, StreamFailure, StreamJob};
+use crate::structured::{apply_structured_output, check_output, repair_message, StructuredOutput};
+use crate::tools::approval::ApprovalManager;
+use crate::tools::{attach_tools, run_tool_call, ToolCallAccumulator, DEFAULT_MAX_TOOL_ITERATIONS};
+use crate::usage::next_request_id;
+
+/// OpenRouter API 根地址
+/// OpenRouter API base URL.
+pub const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
+
+static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// 全局共享的 HTTP 客户端（复用连接池）
+/// Process-wide shared HTTP client (reuses the connection pool).
+pub fn http_client() -> Client {
+    HTTP_CLIENT.get_or_init(Client::new).clone()
+}
 
 /// 命令通用返回结构
 /// Generic command result envelope.
@@ -22,10 +53,10 @@ pub struct CommandResult {
 /// Returns a JSON string of `CommandResult`.
 #[command]
 pub async fn get_open_router_models() -> String {
-    let url = "https://openrouter.ai/api/v1/models";
-    let client = Client::new();
+    let url = format!("{}/models", OPENROUTER_API_BASE);
+    let client = http_client();
 
-    match client.get(url).send().await {
+    match client.get(&url).send().await {
         Ok(response) => {
             if response.status().is_success() {
                 let data = response.json::<serde_json::Value>().await;
@@ -73,23 +104,63 @@ pub async fn get_open_router_models() -> String {
     }
 }
 
+/// `proxy_stream` 的可选参数
+/// Optional parameters of `proxy_stream`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ProxyOptions {
+    /// 会话 ID（会话创建时间）/ conversation id (its create time)
+    pub conversation_id: Option<String>,
+    /// 显式指定的 Key 档案 / explicitly selected key profile
+    pub key_id: Option<String>,
+    /// 请求 ID，用于 `cancel_stream`；为空时自动生成 / request id for `cancel_stream`, generated when empty
+    pub request_id: Option<String>,
+    /// 提供给模型的后端工具名，`*` 表示全部 / backend tools offered to the model, `*` for all
+    pub tools: Option<Vec<String>>,
+    /// 工具调用最大轮数 / max tool-calling rounds
+    pub max_tool_iterations: Option<u32>,
+    /// 本次请求的联网搜索参数，覆盖设置 / web search parameters for this request, overriding settings
+    pub web_search: Option<WebSearchConfig>,
+    /// 本次请求的 PDF 解析引擎，覆盖设置 / PDF engine for this request, overriding settings
+    pub pdf_engine: Option<String>,
+    /// 本次请求检索的文件夹索引 / folder indexes to search for this request
+    pub retrieval: Option<RetrievalOptions>,
+    /// 要求回答符合的 JSON Schema / JSON Schema the answer must conform to
+    pub structured_output: Option<StructuredOutput>,
+    /// 本次请求的供应商路由偏好 / provider routing preferences for this request
+    pub provider: Option<ProviderPreferences>,
+    /// 本次请求的提示缓存参数，覆盖设置 / prompt cache parameters for this request, overriding settings
+    pub prompt_cache: Option<PromptCacheConfig>,
+}
+
 /// 代理转发流式响应到前端（SSE/流）
 /// Proxy OpenRouter streaming response to frontend (SSE/stream).
 ///
 /// - 实时将 `delta.content` 片段通过 `stream-response` 事件推送给窗口。
 /// - 当首次对话完成后，异步生成标题并通过 `update_chat_title` 通知前端。
+/// - `options` 中的各项由对应的 `apply_*` 函数写入请求体。
+/// - 结构化输出修复重试前发送 `stream-reset`：此前推送的文本作废，之后推送的才是最终回答。
 /// - Push `delta.content` chunks via `stream-response`.
 /// - On first conversation, asynchronously request a title and emit `update_chat_title`.
+/// - Each item in `options` is written into the body by its `apply_*` helper.
+/// - Before a structured-output repair retry, `stream-reset` is emitted: text pushed so far is
+///   discarded, and only text pushed after the last reset is the final answer.
 #[command]
 pub async fn proxy_stream(
+    app: AppHandle,
     window: Window,
     mut body: Value,
-    model: String,
-    token: String,
+    mut model: String,
+    token: Option<String>,
+    options: Option<ProxyOptions>,
 ) -> Result<(), String> {
-    let url = "https://openrouter.ai/api/v1/chat/completions".to_string();
-    let client = Client::builder().build().map_err(|e| e.to_string())?;
-
+    let mut options = options.unwrap_or_default();
+    let key = resolve_key(
+        &app,
+        options.key_id.as_deref(),
+        options.conversation_id.as_deref(),
+        token.as_deref(),
+    )?;
+    app.state::<AttachmentStore>().inline(&mut body)?;
     // 首次对话：消息数为 2（user+system or user+assistant？按你的逻辑保持不变）
     // First interaction heuristic: messages length == 2.
     let is_first_interaction = body
@@ -97,72 +168,267 @@ pub async fn proxy_stream(
         .and_then(|msgs| msgs.as_array())
         .map(|msgs| msgs.len() == 2)
         .unwrap_or(false);
+    let title_source = is_first_interaction.then(|| body.clone());
 
-    body.as_object_mut()
-        .ok_or_else(|| "Body is not a JSON object".to_string())?
-        .insert("model".to_string(), Value::String(model.clone()));
+    if let Some(names) = &options.tools {
+        attach_tools(&app, &mut body, names);
+    }
+    apply_web_search(&app, &mut body, &mut model, options.web_search.take())?;
+    apply_file_parser(&app, &mut body, options.pdf_engine.take())?;
+    if let Some(conversation_id) = &options.conversation_id {
+        app.state::<FileAnnotationStore>().reattach(conversation_id, &mut body);
+    }
+    apply_provider_routing(
+        &app,
+        &mut body,
+        options.conversation_id.as_deref(),
+        options.provider.take(),
+    )?;
+    apply_cache_control(&app, &mut body, &model, options.prompt_cache.take());
+    let image_output = apply_image_modalities(&app, &mut body, &model).await;
+    let sources = match options.retrieval.take() {
+        Some(retrieval) => apply_retrieval(&app, &mut body, &retrieval).await?,
+        None => Vec::new(),
+    };
+    let structured = options.structured_output.take();
+    if let Some(output) = &structured {
+        apply_structured_output(&app, &mut body, &model, output).await?;
+    }
+    let mut repairs_left = structured.as_ref().map_or(0, |o| o.repair_attempts);
+    let max_iterations = options
+        .max_tool_iterations
+        .unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS);
 
-    let response = client
-        .post(&url)
-        .bearer_auth(token.clone())
-        .json(&body)
-        .send()
+    let request_id = options.request_id.clone().unwrap_or_else(next_request_id);
+    // 发送前按模型校验参数 / validate parameters for the model before sending
+    let body = match validate_body(&app, &body, &model).await {
+        Ok(body) => body,
+        Err(message) => {
+            let failure = StreamFailure {
+                code: "invalid_request".into(),
+                message,
+                budget: None,
+            };
+            return Err(emit_stream_failure(&window, &request_id, failure));
+        }
+    };
+    let mut job = StreamJob::new(
+        &app,
+        request_id,
+        key,
+        model,
+        options.conversation_id.clone(),
+        Endpoint::Chat,
+        body,
+    )?;
+    // 请求 ID 可交给 `cancel_stream` / the request id is what `cancel_stream` takes
+    let _ = window.emit("stream-start", json!({ "request_id": job.request_id }));
+    if !sources.is_empty() {
+        // 注入的检索结果 / the injected retrieval hits
+        let _ = window.emit(
+            "stream-sources",
+            json!({ "request_id": job.request_id, "sources": sources }),
+        );
+    }
+
+    let mut iteration = 0;
+    let mut images = ImageOutputCollector::default();
+    let final_text = loop {
+        // 每轮请求前检查取消 / check for cancellation before every round
+        if is_cancelled(&app, &job) {
+            return emit_cancelled(&window, &job.request_id);
+        }
+        // 预算检查 / budget check
+        let warnings = check_budget(&app, &job)
+            .await
+            .map_err(|f| emit_stream_failure(&window, &job.request_id, f))?;
+        for warning in &warnings {
+            let _ = window.emit("budget-warning", warning);
+        }
+
+        let mut tool_calls = ToolCallAccumulator::default();
+        let mut annotations = AnnotationCollector::default();
+        let mut generated = Vec::new();
+        let output = execute(&app, &job, |chunk, text| {
+            tool_calls.feed(chunk);
+            annotations.feed(chunk);
+            if image_output {
+                generated.extend(images.feed(chunk));
+            }
+            if let Some(text) = text {
+                window.emit("stream-response", text.to_string()).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        })
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|f| emit_stream_failure(&window, &job.request_id, f))?;
 
-    let mut stream = response.bytes_stream();
-    let mut assistant_response = String::new();
+        if let Some(usage) = &output.usage {
+            let _ = window.emit("stream-usage", usage);
+        }
+        // 图片在流结束后再保存，不阻塞读流 / images are saved once the stream ends, off the read loop
+        for (index, url) in generated {
+            emit_generated_image(&app, &window, &job, index, url).await;
+        }
+        if let Some(conversation_id) = &job.conversation_id {
+            app.state::<FileAnnotationStore>()
+                .remember(conversation_id, annotations.take_files());
+        }
+        // 联网搜索的 `url_citation` 注解 / `url_citation` annotations from web search
+        let citations = annotations.take_citations();
+        if !citations.is_empty() {
+            let _ = window.emit(
+                "stream-citations",
+                json!({ "request_id": job.request_id, "citations": citations }),
+            );
+        }
+        if output.cancelled {
+            return emit_cancelled(&window, &job.request_id);
+        }
 
-    while let Some(item) = stream.next().await {
-        match item {
-            Ok(chunk) => {
-                let text_chunk = String::from_utf8_lossy(&chunk);
-                for line in text_chunk.split("\n\n") {
-                    if !line.starts_with("data: ") {
-                        continue;
-                    }
-                    let json_str = line.trim_start_matches("data: ");
-                    if json_str == "[DONE]" {
-                        window.emit("stream-response", json_str.to_string()).map_err(|e| e.to_string())?;
-
-                        if is_first_interaction {
-                            let title_body = create_title_body(&body, &assistant_response)?;
-                            spawn_fetch_chat_title(window.clone(), title_body, model.clone(), token.clone());
-                        }
-                        return Ok(());
-                    }
-                    if let Ok(json_value) = serde_json::from_str::<Value>(json_str) {
-                        if let Some(content) = json_value
-                            .get("choices")
-                            .and_then(|choices| choices.get(0))
-                            .and_then(|choice| choice.get("delta"))
-                            .and_then(|delta| delta.get("content"))
-                            .and_then(|content| content.as_str())
-                        {
-                            assistant_response.push_str(content);
-                            window.emit("stream-response", content.to_string()).map_err(|e| e.to_string())?;
-                        }
-                    }
-                }
+        let calls = tool_calls.take(&job.request_id);
+        if calls.is_empty() {
+            let Some(output_schema) = &structured else {
+                break output.text;
+            };
+            // 本地校验结果（schema 本身无效时同样推送 `stream-error`）
+            // Local validation result (an invalid schema also emits `stream-error`)
+            let check = check_output(&output.text, &output_schema.schema).map_err(|message| {
+                let failure = StreamFailure {
+                    code: "invalid_request".into(),
+                    message,
+                    budget: None,
+                };
+                emit_stream_failure(&window, &job.request_id, failure)
+            })?;
+            let _ = window.emit(
+                "stream-structured",
+                json!({ "request_id": job.request_id, "check": check }),
+            );
+            if check.valid || repairs_left == 0 {
+                break output.text;
             }
-            Err(e) => {
-                eprintln!("{}", e.to_string());
-                return Err(e.to_string());
+            // 要求模型修正后再请求一次 / ask the model to fix its answer and request again
+            repairs_left -= 1;
+            let _ = window.emit(
+                "stream-repair",
+                json!({ "request_id": job.request_id, "errors": check.errors }),
+            );
+            if let Some(messages) = job.body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+                messages.push(json!({ "role": "assistant", "content": output.text }));
+                messages.push(repair_message(&check));
             }
+            // 已推送的无效回答作废，修复后的回答从头推送
+            // The invalid answer already streamed is discarded; the repaired one streams from scratch
+            window
+                .emit("stream-reset", json!({ "request_id": job.request_id, "reason": "repair" }))
+                .map_err(|e| e.to_string())?;
+            continue;
         }
-    }
+        iteration += 1;
+        if iteration > max_iterations {
+            let failure = StreamFailure {
+                code: "tool_loop_limit".into(),
+                message: format!("Tool calling stopped after {} iterations", max_iterations),
+                budget: None,
+            };
+            return Err(emit_stream_failure(&window, &job.request_id, failure));
+        }
+
+        // 追加 assistant 的工具调用与各工具结果，再继续请求
+        // Append the assistant tool calls and each tool result, then continue
+        let mut appended = vec![json!({
+            "role": "assistant",
+            "content": if output.text.is_empty() { Value::Null } else { Value::String(output.text) },
+            "tool_calls": calls.iter().map(|c| c.to_message_part()).collect::<Vec<_>>(),
+        })];
+        for call in &calls {
+            appended.push(run_tool_call(&app, &window, &job.request_id, &job.cancel, call).await);
+            if is_cancelled(&app, &job) {
+                return emit_cancelled(&window, &job.request_id);
+            }
+        }
+        if let Some(messages) = job.body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+            messages.extend(appended);
+        }
+    };
 
-    // 兜底 DONE 事件 / emit final DONE as fallback
     window.emit("stream-response", "[DONE]".to_string()).map_err(|e| e.to_string())?;
 
-    if is_first_interaction {
-        let title_body = create_title_body(&body, &assistant_response)?;
-        spawn_fetch_chat_title(window.clone(), title_body, model.clone(), token.clone());
+    if let Some(source) = title_source {
+        let mut title_body = create_title_body(&source, &final_text)?;
+        // 标题请求带有完整对话，同样遵守供应商路由
+        // The title request carries the whole conversation, so it follows provider routing too
+        if let (Some(obj), Some(provider)) = (title_body.as_object_mut(), job.body.get("provider")) {
+            obj.insert("provider".into(), provider.clone());
+        }
+        spawn_fetch_chat_title(window.clone(), title_body, job.model.clone(), job.key.clone());
     }
 
     Ok(())
 }
 
+/// 请求是否已被 `cancel_stream` 取消（同时清除审批中的取消标记）
+/// Whether `cancel_stream` cancelled the request (also clears the flag set during approval).
+fn is_cancelled(app: &AppHandle, job: &StreamJob) -> bool {
+    let during_approval = app.state::<ApprovalManager>().take_cancelled(&job.request_id);
+    during_approval || job.cancel.is_cancelled()
+}
+
+/// 结束已取消的请求：`[DONE]` 后发送 `stream-cancelled`
+/// Finish a cancelled request: `[DONE]` followed by `stream-cancelled`.
+fn emit_cancelled(window: &Window, request_id: &str) -> Result<(), String> {
+    window.emit("stream-response", "[DONE]".to_string()).map_err(|e| e.to_string())?;
+    let _ = window.emit("stream-cancelled", json!({ "request_id": request_id }));
+    Ok(())
+}
+
+/// 保存生成的图片并发送 `stream-image`；无法保存时只带原始地址
+/// Save a generated image and emit `stream-image`; carries only the original URL when it
+/// can't be saved.
+///
+/// 解码、哈希与写盘（最多数十 MB）在阻塞线程池中进行。
+/// Decoding, hashing and writing (up to tens of MB) run on the blocking pool.
+async fn emit_generated_image(
+    app: &AppHandle,
+    window: &Window,
+    job: &StreamJob,
+    index: usize,
+    url: String,
+) {
+    let owner = job.conversation_id.clone().unwrap_or_else(|| job.request_id.clone());
+    let handle = app.clone();
+    let saved = tauri::async_runtime::spawn_blocking(move || {
+        let result = save_generated_image(&handle, &url, index, &owner);
+        (result, url)
+    })
+    .await;
+    let payload = match saved {
+        Ok((Ok(attachment), _)) => {
+            json!({ "request_id": job.request_id, "index": index, "attachment": attachment })
+        }
+        Ok((Err(e), url)) => {
+            warn!(request_id = %job.request_id, "Failed to save generated image: {}", e);
+            json!({ "request_id": job.request_id, "index": index, "url": url })
+        }
+        Err(e) => {
+            warn!(request_id = %job.request_id, "Failed to save generated image: {}", e);
+            json!({ "request_id": job.request_id, "index": index })
+        }
+    };
+    let _ = window.emit("stream-image", payload);
+}
+
+/// 发送 `stream-error` 事件并返回错误信息
+/// Emit a `stream-error` event and return the error message.
+fn emit_stream_failure(window: &Window, request_id: &str, failure: StreamFailure) -> String {
+    warn!(request_id = %request_id, code = %failure.code, "{}", failure.message);
+    let mut payload = serde_json::to_value(&failure).unwrap_or_else(|_| json!({}));
+    payload["request_id"] = Value::String(request_id.to_string());
+    let _ = window.emit("stream-error", payload);
+    failure.message
+}
+
 /// 生成用于标题生成的请求体（在原有 messages 末尾追加）
 /// Build request body for title generation (append to messages).
 ///
@@ -173,6 +439,7 @@ pub fn create_title_body(original_body: &Value, assistant_response: &str) -> Res
 
     if let Some(obj) = title_body.as_object_mut() {
         obj.remove("stream");
+        obj.remove("tools");
 
         if let Some(messages) = obj.get_mut("messages").and_then(|m| m.as_array_mut()) {
             let assistant_message = serde_json::json!({
@@ -194,14 +461,14 @@ pub fn create_title_body(original_body: &Value, assistant_response: &str) -> Res
 
 /// 后台异步任务：获取会话标题
 /// Spawn a background task to fetch the chat title.
-pub fn spawn_fetch_chat_title(window: Window, body: Value, model: String, token: String) {
+pub fn spawn_fetch_chat_title(window: Window, body: Value, model: String, key: ResolvedKey) {
     tauri::async_runtime::spawn(async move {
-        match fetch_chat_title(window, body, model, token).await {
+        match request_chat_title(&window, body, model, &key).await {
             Ok(_) => {
-                println!("Chat title generated successfully");
+                info!("Chat title generated successfully");
             }
             Err(e) => {
-                eprintln!("Failed to generate chat title: {}", e);
+                error!("Failed to generate chat title: {}", e);
             }
         }
     });
@@ -214,13 +481,33 @@ pub fn spawn_fetch_chat_title(window: Window, body: Value, model: String, token:
 /// On success, emits `update_chat_title` with full JSON payload.
 #[command]
 pub async fn fetch_chat_title(
+    app: AppHandle,
     window: Window,
+    body: Value,
+    model: String,
+    token: Option<String>,
+    options: Option<ProxyOptions>,
+) -> Result<Value, String> {
+    let options = options.unwrap_or_default();
+    let key = resolve_key(
+        &app,
+        options.key_id.as_deref(),
+        options.conversation_id.as_deref(),
+        token.as_deref(),
+    )?;
+    request_chat_title(&window, body, model, &key).await
+}
+
+/// 使用指定 Key 请求标题
+/// Request a title with the given key.
+async fn request_chat_title(
+    window: &Window,
     mut body: Value,
     model: String,
-    token: String,
+    key: &ResolvedKey,
 ) -> Result<Value, String> {
-    let url = "https://openrouter.ai/api/v1/chat/completions".to_string();
-    let client = Client::new();
+    let url = format!("{}/chat/completions", key.base_url);
+    let client = http_client();
 
     body.as_object_mut()
         .ok_or_else(|| "Body is not a JSON object".to_string())?
@@ -228,7 +515,7 @@ pub async fn fetch_chat_title(
 
     let response = client
         .post(&url)
-        .bearer_auth(token.to_string())
+        .bearer_auth(&key.token)
         .json(&body)
         .send()
         .await
@@ -0,0 +1,519 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{command, AppHandle, Manager, State};
+use tracing::{info, warn};
+
+use crate::helpers::now_millis;
+use crate::images::read_source;
+
+/// 附件引用的 URI 前缀
+/// URI prefix of attachment references.
+pub const ATTACHMENT_SCHEME: &str = "attachment://";
+
+/// 附件目录（位于应用数据目录）
+/// Attachment directory (in the app data dir).
+const ATTACHMENTS_DIR: &str = "attachments";
+/// 附件索引文件
+/// Attachment index file.
+const INDEX_FILE: &str = "index.json";
+/// 单个附件的最大字节数
+/// Max size of a single attachment.
+const MAX_ATTACHMENT_BYTES: usize = 100 * 1024 * 1024;
+/// 可内联附件引用的位置：(片段字段, 子字段, 是否为裸 base64)
+/// Where attachment references are inlined: (part field, subfield, raw base64).
+const INLINE_FIELDS: &[(&str, &str, bool)] = &[
+    ("image_url", "url", false),
+    ("file", "file_data", false),
+    ("input_audio", "data", true),
+];
+
+/// 附件元数据
+/// Attachment metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentMeta {
+    /// SHA-256（十六进制）/ SHA-256 (hex)
+    pub hash: String,
+    pub mime: String,
+    pub size: u64,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub created_at: u64,
+    /// 引用方（会话或消息 ID），为空即可回收
+    /// Referrers (conversation or message ids); collectable once empty
+    #[serde(default)]
+    pub refs: BTreeSet<String>,
+}
+
+/// 存入后返回给前端的信息
+/// Info returned to the frontend after storing.
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachmentInfo {
+    /// `attachment://<hash>`
+    pub uri: String,
+    #[serde(flatten)]
+    pub meta: AttachmentMeta,
+}
+
+/// 回收结果
+/// Garbage-collection report.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GcReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+/// 按 SHA-256 寻址的附件存储，带引用计数
+/// Content-addressed attachment store keyed by SHA-256, with refcounts.
+pub struct AttachmentStore {
+    dir: PathBuf,
+    /// 哈希 → 元数据 / hash → metadata
+    index: Mutex<HashMap<String, AttachmentMeta>>,
+    /// 索引损坏：索引外的文件可能仍被引用，不回收
+    /// The index was corrupt: files outside it may still be referenced, so they are kept
+    index_lost: bool,
+}
+
+/// 是否为附件文件（SHA-256 命名）或写入中断留下的临时文件
+/// Whether a file is a blob (named by its SHA-256) or a temp file left by an interrupted write.
+fn is_collectable(name: &str) -> bool {
+    let hash = name.strip_suffix(".tmp").unwrap_or(name);
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+impl AttachmentStore {
+    /// 从应用数据目录加载，并回收上次遗留的孤立文件
+    /// Load from the app data dir and collect blobs orphaned last run.
+    ///
+    /// 索引无法读取或解析时将其改名留存，并跳过回收，以免删除仍被引用的附件。
+    /// An unreadable or corrupt index is renamed aside and collection is skipped, so referenced
+    /// attachments are not deleted.
+    pub fn load(data_dir: PathBuf) -> Self {
+        let dir = data_dir.join(ATTACHMENTS_DIR);
+        let path = dir.join(INDEX_FILE);
+        let loaded = match std::fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.to_string()),
+        };
+        let index_lost = loaded.is_err();
+        let index = loaded.unwrap_or_else(|e| {
+            let aside = dir.join(format!("{}.corrupt-{}", INDEX_FILE, now_millis()));
+            warn!(
+                "Attachment index is unreadable ({}); kept as {}",
+                e,
+                aside.display()
+            );
+            let _ = std::fs::rename(&path, &aside);
+            HashMap::new()
+        });
+        let store = Self {
+            dir,
+            index: Mutex::new(index),
+            index_lost,
+        };
+        if index_lost {
+            return store;
+        }
+        let report = store.gc();
+        if report.removed > 0 {
+            info!(
+                removed = report.removed,
+                freed_bytes = report.freed_bytes,
+                "collected orphaned attachments"
+            );
+        }
+        store
+    }
+
+    fn save(&self, index: &HashMap<String, AttachmentMeta>) {
+        let result = serde_json::to_string(index)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+                let tmp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+                std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+                std::fs::rename(&tmp, self.dir.join(INDEX_FILE)).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to save attachment index: {}", e);
+        }
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(hash)
+    }
+
+    /// 存入内容并为 `owner` 增加引用；内容相同则复用已有文件
+    /// Store content and add a reference for `owner`; identical content reuses the blob.
+    pub fn put(
+        &self,
+        bytes: &[u8],
+        mime: String,
+        name: Option<String>,
+        owner: &str,
+    ) -> Result<AttachmentMeta, String> {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        let path = self.blob_path(&hash);
+        // 持锁写入，避免与回收并发时文件被当作孤立项删除
+        // Write under the lock so a concurrent gc can't take the blob for an orphan
+        let mut index = self.index.lock().unwrap();
+        if !path.is_file() {
+            std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+            let tmp = self.dir.join(format!("{}.tmp", hash));
+            std::fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+            std::fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+        }
+        let meta = index.entry(hash.clone()).or_insert_with(|| AttachmentMeta {
+            hash,
+            mime,
+            size: bytes.len() as u64,
+            name,
+            created_at: now_millis(),
+            refs: BTreeSet::new(),
+        });
+        meta.refs.insert(owner.to_string());
+        let meta = meta.clone();
+        self.save(&index);
+        Ok(meta)
+    }
+
+    /// 为 `owner` 增加引用（如消息被复制到另一会话）
+    /// Add a reference for `owner` (e.g. a message copied to another conversation).
+    pub fn retain(&self, hash: &str, owner: &str) -> Result<(), String> {
+        let mut index = self.index.lock().unwrap();
+        let meta = index
+            .get_mut(hash)
+            .ok_or_else(|| format!("Attachment not found: {}", hash))?;
+        if meta.refs.insert(owner.to_string()) {
+            self.save(&index);
+        }
+        Ok(())
+    }
+
+    /// 移除 `owner` 的引用；`hash` 为空时移除其全部引用。返回受影响的附件数
+    /// Drop `owner`'s reference; with no `hash`, all of its references. Returns how many changed.
+    pub fn release(&self, hash: Option<&str>, owner: &str) -> usize {
+        let mut index = self.index.lock().unwrap();
+        let released = index
+            .values_mut()
+            .filter(|meta| hash.is_none_or(|h| meta.hash == h))
+            .map(|meta| meta.refs.remove(owner))
+            .filter(|removed| *removed)
+            .count();
+        if released > 0 {
+            self.save(&index);
+        }
+        released
+    }
+
+    /// 读取附件内容与 MIME
+    /// Read an attachment's bytes and MIME type.
+    pub fn read(&self, hash: &str) -> Result<(Vec<u8>, String), String> {
+        let mime = self
+            .index
+            .lock()
+            .unwrap()
+            .get(hash)
+            .map(|meta| meta.mime.clone())
+            .ok_or_else(|| format!("Attachment not found: {}", hash))?;
+        let bytes = std::fs::read(self.blob_path(hash))
+            .map_err(|e| format!("Failed to read attachment {}: {}", hash, e))?;
+        Ok((bytes, mime))
+    }
+
+    /// 回收无引用的附件与索引外的文件
+    /// Collect unreferenced attachments and files missing from the index.
+    pub fn gc(&self) -> GcReport {
+        let mut index = self.index.lock().unwrap();
+        let before = index.len();
+        let mut unreferenced = Vec::new();
+        index.retain(|hash, meta| {
+            if meta.refs.is_empty() {
+                unreferenced.push(hash.clone());
+            }
+            !meta.refs.is_empty()
+        });
+        let mut report = GcReport::default();
+        if let Ok(entries) = std::fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !is_collectable(&name) || index.contains_key(&name) {
+                    continue;
+                }
+                if self.index_lost && !unreferenced.contains(&name) {
+                    continue;
+                }
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                if std::fs::remove_file(entry.path()).is_ok() {
+                    report.removed += 1;
+                    report.freed_bytes += size;
+                }
+            }
+        }
+        if index.len() != before {
+            self.save(&index);
+        }
+        report
+    }
+
+    /// 把请求体消息中的 `attachment://` 引用替换为内联数据
+    /// Replace `attachment://` references in the body's messages with inline data.
+    ///
+    /// 只处理内容片段的 `image_url.url`、`file.file_data`（替换为 data URL）与
+    /// `input_audio.data`（替换为裸 base64），普通文本不受影响。
+    /// Only `image_url.url`, `file.file_data` (given a data URL) and `input_audio.data` (given
+    /// raw base64) of content parts are rewritten; plain text is left alone.
+    pub fn inline(&self, body: &mut Value) -> Result<(), String> {
+        let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+            return Ok(());
+        };
+        let mut cache = HashMap::new();
+        let parts = messages
+            .iter_mut()
+            .filter_map(|m| m.get_mut("content").and_then(|c| c.as_array_mut()))
+            .flatten();
+        for part in parts {
+            for (field, key, raw_base64) in INLINE_FIELDS {
+                if let Some(Value::String(s)) = part.get_mut(*field).and_then(|f| f.get_mut(*key)) {
+                    self.inline_ref(s, *raw_base64, &mut cache)?;
+                }
+            }
+        }
+        if !cache.is_empty() {
+            info!(count = cache.len(), "inlined attachments");
+        }
+        Ok(())
+    }
+
+    fn inline_ref(
+        &self,
+        s: &mut String,
+        raw_base64: bool,
+        cache: &mut HashMap<String, (String, String)>,
+    ) -> Result<(), String> {
+        let Some(hash) = s.strip_prefix(ATTACHMENT_SCHEME) else {
+            return Ok(());
+        };
+        if !cache.contains_key(hash) {
+            let (bytes, mime) = self.read(hash)?;
+            cache.insert(hash.to_string(), (mime, STANDARD.encode(bytes)));
+        }
+        let (mime, data) = &cache[hash];
+        *s = if raw_base64 {
+            data.clone()
+        } else {
+            format!("data:{};base64,{}", mime, data)
+        };
+        Ok(())
+    }
+}
+
+/// 按扩展名推测 MIME
+/// Guess the MIME type from the extension.
+fn guess_mime(name: &str) -> &'static str {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "flac" => "audio/flac",
+        "ogg" => "audio/ogg",
+        "txt" | "md" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// 存入附件（data URL 或文件路径）并为 `owner` 增加引用
+/// Store an attachment (data URL or file path) and reference it from `owner`.
+#[command]
+pub async fn store_attachment(
+    app: AppHandle,
+    source: String,
+    owner: String,
+    name: Option<String>,
+    mime: Option<String>,
+) -> Result<AttachmentInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mime = mime
+            .or_else(|| {
+                source
+                    .strip_prefix("data:")
+                    .and_then(|rest| rest.split(';').next())
+                    .filter(|m| !m.is_empty())
+                    .map(String::from)
+            })
+            .unwrap_or_else(|| guess_mime(name.as_deref().unwrap_or(&source)).to_string());
+        let name = name.or_else(|| {
+            (!source.starts_with("data:"))
+                .then(|| Path::new(&source).file_name())
+                .flatten()
+                .map(|n| n.to_string_lossy().into_owned())
+        });
+        let bytes = read_source(&source, MAX_ATTACHMENT_BYTES)?;
+        let meta = app
+            .state::<AttachmentStore>()
+            .put(&bytes, mime, name, &owner)?;
+        Ok(AttachmentInfo {
+            uri: format!("{}{}", ATTACHMENT_SCHEME, meta.hash),
+            meta,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// 为附件增加引用方
+/// Add a referrer to an attachment.
+#[command]
+pub fn retain_attachment(
+    store: State<'_, AttachmentStore>,
+    hash: String,
+    owner: String,
+) -> Result<(), String> {
+    store.retain(&hash, &owner)
+}
+
+/// 移除引用方；不传 `hash` 时移除该引用方的全部附件（如删除会话）
+/// Remove a referrer; without `hash`, all of its attachments (e.g. a deleted conversation).
+#[command]
+pub fn release_attachments(
+    store: State<'_, AttachmentStore>,
+    owner: String,
+    hash: Option<String>,
+) -> usize {
+    store.release(hash.as_deref(), &owner)
+}
+
+/// 读取附件为 data URL（用于显示）
+/// Read an attachment as a data URL (for display).
+#[command]
+pub async fn get_attachment(app: AppHandle, hash: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (bytes, mime) = app.state::<AttachmentStore>().read(&hash)?;
+        Ok(format!("data:{};base64,{}", mime, STANDARD.encode(bytes)))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// 列出全部附件
+/// List all attachments.
+#[command]
+pub fn list_attachments(store: State<'_, AttachmentStore>) -> Vec<AttachmentMeta> {
+    let mut list: Vec<AttachmentMeta> = store.index.lock().unwrap().values().cloned().collect();
+    list.sort_by_key(|meta| std::cmp::Reverse(meta.created_at));
+    list
+}
+
+/// 回收无引用的附件
+/// Garbage-collect unreferenced attachments.
+#[command]
+pub fn gc_attachments(store: State<'_, AttachmentStore>) -> GcReport {
+    let report = store.gc();
+    info!(
+        removed = report.removed,
+        freed_bytes = report.freed_bytes,
+        "collected attachments"
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "sengine-attachments-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn inlines_only_attachment_fields() {
+        let store = AttachmentStore::load(temp_dir("inline"));
+        let meta = store
+            .put(b"abc", "image/png".into(), None, "conv-1")
+            .unwrap();
+        let uri = format!("{}{}", ATTACHMENT_SCHEME, meta.hash);
+        let mut body = json!({ "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "attachment://not-a-hash" },
+                { "type": "image_url", "image_url": { "url": uri } },
+                { "type": "input_audio", "input_audio": { "data": uri, "format": "wav" } },
+            ],
+        }, { "role": "user", "content": uri }] });
+        store.inline(&mut body).unwrap();
+        let parts = &body["messages"][0]["content"];
+        assert_eq!(parts[0]["text"], "attachment://not-a-hash");
+        assert_eq!(parts[1]["image_url"]["url"], "data:image/png;base64,YWJj");
+        assert_eq!(parts[2]["input_audio"]["data"], "YWJj");
+        assert_eq!(body["messages"][1]["content"], uri.as_str());
+    }
+
+    #[test]
+    fn unknown_attachment_fails() {
+        let store = AttachmentStore::load(temp_dir("unknown"));
+        let mut body = json!({ "messages": [{ "role": "user", "content": [
+            { "type": "file", "file": { "filename": "a.pdf", "file_data": "attachment://00" } },
+        ] }] });
+        assert!(store.inline(&mut body).is_err());
+    }
+
+    #[test]
+    fn corrupt_index_keeps_blobs() {
+        let data_dir = temp_dir("corrupt");
+        let blob = AttachmentStore::load(data_dir.clone())
+            .put(b"keep me", "text/plain".into(), None, "conv-1")
+            .unwrap()
+            .hash;
+        let dir = data_dir.join(ATTACHMENTS_DIR);
+        std::fs::write(dir.join(INDEX_FILE), "{ not json").unwrap();
+
+        let store = AttachmentStore::load(data_dir);
+        assert!(dir.join(&blob).is_file());
+        assert_eq!(store.gc().removed, 0);
+        assert!(dir.join(&blob).is_file());
+        let aside = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .any(|e| e.file_name().to_string_lossy().contains(".corrupt-"));
+        assert!(aside);
+    }
+
+    #[test]
+    fn gc_collects_unreferenced_blobs() {
+        let data_dir = temp_dir("gc");
+        let store = AttachmentStore::load(data_dir.clone());
+        let meta = store
+            .put(b"drop me", "text/plain".into(), None, "conv-1")
+            .unwrap();
+        store.release(None, "conv-1");
+        let report = store.gc();
+        assert_eq!(report.removed, 1);
+        assert!(!data_dir.join(ATTACHMENTS_DIR).join(meta.hash).exists());
+    }
+}
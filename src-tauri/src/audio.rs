@@ -0,0 +1,673 @@
+use std::io::Cursor;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tauri::{command, AppHandle};
+use tracing::info;
+
+use crate::images::read_source;
+use crate::settings::{read_setting, write_setting};
+
+/// 音频输入配置在 store 中的字段名
+/// Store field holding the audio input config.
+pub const AUDIO_INPUT_FIELD: &str = "audio_input";
+
+/// 输入音频的最大字节数
+/// Max size of an input audio file.
+const MAX_INPUT_BYTES: usize = 100 * 1024 * 1024;
+/// 时长上限的最大值（48 kHz 立体声约 460 MB 样本）
+/// Highest allowed duration limit (about 460 MB of samples at 48 kHz stereo).
+const MAX_DURATION_SECS: u32 = 1200;
+
+/// 音频输入配置
+/// Audio input config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioInputConfig {
+    /// 最长时长（秒），超出部分被截去 / max duration (seconds); the rest is cut
+    #[serde(default = "default_max_duration_secs")]
+    pub max_duration_secs: u32,
+    /// 转码输出的采样率 / sample rate of transcoded output
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// 转码时混为单声道 / downmix to mono when transcoding
+    #[serde(default = "default_mono")]
+    pub mono: bool,
+}
+
+fn default_max_duration_secs() -> u32 {
+    600
+}
+
+fn default_sample_rate() -> u32 {
+    16_000
+}
+
+fn default_mono() -> bool {
+    true
+}
+
+impl Default for AudioInputConfig {
+    fn default() -> Self {
+        Self {
+            max_duration_secs: default_max_duration_secs(),
+            sample_rate: default_sample_rate(),
+            mono: default_mono(),
+        }
+    }
+}
+
+impl AudioInputConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_DURATION_SECS).contains(&self.max_duration_secs) {
+            return Err(format!(
+                "max_duration_secs must be between 1 and {}",
+                MAX_DURATION_SECS
+            ));
+        }
+        if !(8_000..=48_000).contains(&self.sample_rate) {
+            return Err("sample_rate must be between 8000 and 48000".into());
+        }
+        Ok(())
+    }
+}
+
+/// 单次调用覆盖的参数
+/// Per-call overrides.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AudioOptions {
+    /// 目标模型，用于选择可接受的格式 / target model, for the accepted formats
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 文件名，辅助探测 / file name, a probing hint
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// 覆盖模型可接受的格式 / override the formats the model accepts
+    #[serde(default)]
+    pub accepted_formats: Option<Vec<String>>,
+    #[serde(default)]
+    pub max_duration_secs: Option<u32>,
+}
+
+/// 处理后的音频，可直接作为 `input_audio` 内容块
+/// A processed audio clip, ready for an `input_audio` part.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedAudio {
+    /// base64 数据 / base64 data
+    pub data: String,
+    /// `input_audio.format`
+    pub format: String,
+    /// 探测到的容器 / probed container
+    pub source_format: Option<String>,
+    /// 探测到的编码 / probed codec
+    pub codec: Option<String>,
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub duration_secs: f64,
+    pub original_bytes: usize,
+    pub bytes: usize,
+    /// 是否经过转码 / whether it was transcoded
+    pub transcoded: bool,
+    /// 是否因时长上限被截断 / whether the duration limit cut it
+    pub truncated: bool,
+}
+
+/// 按文件头识别容器
+/// Identify the container from the file header.
+fn sniff_container(bytes: &[u8]) -> Option<&'static str> {
+    let head = |n: usize| bytes.get(..n).unwrap_or(&[]);
+    if head(4) == b"RIFF" && bytes.get(8..12) == Some(b"WAVE") {
+        Some("wav")
+    } else if head(4) == b"FORM" && matches!(bytes.get(8..12), Some(b"AIFF") | Some(b"AIFC")) {
+        Some("aiff")
+    } else if head(4) == b"fLaC" {
+        Some("flac")
+    } else if head(4) == b"OggS" {
+        Some("ogg")
+    } else if bytes.get(4..8) == Some(b"ftyp") {
+        Some("m4a")
+    } else if head(3) == b"ID3" {
+        Some("mp3")
+    } else if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xF6 == 0xF0 {
+        // ADTS 同步字（layer 位为 0）/ ADTS sync word (layer bits zero)
+        Some("aac")
+    } else if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0 {
+        Some("mp3")
+    } else {
+        None
+    }
+}
+
+/// 模型可接受的 `input_audio` 格式
+/// `input_audio` formats a model accepts.
+fn accepted_formats(model: Option<&str>) -> Vec<String> {
+    let model = model.unwrap_or("").to_ascii_lowercase();
+    let formats: &[&str] = if model.starts_with("google/") || model.contains("gemini") {
+        &["wav", "mp3", "aiff", "aac", "ogg", "flac"]
+    } else {
+        &["wav", "mp3"]
+    };
+    formats.iter().map(|f| f.to_string()).collect()
+}
+
+/// 解码结果；`samples` 为输出采样率与声道数下的交错 f32 样本
+/// Decode result; `samples` are interleaved f32 at the output rate and channel count.
+struct Decoded {
+    codec: Option<String>,
+    /// 源采样率与声道数 / source rate and channels
+    sample_rate: u32,
+    channels: usize,
+    output_rate: u32,
+    output_channels: usize,
+    samples: Vec<f32>,
+    /// 总时长，未知时为空 / total duration, if known
+    duration_secs: Option<f64>,
+    truncated: bool,
+}
+
+/// 探测并解码；`decode_limit` 为 0 时只读取流参数
+/// Probe and decode; with a `decode_limit` of 0 only the stream parameters are read.
+///
+/// 每个包解码后即按 `config` 混音、重采样，不保留整段源样本。
+/// Each packet is downmixed and resampled per `config` as it is decoded, so the whole source
+/// is never held in memory.
+fn decode(
+    bytes: Vec<u8>,
+    extension: Option<&str>,
+    decode_limit: f64,
+    config: &AudioInputConfig,
+) -> Result<Decoded, String> {
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = extension {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("Unsupported audio: {}", e))?;
+    let mut format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No audio track found")?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let codec = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map(|c| c.short_name.to_string());
+    let mut sample_rate = params.sample_rate.unwrap_or(0);
+    let mut channels = params.channels.map(|c| c.count()).unwrap_or(0);
+    let duration_secs = match (params.n_frames, params.sample_rate) {
+        (Some(frames), Some(rate)) if rate > 0 => Some(frames as f64 / rate as f64),
+        _ => None,
+    };
+
+    let mut resampler: Option<Resampler> = None;
+    let mut frames = 0usize;
+    let mut truncated = false;
+    if decode_limit > 0.0 {
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .map_err(|e| format!("Unsupported codec: {}", e))?;
+        let mut interleaved: Option<SampleBuffer<f32>> = None;
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    break
+                }
+                Err(DecodeError::ResetRequired) => break,
+                Err(e) => return Err(e.to_string()),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let buffer = match decoder.decode(&packet) {
+                Ok(buffer) => buffer,
+                // 跳过损坏的帧 / skip corrupt frames
+                Err(DecodeError::DecodeError(_)) => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            let spec = *buffer.spec();
+            let resampler = match &mut resampler {
+                Some(r) if r.from != spec.rate || r.source_channels != spec.channels.count() => {
+                    return Err("Audio changes sample rate or channels mid-stream".into());
+                }
+                Some(r) => r,
+                None => {
+                    sample_rate = spec.rate;
+                    channels = spec.channels.count();
+                    let output_channels = if config.mono { 1 } else { channels };
+                    let output_rate = config.sample_rate.min(sample_rate);
+                    resampler.insert(Resampler::new(
+                        channels,
+                        output_channels,
+                        sample_rate,
+                        output_rate,
+                    ))
+                }
+            };
+            let interleaved = match &mut interleaved {
+                Some(b) if b.capacity() >= buffer.capacity() * channels => b,
+                _ => interleaved.insert(SampleBuffer::<f32>::new(buffer.capacity() as u64, spec)),
+            };
+            interleaved.copy_interleaved_ref(buffer);
+
+            let limit = (decode_limit * sample_rate as f64) as usize;
+            let packet_frames = interleaved.samples().len() / channels.max(1);
+            let take = packet_frames.min(limit.saturating_sub(frames));
+            resampler.push(&interleaved.samples()[..take * channels]);
+            frames += packet_frames;
+            if frames >= limit {
+                truncated = frames > limit || duration_secs.is_none_or(|d| d > decode_limit);
+                break;
+            }
+        }
+        if sample_rate == 0 || channels == 0 {
+            return Err("Audio has no decodable samples".into());
+        }
+    }
+
+    let (output_rate, output_channels, samples) = match resampler {
+        Some(r) => (r.to, r.output_channels, r.finish()),
+        None => (sample_rate, channels, Vec::new()),
+    };
+    Ok(Decoded {
+        codec,
+        sample_rate,
+        channels,
+        output_rate,
+        output_channels,
+        samples,
+        duration_secs,
+        truncated,
+    })
+}
+
+/// 混为单声道
+/// Downmix to mono.
+fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// 逐包混音与重采样
+/// Downmixes and resamples packet by packet.
+///
+/// 线性插值重采样；降采样前先做盒式低通，减少混叠。只保留插值所需的尾部帧。
+/// Linear resampling; downsampling box-filters first to limit aliasing. Only the frames the
+/// interpolation still needs are kept.
+struct Resampler {
+    source_channels: usize,
+    output_channels: usize,
+    from: u32,
+    to: u32,
+    ratio: f64,
+    /// 盒式低通的宽度（帧）/ box filter width (frames)
+    width: usize,
+    /// 尚需使用的源帧（已混音）/ source frames still needed (downmixed)
+    pending: Vec<f32>,
+    /// 已丢弃的源帧数 / source frames dropped so far
+    offset: usize,
+    /// 已输出的帧数 / frames produced so far
+    produced: usize,
+    out: Vec<f32>,
+}
+
+impl Resampler {
+    fn new(source_channels: usize, output_channels: usize, from: u32, to: u32) -> Self {
+        let ratio = from as f64 / to as f64;
+        Self {
+            source_channels,
+            output_channels,
+            from,
+            to,
+            ratio,
+            width: (ratio.ceil() as usize).max(1),
+            pending: Vec::new(),
+            offset: 0,
+            produced: 0,
+            out: Vec::new(),
+        }
+    }
+
+    /// 追加交错的源样本
+    /// Push interleaved source samples.
+    fn push(&mut self, samples: &[f32]) {
+        if self.output_channels == self.source_channels {
+            self.pending.extend_from_slice(samples);
+        } else {
+            self.pending.extend(downmix(samples, self.source_channels));
+        }
+        self.run(false);
+    }
+
+    /// 处理剩余帧并返回全部输出
+    /// Process the remaining frames and return all output.
+    fn finish(mut self) -> Vec<f32> {
+        self.run(true);
+        self.out
+    }
+
+    /// 源帧 `frame` 在声道 `ch` 上低通后的值 / low-passed value of source `frame` on `ch`
+    fn filtered(&self, frame: usize, ch: usize, frames: usize) -> f32 {
+        let channels = self.output_channels;
+        let end = (frame + self.width).min(frames);
+        let sum: f32 = (frame..end)
+            .map(|f| self.pending[(f - self.offset) * channels + ch])
+            .sum();
+        sum / (end - frame) as f32
+    }
+
+    fn run(&mut self, last: bool) {
+        let channels = self.output_channels;
+        let frames = self.offset + self.pending.len() / channels;
+        if self.from == self.to {
+            self.out.append(&mut self.pending);
+            self.offset = frames;
+            return;
+        }
+        let total = (frames as f64 / self.ratio) as usize;
+        loop {
+            let pos = self.produced as f64 * self.ratio;
+            let index = pos as usize;
+            // 还未到齐的帧等下一个包 / frames not yet decoded wait for the next packet
+            if (last && self.produced >= total) || (!last && index + 1 + self.width > frames) {
+                break;
+            }
+            let frac = (pos - index as f64) as f32;
+            for ch in 0..channels {
+                let a = self.filtered(index, ch, frames);
+                let b = if index + 1 < frames {
+                    self.filtered(index + 1, ch, frames)
+                } else {
+                    a
+                };
+                self.out.push(a + (b - a) * frac);
+            }
+            self.produced += 1;
+        }
+        // 丢弃之后不再用到的帧 / drop frames no longer needed
+        let keep_from = ((self.produced as f64 * self.ratio) as usize).min(frames);
+        if keep_from > self.offset {
+            self.pending.drain(..(keep_from - self.offset) * channels);
+            self.offset = keep_from;
+        }
+    }
+}
+
+/// 一次性重采样整段样本
+/// Resample a whole clip at once.
+#[cfg(test)]
+fn resample(samples: &[f32], channels: usize, from: u32, to: u32) -> Vec<f32> {
+    let mut resampler = Resampler::new(channels, channels, from, to);
+    resampler.push(samples);
+    resampler.finish()
+}
+
+/// 编码为 16 位 PCM WAV
+/// Encode as 16-bit PCM WAV.
+fn encode_wav(samples: &[f32], channels: usize, sample_rate: u32) -> Result<Vec<u8>, String> {
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut out = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut out, spec).map_err(|e| e.to_string())?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(value).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+/// 执行转码（阻塞调用）
+/// Run the transcoder (blocking).
+fn prepare_blocking(
+    source: &str,
+    config: &AudioInputConfig,
+    options: &AudioOptions,
+) -> Result<PreparedAudio, String> {
+    let bytes = read_source(source, MAX_INPUT_BYTES)?;
+    let original_bytes = bytes.len();
+    let container = sniff_container(&bytes);
+    let extension = container.map(String::from).or_else(|| {
+        options
+            .file_name
+            .as_deref()
+            .and_then(|n| n.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+    });
+    let accepted = options
+        .accepted_formats
+        .clone()
+        .unwrap_or_else(|| accepted_formats(options.model.as_deref()));
+    let max_duration = options
+        .max_duration_secs
+        .unwrap_or(config.max_duration_secs)
+        .clamp(1, MAX_DURATION_SECS) as f64;
+
+    // 格式已被接受且时长已知未超限时原样发送
+    // Send as-is when the format is accepted and the known duration is within the limit
+    if let Some(container) = container.filter(|c| accepted.iter().any(|a| a == c)) {
+        let probe = decode(bytes.clone(), Some(container), 0.0, config)?;
+        if let Some(duration) = probe.duration_secs.filter(|d| *d <= max_duration) {
+            return Ok(PreparedAudio {
+                data: STANDARD.encode(&bytes),
+                format: container.to_string(),
+                source_format: Some(container.to_string()),
+                codec: probe.codec,
+                sample_rate: probe.sample_rate,
+                channels: probe.channels,
+                duration_secs: duration,
+                original_bytes,
+                bytes: original_bytes,
+                transcoded: false,
+                truncated: false,
+            });
+        }
+    }
+
+    if !accepted.iter().any(|a| a == "wav") {
+        return Err(format!(
+            "Cannot transcode to any accepted format ({})",
+            accepted.join(", ")
+        ));
+    }
+    let decoded = decode(bytes, extension.as_deref(), max_duration, config)?;
+    let (samples, channels, sample_rate) = (
+        decoded.samples,
+        decoded.output_channels,
+        decoded.output_rate,
+    );
+    let wav = encode_wav(&samples, channels, sample_rate)?;
+
+    Ok(PreparedAudio {
+        data: STANDARD.encode(&wav),
+        format: "wav".into(),
+        source_format: container.map(String::from),
+        codec: decoded.codec,
+        sample_rate,
+        channels,
+        duration_secs: samples.len() as f64 / channels as f64 / sample_rate as f64,
+        original_bytes,
+        bytes: wav.len(),
+        transcoded: true,
+        truncated: decoded.truncated,
+    })
+}
+
+/// 把音频转为目标模型可接受的 `input_audio`（wav/mp3/flac/ogg/m4a/aac 输入）
+/// Turn audio into an `input_audio` the target model accepts (wav/mp3/flac/ogg/m4a/aac input).
+///
+/// `source` 为 data URL 或文件路径。已被接受的格式原样发送，否则解码、
+/// 重采样/混音并编码为 WAV，超出时长上限的部分被截去。
+/// `source` is a data URL or a file path. Accepted formats pass through; anything else
+/// is decoded, resampled/downmixed and encoded as WAV, cut at the duration limit.
+#[command]
+pub async fn prepare_audio(
+    app: AppHandle,
+    source: String,
+    options: Option<AudioOptions>,
+) -> Result<PreparedAudio, String> {
+    let config: AudioInputConfig = read_setting(&app, AUDIO_INPUT_FIELD).unwrap_or_default();
+    let options = options.unwrap_or_default();
+    let prepared =
+        tauri::async_runtime::spawn_blocking(move || prepare_blocking(&source, &config, &options))
+            .await
+            .map_err(|e| e.to_string())??;
+    info!(
+        source_format = ?prepared.source_format,
+        format = %prepared.format,
+        transcoded = prepared.transcoded,
+        truncated = prepared.truncated,
+        duration = prepared.duration_secs,
+        "prepared audio"
+    );
+    Ok(prepared)
+}
+
+/// 读取音频输入配置
+/// Get the audio input config.
+#[command]
+pub fn get_audio_input_config(app: AppHandle) -> AudioInputConfig {
+    read_setting(&app, AUDIO_INPUT_FIELD).unwrap_or_default()
+}
+
+/// 保存音频输入配置
+/// Save the audio input config.
+#[command]
+pub fn save_audio_input_config(app: AppHandle, config: AudioInputConfig) -> Result<(), String> {
+    config.validate()?;
+    write_setting(&app, AUDIO_INPUT_FIELD, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_containers_from_headers() {
+        let riff = |kind: &[u8; 4]| [b"RIFF".as_slice(), &[0; 4], kind].concat();
+        assert_eq!(sniff_container(&riff(b"WAVE")), Some("wav"));
+        assert_eq!(sniff_container(&riff(b"AVI ")), None);
+        let form = [b"FORM".as_slice(), &[0; 4], b"AIFC"].concat();
+        assert_eq!(sniff_container(&form), Some("aiff"));
+        assert_eq!(sniff_container(b"fLaC\0\0"), Some("flac"));
+        assert_eq!(sniff_container(b"OggS\0\0"), Some("ogg"));
+        assert_eq!(sniff_container(b"\0\0\0\x20ftypM4A "), Some("m4a"));
+        assert_eq!(sniff_container(b"ID3\x04"), Some("mp3"));
+        assert_eq!(sniff_container(&[0xFF, 0xF1, 0x50]), Some("aac"));
+        assert_eq!(sniff_container(&[0xFF, 0xFB, 0x90]), Some("mp3"));
+        assert_eq!(sniff_container(b"RIFF"), None);
+        assert_eq!(sniff_container(&[0xFF]), None);
+        assert_eq!(sniff_container(b""), None);
+    }
+
+    #[test]
+    fn downmix_averages_frames() {
+        assert_eq!(downmix(&[0.5, -0.5, 1.0, 0.0], 2), [0.0, 0.5]);
+        assert_eq!(downmix(&[0.25, 0.5, 0.75], 3), [0.5]);
+        assert_eq!(downmix(&[0.1, 0.2], 1), [0.1, 0.2]);
+    }
+
+    #[test]
+    fn resample_keeps_level_and_length() {
+        let tone: Vec<f32> = (0..4800).map(|i| (i as f32 * 0.01).sin()).collect();
+        assert_eq!(resample(&tone, 1, 16_000, 16_000), tone);
+
+        let constant = vec![0.25f32; 2 * 4800];
+        let down = resample(&constant, 2, 48_000, 16_000);
+        assert_eq!(down.len(), 2 * 1600);
+        assert!(down.iter().all(|s| (s - 0.25).abs() < 1e-6));
+
+        let up = resample(&tone, 1, 8_000, 16_000);
+        assert_eq!(up.len(), 9600);
+        // 插值点位于相邻样本之间 / interpolated points sit between their neighbours
+        assert_eq!(up[10], tone[5]);
+        assert!((up[11] - (tone[5] + tone[6]) / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn packetwise_resampling_matches_one_pass() {
+        let samples: Vec<f32> = (0..2 * 44_100)
+            .map(|i| ((i / 2) as f32 * 0.003).sin() * if i % 2 == 0 { 1.0 } else { 0.5 })
+            .collect();
+        for (source, output, to) in [(2, 2, 16_000), (2, 1, 16_000), (2, 2, 48_000)] {
+            let mut whole = Resampler::new(source, output, 44_100, to);
+            whole.push(&samples);
+            let whole = whole.finish();
+            let mut packets = Resampler::new(source, output, 44_100, to);
+            for packet in samples.chunks(2 * 1152) {
+                packets.push(packet);
+                // 只保留少量尾部帧 / only a short tail is kept
+                assert!(packets.pending.len() < 2 * 1152 + 8);
+            }
+            assert_eq!(packets.finish(), whole);
+        }
+    }
+
+    fn wav(seconds: u32, rate: u32, channels: u16) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate: rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut out = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut out, spec).unwrap();
+        for i in 0..seconds * rate * channels as u32 {
+            writer
+                .write_sample(if i % 2 == 0 { 8192i16 } else { -8192 })
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn decode_downmixes_resamples_and_cuts() {
+        let config = AudioInputConfig::default();
+        let decoded = decode(wav(3, 32_000, 2), Some("wav"), 2.0, &config).unwrap();
+        assert_eq!((decoded.sample_rate, decoded.channels), (32_000, 2));
+        assert_eq!((decoded.output_rate, decoded.output_channels), (16_000, 1));
+        assert_eq!(decoded.samples.len(), 2 * 16_000);
+        assert!(decoded.samples.iter().all(|s| s.abs() < 1e-6));
+        assert!(decoded.truncated);
+        assert_eq!(decoded.duration_secs, Some(3.0));
+
+        let stereo = AudioInputConfig {
+            mono: false,
+            ..config
+        };
+        let decoded = decode(wav(1, 8_000, 2), Some("wav"), 5.0, &stereo).unwrap();
+        assert_eq!((decoded.output_rate, decoded.output_channels), (8_000, 2));
+        assert_eq!(decoded.samples.len(), 2 * 8_000);
+        assert!(!decoded.truncated);
+
+        let probe = decode(wav(1, 8_000, 1), Some("wav"), 0.0, &stereo).unwrap();
+        assert!(probe.samples.is_empty());
+        assert_eq!(probe.sample_rate, 8_000);
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, AppHandle, State};
+
+use crate::models::{pricing_of, ModelCatalog};
+use crate::settings::{read_setting, write_setting};
+
+/// 预算规则在 store 中的字段名
+/// Store field holding the budget rules.
+pub const BUDGET_RULES_FIELD: &str = "budget_rules";
+
+/// 账本文件名（位于应用数据目录）
+/// Ledger file name (inside the app data dir).
+const LEDGER_FILE: &str = "budget_ledger.json";
+/// 保留的日账本/月账本数量
+/// Number of daily/monthly buckets kept.
+const KEEP_DAYS: usize = 62;
+const KEEP_MONTHS: usize = 24;
+/// 请求与模型都未给出输出上限时，估算所用的输出 Token 数
+/// Completion tokens assumed by the estimate when neither the request nor the model gives a limit.
+const DEFAULT_COMPLETION_TOKENS: f64 = 4096.0;
+
+/// 预算周期
+/// Budget period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    Daily,
+    Monthly,
+}
+
+/// 预算作用范围
+/// Budget scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    /// 全部花费 / all spend
+    Global,
+    /// 单个 Key / a single key
+    Key,
+    /// 单个模型 / a single model
+    Model,
+}
+
+/// 预算规则：软警告与硬上限（美元）
+/// Budget rule: soft warning and hard cap (USD).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetRule {
+    pub id: String,
+    pub scope: BudgetScope,
+    /// Key ID 或模型 ID（`global` 时为空）/ key id or model id (empty for `global`)
+    #[serde(default)]
+    pub target: Option<String>,
+    pub period: BudgetPeriod,
+    #[serde(default)]
+    pub soft_limit: Option<f64>,
+    #[serde(default)]
+    pub hard_limit: Option<f64>,
+}
+
+/// 某条规则的当前状态
+/// Current state of a rule.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    pub rule: BudgetRule,
+    /// 本周期已花费 / spent in the current period
+    pub spent: f64,
+    /// 距硬上限剩余 / remaining before the hard cap
+    pub remaining: Option<f64>,
+    pub soft_exceeded: bool,
+    pub hard_exceeded: bool,
+}
+
+/// 请求前检查结果
+/// Result of the pre-request check.
+#[derive(Debug, Default)]
+pub struct BudgetCheck {
+    pub warnings: Vec<BudgetStatus>,
+    pub blocked: Option<BudgetStatus>,
+}
+
+/// 单个周期的花费
+/// Spend within one period.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LedgerBucket {
+    pub total: f64,
+    #[serde(default)]
+    pub by_key: HashMap<String, f64>,
+    #[serde(default)]
+    pub by_model: HashMap<String, f64>,
+}
+
+impl LedgerBucket {
+    fn spent(&self, scope: BudgetScope, target: Option<&str>) -> f64 {
+        match (scope, target) {
+            (BudgetScope::Global, _) => self.total,
+            (BudgetScope::Key, Some(t)) => self.by_key.get(t).copied().unwrap_or(0.0),
+            (BudgetScope::Model, Some(t)) => self.by_model.get(t).copied().unwrap_or(0.0),
+            _ => 0.0,
+        }
+    }
+
+    fn add(&mut self, key_id: &str, model: &str, cost: f64) {
+        self.total += cost;
+        *self.by_key.entry(key_id.to_string()).or_default() += cost;
+        *self.by_model.entry(model.to_string()).or_default() += cost;
+    }
+}
+
+/// 日/月账本
+/// Daily and monthly ledgers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    /// `YYYY-MM-DD` → 花费 / spend
+    #[serde(default)]
+    pub daily: BTreeMap<String, LedgerBucket>,
+    /// `YYYY-MM` → 花费 / spend
+    #[serde(default)]
+    pub monthly: BTreeMap<String, LedgerBucket>,
+}
+
+fn period_key(period: BudgetPeriod) -> String {
+    match period {
+        BudgetPeriod::Daily => Local::now().format("%Y-%m-%d").to_string(),
+        BudgetPeriod::Monthly => Local::now().format("%Y-%m").to_string(),
+    }
+}
+
+fn prune(map: &mut BTreeMap<String, LedgerBucket>, keep: usize) {
+    while map.len() > keep {
+        let oldest = map.keys().next().cloned();
+        match oldest {
+            Some(k) => map.remove(&k),
+            None => break,
+        };
+    }
+}
+
+/// 预算管理器：累计每条消息的花费并执行上限
+/// Budget manager: accumulates per-message cost and enforces caps.
+pub struct BudgetManager {
+    ledger: Mutex<Ledger>,
+    path: PathBuf,
+}
+
+impl BudgetManager {
+    /// 从应用数据目录加载账本
+    /// Load the ledger from the app data dir.
+    pub fn load(data_dir: PathBuf) -> Self {
+        let path = data_dir.join(LEDGER_FILE);
+        let ledger = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            ledger: Mutex::new(ledger),
+            path,
+        }
+    }
+
+    fn save(&self, ledger: &Ledger) {
+        let result = serde_json::to_string(ledger)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&self.path, json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to save budget ledger: {}", e);
+        }
+    }
+
+    fn spent(&self, rule: &BudgetRule) -> f64 {
+        let ledger = self.ledger.lock().unwrap();
+        let bucket = match rule.period {
+            BudgetPeriod::Daily => ledger.daily.get(&period_key(BudgetPeriod::Daily)),
+            BudgetPeriod::Monthly => ledger.monthly.get(&period_key(BudgetPeriod::Monthly)),
+        };
+        bucket
+            .map(|b| b.spent(rule.scope, rule.target.as_deref()))
+            .unwrap_or(0.0)
+    }
+
+    fn status(&self, rule: &BudgetRule, extra: f64) -> BudgetStatus {
+        let spent = self.spent(rule);
+        let projected = spent + extra;
+        BudgetStatus {
+            rule: rule.clone(),
+            spent,
+            remaining: rule.hard_limit.map(|h| (h - spent).max(0.0)),
+            soft_exceeded: rule.soft_limit.is_some_and(|s| projected >= s),
+            hard_exceeded: rule.hard_limit.is_some_and(|h| projected > h || spent >= h),
+        }
+    }
+
+    /// 请求前检查：`estimate` 为预计花费
+    /// Pre-request check; `estimate` is the expected cost.
+    pub fn check(
+        &self,
+        rules: &[BudgetRule],
+        key_id: &str,
+        model: &str,
+        estimate: f64,
+    ) -> BudgetCheck {
+        let mut check = BudgetCheck::default();
+        for rule in rules.iter().filter(|r| applies(r, key_id, model)) {
+            let status = self.status(rule, estimate);
+            if status.hard_exceeded {
+                if check.blocked.is_none() {
+                    check.blocked = Some(status);
+                }
+            } else if status.soft_exceeded {
+                check.warnings.push(status);
+            }
+        }
+        check
+    }
+
+    /// 记录一次请求的实际花费
+    /// Record the actual cost of a request.
+    pub fn record(&self, key_id: &str, model: &str, cost: f64) {
+        if cost <= 0.0 {
+            return;
+        }
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger
+            .daily
+            .entry(period_key(BudgetPeriod::Daily))
+            .or_default()
+            .add(key_id, model, cost);
+        ledger
+            .monthly
+            .entry(period_key(BudgetPeriod::Monthly))
+            .or_default()
+            .add(key_id, model, cost);
+        prune(&mut ledger.daily, KEEP_DAYS);
+        prune(&mut ledger.monthly, KEEP_MONTHS);
+        self.save(&ledger);
+    }
+}
+
+fn applies(rule: &BudgetRule, key_id: &str, model: &str) -> bool {
+    match rule.scope {
+        BudgetScope::Global => true,
+        BudgetScope::Key => rule.target.as_deref() == Some(key_id),
+        BudgetScope::Model => rule.target.as_deref().is_some_and(|t| {
+            t == model || model.rsplit_once(':').is_some_and(|(base, _)| base == t)
+        }),
+    }
+}
+
+/// 读取预算规则
+/// Load the budget rules.
+pub fn load_budget_rules(app: &AppHandle) -> Vec<BudgetRule> {
+    read_setting(app, BUDGET_RULES_FIELD).unwrap_or_default()
+}
+
+/// 估算请求花费：按消息或 `prompt` 的字符数粗估输入 Token，输出按 `max_tokens`
+/// （或 `max_completion_tokens`）计
+/// Estimate request cost: prompt tokens from the character count of the messages or `prompt`,
+/// completion from `max_tokens` (or `max_completion_tokens`).
+///
+/// 两者都未设置时按模型的 `top_provider.max_completion_tokens`（不超过剩余上下文）计，
+/// 都没有时按 [`DEFAULT_COMPLETION_TOKENS`] 计。
+/// Without either, the model's `top_provider.max_completion_tokens` (capped by the context
+/// left) is used, or [`DEFAULT_COMPLETION_TOKENS`] when that is unknown too.
+pub async fn estimate_cost(catalog: &ModelCatalog, model: &str, body: &Value) -> f64 {
+    match catalog.get(model).await {
+        Some(meta) => estimate_with(&meta, body),
+        None => 0.0,
+    }
+}
+
+/// 按模型元数据估算请求花费
+/// Estimate request cost from the model metadata.
+fn estimate_with(meta: &Value, body: &Value) -> f64 {
+    let pricing = pricing_of(meta);
+
+    let mut chars = 0usize;
+    if let Some(messages) = body.get("messages").and_then(|m| m.as_array()) {
+        for msg in messages {
+            match msg.get("content") {
+                Some(Value::String(s)) => chars += s.len(),
+                Some(Value::Array(parts)) => {
+                    chars += parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                        .map(str::len)
+                        .sum::<usize>();
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some(prompt) = body.get("prompt").and_then(|p| p.as_str()) {
+        chars += prompt.len();
+    }
+    let prompt_tokens = (chars / 4) as f64;
+    let requested = ["max_tokens", "max_completion_tokens"]
+        .iter()
+        .find_map(|field| body.get(*field).and_then(|m| m.as_f64()));
+    let completion_tokens = match requested {
+        Some(max_tokens) => max_tokens,
+        None => {
+            let limit = meta
+                .get("top_provider")
+                .and_then(|p| p.get("max_completion_tokens"))
+                .and_then(|m| m.as_f64())
+                .unwrap_or(DEFAULT_COMPLETION_TOKENS);
+            match meta.get("context_length").and_then(|c| c.as_f64()) {
+                Some(context) => limit.min((context - prompt_tokens).max(0.0)),
+                None => limit,
+            }
+        }
+    };
+
+    prompt_tokens * pricing.prompt + completion_tokens * pricing.completion + pricing.request
+}
+
+/// 列出预算规则及当前花费
+/// List budget rules with current spend.
+#[command]
+pub fn list_budgets(app: AppHandle, manager: State<'_, BudgetManager>) -> Vec<BudgetStatus> {
+    load_budget_rules(&app)
+        .iter()
+        .map(|rule| manager.status(rule, 0.0))
+        .collect()
+}
+
+/// 保存预算规则
+/// Save the budget rules.
+#[command]
+pub fn save_budget_rules(
+    app: AppHandle,
+    manager: State<'_, BudgetManager>,
+    rules: Vec<BudgetRule>,
+) -> Result<Vec<BudgetStatus>, String> {
+    for rule in &rules {
+        if rule.scope != BudgetScope::Global && rule.target.as_deref().unwrap_or("").is_empty() {
+            return Err(format!("Budget rule {} has no target", rule.id));
+        }
+    }
+    write_setting(&app, BUDGET_RULES_FIELD, &rules)?;
+    Ok(rules.iter().map(|rule| manager.status(rule, 0.0)).collect())
+}
+
+/// 获取日/月账本
+/// Get the daily and monthly ledgers.
+#[command]
+pub fn get_budget_ledger(manager: State<'_, BudgetManager>) -> Ledger {
+    manager.ledger.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn meta(top_provider: Value, context_length: Value) -> Value {
+        json!({
+            "pricing": { "prompt": "0.000001", "completion": "0.00001", "request": "0" },
+            "top_provider": top_provider,
+            "context_length": context_length,
+        })
+    }
+
+    #[test]
+    fn completion_uses_max_tokens_when_set() {
+        let body = json!({ "messages": [{ "role": "user", "content": "x".repeat(400) }], "max_tokens": 100 });
+        let cost = estimate_with(&meta(json!({}), json!(1000)), &body);
+        assert!((cost - (100.0 * 0.000001 + 100.0 * 0.00001)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn completion_falls_back_to_the_model_limit() {
+        let body = json!({ "prompt": "x".repeat(400) });
+        let limited = meta(json!({ "max_completion_tokens": 2000 }), json!(100_000));
+        assert!((estimate_with(&limited, &body) - (0.0001 + 0.02)).abs() < 1e-12);
+        // 不超过剩余上下文 / capped by the context left
+        let small = meta(json!({ "max_completion_tokens": 2000 }), json!(600));
+        assert!((estimate_with(&small, &body) - (0.0001 + 0.005)).abs() < 1e-12);
+        let unknown = meta(json!({ "max_completion_tokens": null }), Value::Null);
+        assert!((estimate_with(&unknown, &body) - (0.0001 + 0.04096)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn completion_reads_max_completion_tokens_from_the_body() {
+        let body = json!({ "prompt": "x".repeat(400), "max_completion_tokens": 100 });
+        let limited = meta(json!({ "max_completion_tokens": 2000 }), json!(100_000));
+        assert!((estimate_with(&limited, &body) - (0.0001 + 0.001)).abs() < 1e-12);
+    }
+
+    fn manager(name: &str) -> (BudgetManager, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("sengine-budget-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        (BudgetManager::load(dir.clone()), dir)
+    }
+
+    fn rule(scope: BudgetScope, target: Option<&str>, period: BudgetPeriod) -> BudgetRule {
+        BudgetRule {
+            id: "r".into(),
+            scope,
+            target: target.map(str::to_string),
+            period,
+            soft_limit: Some(0.5),
+            hard_limit: Some(1.0),
+        }
+    }
+
+    #[test]
+    fn ledger_rolls_over_to_new_periods() {
+        let (manager, dir) = manager("rollover");
+        {
+            let mut ledger = manager.ledger.lock().unwrap();
+            let mut old = LedgerBucket::default();
+            old.add("k", "m", 50.0);
+            for day in 1..=KEEP_DAYS + 5 {
+                ledger
+                    .daily
+                    .insert(format!("2000-01-{:02}", day), old.clone());
+            }
+            ledger.monthly.insert("2000-01".into(), old);
+        }
+        let daily = rule(BudgetScope::Global, None, BudgetPeriod::Daily);
+        let monthly = rule(BudgetScope::Global, None, BudgetPeriod::Monthly);
+        // 旧周期的花费不计入当前周期 / spend of past periods doesn't count now
+        let check = manager.check(&[daily.clone(), monthly.clone()], "k", "m", 0.1);
+        assert!(check.blocked.is_none() && check.warnings.is_empty());
+
+        manager.record("k", "m", 0.25);
+        assert_eq!(manager.status(&daily, 0.0).spent, 0.25);
+        assert_eq!(manager.status(&monthly, 0.0).spent, 0.25);
+        let ledger = manager.ledger.lock().unwrap();
+        assert_eq!(ledger.daily.len(), KEEP_DAYS);
+        assert!(ledger.daily.contains_key(&period_key(BudgetPeriod::Daily)));
+        assert_eq!(ledger.monthly.len(), 2);
+        drop(ledger);
+
+        // 账本写入磁盘后可重新加载 / the saved ledger loads again
+        let reloaded = BudgetManager::load(dir.clone());
+        assert_eq!(reloaded.status(&daily, 0.0).spent, 0.25);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hard_cap_blocks_and_soft_limit_warns() {
+        let (manager, dir) = manager("caps");
+        manager.record("k", "openai/gpt-4o", 0.8);
+        let global = rule(BudgetScope::Global, None, BudgetPeriod::Daily);
+        let model = rule(
+            BudgetScope::Model,
+            Some("openai/gpt-4o"),
+            BudgetPeriod::Monthly,
+        );
+        let other_key = rule(BudgetScope::Key, Some("other"), BudgetPeriod::Daily);
+
+        let check = manager.check(
+            &[global.clone(), other_key.clone()],
+            "k",
+            "openai/gpt-4o",
+            0.1,
+        );
+        assert!(check.blocked.is_none());
+        assert_eq!(check.warnings.len(), 1);
+
+        // 预计花费越过硬上限即拒绝，`:variant` 后缀仍匹配模型规则
+        // Blocked once the estimate crosses the cap; a `:variant` suffix still matches the model rule
+        let check = manager.check(&[model, other_key], "k", "openai/gpt-4o:online", 0.3);
+        let blocked = check.blocked.unwrap();
+        assert!(blocked.hard_exceeded);
+        assert!((blocked.remaining.unwrap() - 0.2).abs() < 1e-12);
+
+        manager.record("k", "openai/gpt-4o", 0.2);
+        let check = manager.check(&[global], "k", "openai/gpt-4o", 0.0);
+        assert!(check.blocked.is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
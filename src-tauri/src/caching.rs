@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle};
+use tracing::debug;
+
+use crate::settings::{read_setting, write_setting};
+
+/// 提示缓存配置在 store 中的字段名
+/// Store field holding the prompt cache config.
+pub const PROMPT_CACHE_FIELD: &str = "prompt_cache";
+
+/// 单个请求最多的缓存断点（Anthropic 的上限）
+/// Max cache breakpoints per request (Anthropic's limit).
+const MAX_BREAKPOINTS: usize = 4;
+
+/// 提示缓存配置
+/// Prompt cache config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptCacheConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 文本达到此字符数才设断点 / min characters of text worth a breakpoint
+    #[serde(default = "default_min_chars")]
+    pub min_chars: usize,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_min_chars() -> usize {
+    // 约 1024 Token，低于此值供应商不缓存
+    // About 1024 tokens; providers don't cache anything shorter
+    4096
+}
+
+impl Default for PromptCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            min_chars: default_min_chars(),
+        }
+    }
+}
+
+/// 需要显式 `cache_control` 断点的模型（其它供应商自动缓存）
+/// Models that need explicit `cache_control` breakpoints (other providers cache automatically).
+fn needs_breakpoints(model: &str) -> bool {
+    let model = model.to_ascii_lowercase();
+    model.starts_with("anthropic/")
+        || model.contains("claude")
+        || model.starts_with("google/gemini")
+}
+
+/// 内容片段的大小：文本按字符数，文件视为大块
+/// Size of a content part: text by characters, files count as large.
+fn part_size(part: &Value) -> usize {
+    match part.get("type").and_then(|t| t.as_str()) {
+        Some("text") => part
+            .get("text")
+            .and_then(|t| t.as_str())
+            .map_or(0, str::len),
+        Some("file") => usize::MAX,
+        _ => 0,
+    }
+}
+
+/// 消息本身或其内容片段是否已带 `cache_control`
+/// Whether the message or one of its content parts already carries `cache_control`.
+fn has_cache_control(message: &Value) -> bool {
+    message.get("cache_control").is_some()
+        || message
+            .get("content")
+            .and_then(|c| c.as_array())
+            .is_some_and(|parts| parts.iter().any(|p| p.get("cache_control").is_some()))
+}
+
+/// 为 system 提示与大段附件设置缓存断点
+/// Mark cache breakpoints on the system prompt and large attachment parts.
+///
+/// 断点按消息顺序放在第一个足够大的 system 片段及之后的大片段上，最多 4 个；
+/// 请求体中已有 `cache_control` 时不做改动。
+/// Breakpoints go on the first large enough system parts and then on large parts in message
+/// order, at most 4; bodies that already carry `cache_control` are left alone.
+pub fn apply_cache_control(
+    app: &AppHandle,
+    body: &mut Value,
+    model: &str,
+    request: Option<PromptCacheConfig>,
+) {
+    let config = request
+        .or_else(|| read_setting(app, PROMPT_CACHE_FIELD))
+        .unwrap_or_default();
+    mark_breakpoints(body, model, &config);
+}
+
+fn mark_breakpoints(body: &mut Value, model: &str, config: &PromptCacheConfig) {
+    if !config.enabled || !needs_breakpoints(model) {
+        return;
+    }
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    if messages.iter().any(has_cache_control) {
+        return;
+    }
+
+    let mut marked = 0;
+    // system 消息优先 / system messages first
+    for system_pass in [true, false] {
+        for message in messages.iter_mut() {
+            if marked == MAX_BREAKPOINTS {
+                break;
+            }
+            let is_system = message.get("role").and_then(|r| r.as_str()) == Some("system");
+            if is_system != system_pass {
+                continue;
+            }
+            // 字符串内容转为片段才能附带断点 / string content must become parts to carry a breakpoint
+            if let Some(text) = message.get("content").and_then(|c| c.as_str()) {
+                if !is_system || text.len() < config.min_chars {
+                    continue;
+                }
+                message["content"] = json!([{ "type": "text", "text": text }]);
+            }
+            let Some(parts) = message.get_mut("content").and_then(|c| c.as_array_mut()) else {
+                continue;
+            };
+            if is_system {
+                // 标记最后一个片段即可缓存整段 system / the last part caches the whole system prompt
+                let total: usize = parts.iter().map(part_size).fold(0, usize::saturating_add);
+                if let Some(last) = parts.last_mut().filter(|_| total >= config.min_chars) {
+                    last["cache_control"] = json!({ "type": "ephemeral" });
+                    marked += 1;
+                }
+                continue;
+            }
+            for part in parts.iter_mut() {
+                if marked == MAX_BREAKPOINTS {
+                    break;
+                }
+                if part_size(part) >= config.min_chars {
+                    part["cache_control"] = json!({ "type": "ephemeral" });
+                    marked += 1;
+                }
+            }
+        }
+    }
+    if marked > 0 {
+        debug!(model = %model, breakpoints = marked, "prompt cache breakpoints");
+    }
+}
+
+/// 读取提示缓存配置
+/// Get the prompt cache config.
+#[command]
+pub fn get_prompt_cache_config(app: AppHandle) -> PromptCacheConfig {
+    read_setting(&app, PROMPT_CACHE_FIELD).unwrap_or_default()
+}
+
+/// 保存提示缓存配置
+/// Save the prompt cache config.
+#[command]
+pub fn save_prompt_cache_config(app: AppHandle, config: PromptCacheConfig) -> Result<(), String> {
+    write_setting(&app, PROMPT_CACHE_FIELD, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = "anthropic/claude-sonnet-4";
+
+    fn config(min_chars: usize) -> PromptCacheConfig {
+        PromptCacheConfig {
+            enabled: true,
+            min_chars,
+        }
+    }
+
+    fn text(text: &str) -> Value {
+        json!({ "type": "text", "text": text })
+    }
+
+    /// 带断点的 (消息, 片段) 位置 / (message, part) positions carrying a breakpoint
+    fn breakpoints(body: &Value) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        for (m, message) in body["messages"].as_array().unwrap().iter().enumerate() {
+            for (p, part) in message["content"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                if part.get("cache_control").is_some() {
+                    found.push((m, p));
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn system_prompt_is_marked_first() {
+        let long = "x".repeat(20);
+        let mut body = json!({ "messages": [
+            { "role": "user", "content": [text(&long), text(&long), text(&long)] },
+            { "role": "system", "content": long },
+            { "role": "system", "content": [text("short"), text(&long)] },
+        ] });
+        mark_breakpoints(&mut body, MODEL, &config(10));
+        // 两个 system 断点占用后，用户消息只剩两个 / with two system breakpoints, the user gets two
+        assert_eq!(breakpoints(&body), [(0, 0), (0, 1), (1, 0), (2, 1)]);
+        // 字符串 system 内容转为片段 / string system content becomes parts
+        assert_eq!(body["messages"][1]["content"][0]["text"], long);
+    }
+
+    #[test]
+    fn at_most_four_breakpoints() {
+        let long = "x".repeat(20);
+        let mut body = json!({ "messages": [
+            { "role": "system", "content": long },
+            { "role": "user", "content": [text(&long), text(&long), text(&long)] },
+            { "role": "user", "content": [text(&long), text(&long)] },
+        ] });
+        mark_breakpoints(&mut body, MODEL, &config(10));
+        assert_eq!(breakpoints(&body), [(0, 0), (1, 0), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn small_parts_stay_unmarked() {
+        let mut body = json!({ "messages": [
+            { "role": "system", "content": "x".repeat(9) },
+            { "role": "user", "content": [text(&"x".repeat(9)), text(&"x".repeat(10))] },
+            { "role": "user", "content": "x".repeat(100) },
+            { "role": "user", "content": [{ "type": "file", "file": { "filename": "a.pdf" } }] },
+        ] });
+        mark_breakpoints(&mut body, MODEL, &config(10));
+        assert_eq!(breakpoints(&body), [(1, 1), (3, 0)]);
+        // 非 system 的字符串内容不改写 / non-system string content is kept as is
+        assert!(body["messages"][0]["content"].is_string());
+        assert!(body["messages"][2]["content"].is_string());
+    }
+
+    #[test]
+    fn existing_breakpoints_and_other_models_are_left_alone() {
+        let long = "x".repeat(20);
+        let mut marked = json!({ "messages": [
+            { "role": "system", "content": long },
+            { "role": "user", "content": [text(&long), { "type": "text", "text": "c", "cache_control": { "type": "ephemeral" } }] },
+        ] });
+        let before = marked.clone();
+        mark_breakpoints(&mut marked, MODEL, &config(10));
+        assert_eq!(marked, before);
+
+        // 仅文本中出现该词不算已有断点 / the word inside text is not a breakpoint
+        let mut mentioned = json!({ "messages": [
+            { "role": "user", "content": [text(&format!("{} \"cache_control\"", long))] },
+        ] });
+        mark_breakpoints(&mut mentioned, MODEL, &config(10));
+        assert_eq!(breakpoints(&mentioned), [(0, 0)]);
+
+        let mut other = json!({ "messages": [{ "role": "system", "content": long }] });
+        mark_breakpoints(&mut other, "openai/gpt-4o", &config(10));
+        assert!(other["messages"][0]["content"].is_string());
+        let disabled = PromptCacheConfig {
+            enabled: false,
+            ..config(10)
+        };
+        mark_breakpoints(&mut other, MODEL, &disabled);
+        assert!(other["messages"][0]["content"].is_string());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, AppHandle, Emitter, Manager, Window};
+
+use crate::attachments::AttachmentStore;
+use crate::budget::BudgetStatus;
+use crate::keys::resolve_key;
+use crate::stream::{check_budget, execute, Endpoint, StreamJob, StreamOutput};
+use crate::usage::next_request_id;
+
+/// 对比模式最多并发的模型数
+/// Max number of models in one comparison.
+const MAX_LANES: usize = 8;
+
+/// `compare_stream` 的可选参数
+/// Optional parameters of `compare_stream`.
+#[derive(Debug, Default, Deserialize)]
+pub struct CompareOptions {
+    pub conversation_id: Option<String>,
+    pub key_id: Option<String>,
+}
+
+/// 一条对比通道
+/// One comparison lane.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareLane {
+    pub request_id: String,
+    pub model: String,
+}
+
+/// `compare_stream` 的返回值：用于之后按通道取消
+/// Return value of `compare_stream`; used to cancel lanes later.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareStarted {
+    pub compare_id: String,
+    pub lanes: Vec<CompareLane>,
+}
+
+/// 单个模型的统计
+/// Per-model statistics.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LaneStats {
+    pub latency_ms: u64,
+    pub ttft_ms: Option<u64>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+    pub provider: Option<String>,
+    pub finish_reason: Option<String>,
+}
+
+impl LaneStats {
+    pub fn of(output: &StreamOutput) -> Self {
+        Self {
+            latency_ms: output.record.latency_ms,
+            ttft_ms: output.record.ttft_ms,
+            prompt_tokens: output.record.prompt_tokens,
+            completion_tokens: output.record.completion_tokens,
+            cost: output.record.cost,
+            provider: output.record.provider.clone(),
+            finish_reason: output.finish_reason.clone(),
+        }
+    }
+}
+
+/// 通道事件
+/// Lane event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LaneEvent {
+    Delta { content: String },
+    BudgetWarning { budget: BudgetStatus },
+    Done { stats: LaneStats },
+    Cancelled { stats: LaneStats },
+    Error { code: String, message: String },
+}
+
+/// 通过 `compare-stream` 发送的事件，均带模型与请求 ID
+/// Event sent on `compare-stream`, always tagged with model and request id.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareEvent {
+    pub compare_id: String,
+    pub request_id: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub event: LaneEvent,
+}
+
+/// 通道结束时的汇总
+/// Lane summary when finished.
+#[derive(Debug, Clone, Serialize)]
+pub struct LaneResult {
+    pub request_id: String,
+    pub model: String,
+    /// `done` / `cancelled` / `error`
+    pub status: String,
+    pub text: String,
+    pub stats: Option<LaneStats>,
+    pub error: Option<String>,
+}
+
+fn emit_lane(window: &Window, compare_id: &str, lane: &CompareLane, event: LaneEvent) {
+    let _ = window.emit(
+        "compare-stream",
+        CompareEvent {
+            compare_id: compare_id.to_string(),
+            request_id: lane.request_id.clone(),
+            model: lane.model.clone(),
+            event,
+        },
+    );
+}
+
+async fn run_lane(
+    app: AppHandle,
+    window: Window,
+    compare_id: String,
+    lane: CompareLane,
+    job: StreamJob,
+) -> LaneResult {
+    let failed = |code: String, message: String| {
+        emit_lane(
+            &window,
+            &compare_id,
+            &lane,
+            LaneEvent::Error {
+                code,
+                message: message.clone(),
+            },
+        );
+        LaneResult {
+            request_id: lane.request_id.clone(),
+            model: lane.model.clone(),
+            status: "error".into(),
+            text: String::new(),
+            stats: None,
+            error: Some(message),
+        }
+    };
+
+    match check_budget(&app, &job).await {
+        Ok(warnings) => {
+            for budget in warnings {
+                emit_lane(
+                    &window,
+                    &compare_id,
+                    &lane,
+                    LaneEvent::BudgetWarning { budget },
+                );
+            }
+        }
+        Err(f) => return failed(f.code, f.message),
+    }
+
+    let result = execute(&app, &job, |_, text| {
+        if let Some(content) = text {
+            emit_lane(
+                &window,
+                &compare_id,
+                &lane,
+                LaneEvent::Delta {
+                    content: content.to_string(),
+                },
+            );
+        }
+        Ok(())
+    })
+    .await;
+
+    match result {
+        Ok(output) => {
+            let stats = LaneStats::of(&output);
+            let (status, event) = if output.cancelled {
+                (
+                    "cancelled",
+                    LaneEvent::Cancelled {
+                        stats: stats.clone(),
+                    },
+                )
+            } else {
+                (
+                    "done",
+                    LaneEvent::Done {
+                        stats: stats.clone(),
+                    },
+                )
+            };
+            emit_lane(&window, &compare_id, &lane, event);
+            LaneResult {
+                request_id: lane.request_id.clone(),
+                model: lane.model.clone(),
+                status: status.into(),
+                text: output.text,
+                stats: Some(stats),
+                error: None,
+            }
+        }
+        Err(f) => failed(f.code, f.message),
+    }
+}
+
+/// 多模型对比：同一请求并发发送给多个模型
+/// Multi-model comparison: fan the same request out to several models concurrently.
+///
+/// - 立即返回各通道的请求 ID；可用 `cancel_stream` 单独取消某个通道。
+/// - 增量与结果通过 `compare-stream` 事件发送，全部结束后发送 `compare-done`。
+/// - Returns lane request ids immediately; `cancel_stream` cancels a single lane.
+/// - Deltas and results go out on `compare-stream`; `compare-done` follows when all lanes end.
+#[command]
+pub async fn compare_stream(
+    app: AppHandle,
+    window: Window,
+    mut body: Value,
+    models: Vec<String>,
+    token: Option<String>,
+    options: Option<CompareOptions>,
+) -> Result<CompareStarted, String> {
+    let options = options.unwrap_or_default();
+    let mut seen = HashSet::new();
+    let mut models = models;
+    models.retain(|m| seen.insert(m.clone()));
+    if models.is_empty() {
+        return Err("No models to compare".into());
+    }
+    if models.len() > MAX_LANES {
+        return Err(format!(
+            "At most {} models can be compared at once",
+            MAX_LANES
+        ));
+    }
+
+    let key = resolve_key(
+        &app,
+        options.key_id.as_deref(),
+        options.conversation_id.as_deref(),
+        token.as_deref(),
+    )?;
+    app.state::<AttachmentStore>().inline(&mut body)?;
+    let compare_id = next_request_id().replacen("req-", "cmp-", 1);
+
+    // 先建好全部任务再启动，任一失败时不会留下已在运行的对比道
+    // Build every job before spawning any, so a failure leaves no lane running
+    let mut lanes = Vec::new();
+    let mut jobs = Vec::new();
+    for model in models {
+        let lane = CompareLane {
+            request_id: next_request_id(),
+            model: model.clone(),
+        };
+        jobs.push(StreamJob::new(
+            &app,
+            lane.request_id.clone(),
+            key.clone(),
+            model,
+            options.conversation_id.clone(),
+            Endpoint::Chat,
+            body.clone(),
+        )?);
+        lanes.push(lane);
+    }
+    let handles: Vec<_> = lanes
+        .iter()
+        .zip(jobs)
+        .map(|(lane, job)| {
+            tauri::async_runtime::spawn(run_lane(
+                app.clone(),
+                window.clone(),
+                compare_id.clone(),
+                lane.clone(),
+                job,
+            ))
+        })
+        .collect();
+
+    let done_window = window.clone();
+    let done_id = compare_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let results: Vec<LaneResult> = join_all(handles)
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect();
+        let _ = done_window.emit(
+            "compare-done",
+            serde_json::json!({ "compare_id": done_id, "results": results }),
+        );
+    });
+
+    Ok(CompareStarted { compare_id, lanes })
+}
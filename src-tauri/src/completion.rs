@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{command, AppHandle, Emitter, Window};
+
+use crate::budget::BudgetStatus;
+use crate::compare::LaneStats;
+use crate::keys::resolve_key;
+use crate::stream::{check_budget, execute, Endpoint, StreamJob};
+use crate::usage::next_request_id;
+
+/// `proxy_completion_stream` 的可选参数
+/// Optional parameters of `proxy_completion_stream`.
+#[derive(Debug, Default, Deserialize)]
+pub struct CompletionOptions {
+    pub conversation_id: Option<String>,
+    pub key_id: Option<String>,
+    /// 请求 ID，用于 `cancel_stream`；为空时自动生成 / request id for `cancel_stream`, generated when empty
+    pub request_id: Option<String>,
+    /// 填空补全的后缀（光标之后的文本）/ fill-in-the-middle suffix (the text after the cursor)
+    pub suffix: Option<String>,
+}
+
+/// 补全事件
+/// Completion event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CompletionEvent {
+    Start,
+    Delta { text: String },
+    BudgetWarning { budget: BudgetStatus },
+    Done { stats: LaneStats },
+    Cancelled { stats: LaneStats },
+    Error { code: String, message: String },
+}
+
+/// 通过 `completion-stream` 发送的事件，均带请求 ID 与模型
+/// Event sent on `completion-stream`, always tagged with request id and model.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionStreamEvent {
+    pub request_id: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub event: CompletionEvent,
+}
+
+fn emit_completion(window: &Window, job: &StreamJob, event: CompletionEvent) {
+    let _ = window.emit(
+        "completion-stream",
+        CompletionStreamEvent {
+            request_id: job.request_id.clone(),
+            model: job.model.clone(),
+            event,
+        },
+    );
+}
+
+/// 代理 `/completions` 流式补全（原始提示词，适用于基座模型与填空补全）
+/// Proxy a streaming `/completions` request (raw prompt, for base models and fill-in-the-middle).
+///
+/// - `params` 为其余请求参数（`max_tokens`、`temperature`、`stop` 等）。
+/// - 与聊天共用 SSE 解析、取消（`cancel_stream`）、预算检查、请求日志与供应商路由设置。
+/// - 事件通过 `completion-stream` 发送：`start`、`delta`、`budget_warning`、`done`、`cancelled`、`error`。
+/// - `params` carries the other request parameters (`max_tokens`, `temperature`, `stop`, ...).
+/// - Shares the SSE parser, cancellation (`cancel_stream`), budget checks, request log and
+///   provider routing settings with chat.
+/// - Events go out on `completion-stream`: `start`, `delta`, `budget_warning`, `done`,
+///   `cancelled` and `error`.
+#[command]
+pub async fn proxy_completion_stream(
+    app: AppHandle,
+    window: Window,
+    prompt: String,
+    model: String,
+    params: Option<Map<String, Value>>,
+    token: Option<String>,
+    options: Option<CompletionOptions>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    if prompt.is_empty() && options.suffix.is_none() {
+        return Err("Prompt is empty".into());
+    }
+    let mut params = params.unwrap_or_default();
+    if params.contains_key("messages") {
+        return Err("Completion requests take a prompt, not messages".into());
+    }
+    params.insert("prompt".into(), Value::String(prompt));
+    if let Some(suffix) = options.suffix {
+        params.insert("suffix".into(), Value::String(suffix));
+    }
+    let body = Value::Object(params);
+
+    let key = resolve_key(
+        &app,
+        options.key_id.as_deref(),
+        options.conversation_id.as_deref(),
+        token.as_deref(),
+    )?;
+    let job = StreamJob::new(
+        &app,
+        options.request_id.unwrap_or_else(next_request_id),
+        key,
+        model,
+        options.conversation_id,
+        Endpoint::Completion,
+        body,
+    )?;
+    emit_completion(&window, &job, CompletionEvent::Start);
+
+    let failed = |code: String, message: String| {
+        emit_completion(
+            &window,
+            &job,
+            CompletionEvent::Error {
+                code,
+                message: message.clone(),
+            },
+        );
+        message
+    };
+
+    let warnings = check_budget(&app, &job)
+        .await
+        .map_err(|f| failed(f.code, f.message))?;
+    for budget in warnings {
+        emit_completion(&window, &job, CompletionEvent::BudgetWarning { budget });
+    }
+
+    let output = execute(&app, &job, |_, text| {
+        if let Some(text) = text {
+            emit_completion(
+                &window,
+                &job,
+                CompletionEvent::Delta {
+                    text: text.to_string(),
+                },
+            );
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|f| failed(f.code, f.message))?;
+
+    let stats = LaneStats::of(&output);
+    let event = if output.cancelled {
+        CompletionEvent::Cancelled { stats }
+    } else {
+        CompletionEvent::Done { stats }
+    };
+    emit_completion(&window, &job, event);
+    Ok(())
+}
@@ -0,0 +1,1103 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::DeflateDecoder;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use reqwest::Url;
+use serde::Serialize;
+use tauri::command;
+use tracing::info;
+
+use crate::web::html_to_markdown;
+
+/// 可处理的最大文件大小
+/// Max size of a file to extract.
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+/// 压缩包内单个条目解压后的上限（防压缩炸弹）
+/// Cap of one decompressed archive entry (zip-bomb guard).
+const MAX_ENTRY_BYTES: u64 = 128 * 1024 * 1024;
+/// 默认输出的最大字符数
+/// Default max characters of output.
+const DEFAULT_MAX_CHARS: usize = 1_000_000;
+/// 每个工作表输出的最大行数
+/// Max rows rendered per sheet.
+const MAX_SHEET_ROWS: usize = 5_000;
+/// 每个工作表输出的最大列数
+/// Max columns rendered per sheet.
+const MAX_SHEET_COLS: usize = 64;
+
+/// 文档的一个部分（工作表、幻灯片、章节）
+/// One part of a document (sheet, slide, chapter).
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentSection {
+    /// `sheet` / `slide` / `chapter` / `body`
+    pub kind: &'static str,
+    pub title: String,
+    pub markdown: String,
+}
+
+/// 提取结果
+/// Extraction result.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedDocument {
+    pub path: String,
+    pub file_name: String,
+    /// `docx` / `xlsx` / `csv` / `pptx` / `pdf` / `epub` / `html`
+    pub format: &'static str,
+    pub bytes: u64,
+    pub title: Option<String>,
+    /// 全部内容拼接的 Markdown / Markdown of all sections joined
+    pub markdown: String,
+    pub sections: Vec<DocumentSection>,
+    /// 是否因上限被截断 / whether limits cut the output
+    pub truncated: bool,
+}
+
+/// 极简 ZIP 读取（仅 stored / deflate，不支持 ZIP64 与加密）
+/// Minimal ZIP reader (stored / deflate only; no ZIP64 or encryption).
+struct ZipArchive<'a> {
+    data: &'a [u8],
+    /// 条目名 → (压缩方式, 压缩后大小, 本地头偏移)
+    /// entry name → (method, compressed size, local header offset)
+    entries: HashMap<String, (u16, usize, usize)>,
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+impl<'a> ZipArchive<'a> {
+    fn open(data: &'a [u8]) -> Result<Self, String> {
+        let invalid = || "Not a valid ZIP-based document".to_string();
+        // 从尾部查找目录结束记录 / find the end-of-central-directory record from the tail
+        let search_from = data.len().saturating_sub(22 + 65_535);
+        let eocd = (search_from..data.len().saturating_sub(21))
+            .rev()
+            .find(|&i| le32(data, i) == Some(0x0605_4b50))
+            .ok_or_else(invalid)?;
+        let count = le16(data, eocd + 10).ok_or_else(invalid)? as usize;
+        let mut at = le32(data, eocd + 16).ok_or_else(invalid)? as usize;
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            if le32(data, at) != Some(0x0201_4b50) {
+                return Err(invalid());
+            }
+            let flags = le16(data, at + 8).ok_or_else(invalid)?;
+            let method = le16(data, at + 10).ok_or_else(invalid)?;
+            let size = le32(data, at + 20).ok_or_else(invalid)? as usize;
+            let name_len = le16(data, at + 28).ok_or_else(invalid)? as usize;
+            let extra_len = le16(data, at + 30).ok_or_else(invalid)? as usize;
+            let comment_len = le16(data, at + 32).ok_or_else(invalid)? as usize;
+            let offset = le32(data, at + 42).ok_or_else(invalid)? as usize;
+            let name = data.get(at + 46..at + 46 + name_len).ok_or_else(invalid)?;
+            if flags & 1 == 0 {
+                entries.insert(
+                    String::from_utf8_lossy(name).into_owned(),
+                    (method, size, offset),
+                );
+            }
+            at += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Self { data, entries })
+    }
+
+    /// 读取条目的字节
+    /// Read an entry's bytes.
+    fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let &(method, size, offset) = self
+            .entries
+            .get(name.trim_start_matches('/'))
+            .ok_or_else(|| format!("Missing {} in archive", name))?;
+        let corrupt = || format!("Corrupt archive entry {}", name);
+        if le32(self.data, offset) != Some(0x0403_4b50) {
+            return Err(corrupt());
+        }
+        let name_len = le16(self.data, offset + 26).ok_or_else(corrupt)? as usize;
+        let extra_len = le16(self.data, offset + 28).ok_or_else(corrupt)? as usize;
+        let start = offset + 30 + name_len + extra_len;
+        let raw = self.data.get(start..start + size).ok_or_else(corrupt)?;
+        let mut out = Vec::new();
+        match method {
+            0 => out.extend_from_slice(raw),
+            8 => {
+                DeflateDecoder::new(raw)
+                    .take(MAX_ENTRY_BYTES)
+                    .read_to_end(&mut out)
+                    .map_err(|e| format!("{}: {}", name, e))?;
+            }
+            other => return Err(format!("Unsupported compression {} in {}", other, name)),
+        }
+        Ok(out)
+    }
+
+    fn read_string(&self, name: &str) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(&self.read(name)?).into_owned())
+    }
+}
+
+/// 简化的 XML 事件（元素保留本地名，属性保留带前缀的名字）
+/// Simplified XML event (local element names, prefixed attribute names).
+#[derive(Debug)]
+enum XmlNode {
+    Open(String, Vec<(String, String)>),
+    Close(String),
+    Text(String),
+}
+
+/// 把 XML 解析为事件序列；自闭合元素产生 Open + Close
+/// Parse XML into events; empty elements yield Open + Close.
+fn xml_nodes(xml: &str) -> Result<Vec<XmlNode>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut nodes = Vec::new();
+    let local = |name: &str| name.to_string();
+    let attrs = |e: &BytesStart| -> Vec<(String, String)> {
+        e.attributes()
+            .flatten()
+            .filter_map(|a| {
+                let value = a.normalized_value(XmlVersion::Implicit1_0).ok()?;
+                Some((local(a.key.as_ref()), value.into_owned()))
+            })
+            .collect()
+    };
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => {
+                nodes.push(XmlNode::Open(local(e.local_name().as_ref()), attrs(&e)));
+            }
+            Event::Empty(e) => {
+                let name = local(e.local_name().as_ref());
+                nodes.push(XmlNode::Open(name.clone(), attrs(&e)));
+                nodes.push(XmlNode::Close(name));
+            }
+            Event::End(e) => nodes.push(XmlNode::Close(local(e.local_name().as_ref()))),
+            Event::Text(e) => nodes.push(XmlNode::Text(e.into_inner().into_owned())),
+            Event::CData(e) => nodes.push(XmlNode::Text(e.into_inner().into_owned())),
+            Event::GeneralRef(e) => {
+                let resolved = match e.resolve_char_ref() {
+                    Ok(Some(c)) => c.to_string(),
+                    _ => match &*e.into_inner() {
+                        "lt" => "<".into(),
+                        "gt" => ">".into(),
+                        "amp" => "&".into(),
+                        "apos" => "'".into(),
+                        "quot" => "\"".into(),
+                        _ => String::new(),
+                    },
+                };
+                nodes.push(XmlNode::Text(resolved));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(nodes)
+}
+
+/// 按本地名查找属性
+/// Find an attribute by local name.
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(k, _)| k.rsplit(':').next() == Some(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// 关系 ID（`r:id`，区别于无前缀的 `id`）
+/// Relationship id (`r:id`, as opposed to an unprefixed `id`).
+fn relationship_id(attrs: &[(String, String)]) -> Option<&str> {
+    attrs
+        .iter()
+        .find(|(k, _)| k.contains(':') && k.ends_with(":id"))
+        .map(|(_, v)| v.as_str())
+}
+
+/// 解析 `.rels` 关系文件：ID → 目标
+/// Parse a `.rels` file: id → target.
+fn relationships(zip: &ZipArchive, path: &str) -> HashMap<String, String> {
+    let Ok(xml) = zip.read_string(path) else {
+        return HashMap::new();
+    };
+    xml_nodes(&xml)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|node| match node {
+            XmlNode::Open(name, attrs) if name == "Relationship" => Some((
+                attr(&attrs, "Id")?.to_string(),
+                attr(&attrs, "Target")?.to_string(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 以 `base` 所在目录解析相对路径
+/// Resolve a relative path against the directory of `base`.
+fn join_path(base: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut parts: Vec<&str> = base.split('/').collect();
+    parts.pop();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            s => parts.push(s),
+        }
+    }
+    parts.join("/")
+}
+
+/// 读取 `docProps/core.xml` 中的标题
+/// Read the title from `docProps/core.xml`.
+fn office_title(zip: &ZipArchive) -> Option<String> {
+    let xml = zip.read_string("docProps/core.xml").ok()?;
+    element_text(&xml, "title")
+}
+
+/// 第一个指定元素的文本
+/// Text of the first element with the given name.
+fn element_text(xml: &str, element: &str) -> Option<String> {
+    let nodes = xml_nodes(xml).ok()?;
+    let mut inside = false;
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            XmlNode::Open(name, _) if name == element => inside = true,
+            XmlNode::Close(name) if name == element && inside => break,
+            XmlNode::Text(t) if inside => text.push_str(&t),
+            _ => {}
+        }
+    }
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// 表格行渲染为 Markdown 表格，首行作为表头
+/// Render rows as a Markdown table, the first row as header.
+fn markdown_table(rows: &[Vec<String>]) -> String {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    if width == 0 {
+        return String::new();
+    }
+    let cell = |s: &str| s.replace('|', "\\|").replace(['\n', '\r'], " ");
+    let mut out = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        out.push('|');
+        for col in 0..width {
+            out.push(' ');
+            out.push_str(&cell(row.get(col).map(String::as_str).unwrap_or("")));
+            out.push_str(" |");
+        }
+        out.push('\n');
+        if i == 0 {
+            out.push('|');
+            out.push_str(&" --- |".repeat(width));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// DOCX：段落、标题样式、列表与表格
+/// DOCX: paragraphs, heading styles, lists and tables.
+fn extract_docx(data: &[u8]) -> Result<(Option<String>, Vec<DocumentSection>), String> {
+    let zip = ZipArchive::open(data)?;
+    let nodes = xml_nodes(&zip.read_string("word/document.xml")?)?;
+
+    let mut out = String::new();
+    let mut paragraph = String::new();
+    let mut prefix = String::new();
+    let mut in_text = false;
+    // 表格栈：每层为 (行, 当前行, 当前单元格) / table stack: (rows, current row, current cell)
+    let mut tables: Vec<(Vec<Vec<String>>, Vec<String>, String)> = Vec::new();
+
+    for node in nodes {
+        match node {
+            XmlNode::Open(name, attrs) => match name.as_str() {
+                "p" => {
+                    paragraph.clear();
+                    prefix.clear();
+                }
+                "pStyle" => {
+                    let style = attr(&attrs, "val").unwrap_or("").to_ascii_lowercase();
+                    if style == "title" {
+                        prefix = "# ".into();
+                    } else if let Some(level) = style
+                        .strip_prefix("heading")
+                        .and_then(|l| l.trim().parse::<usize>().ok())
+                    {
+                        prefix = format!("{} ", "#".repeat(level.clamp(1, 6)));
+                    } else if style.contains("list") && prefix.is_empty() {
+                        prefix = "- ".into();
+                    }
+                }
+                "numPr" if prefix.is_empty() => prefix = "- ".into(),
+                "t" => in_text = true,
+                "tab" => paragraph.push('\t'),
+                "br" | "cr" => paragraph.push('\n'),
+                "tbl" => tables.push(Default::default()),
+                _ => {}
+            },
+            XmlNode::Text(text) if in_text => paragraph.push_str(&text),
+            XmlNode::Close(name) => match name.as_str() {
+                "t" => in_text = false,
+                "p" => {
+                    let text = paragraph.trim();
+                    if let Some((_, _, cell)) = tables.last_mut() {
+                        if !cell.is_empty() && !text.is_empty() {
+                            cell.push(' ');
+                        }
+                        cell.push_str(text);
+                    } else if !text.is_empty() {
+                        // 列表结束后空一行 / blank line after a list ends
+                        if prefix != "- " && out.ends_with('\n') && !out.ends_with("\n\n") {
+                            out.push('\n');
+                        }
+                        out.push_str(&prefix);
+                        out.push_str(text);
+                        out.push_str(if prefix == "- " { "\n" } else { "\n\n" });
+                    }
+                }
+                "tc" => {
+                    if let Some((_, row, cell)) = tables.last_mut() {
+                        row.push(std::mem::take(cell));
+                    }
+                }
+                "tr" => {
+                    if let Some((rows, row, _)) = tables.last_mut() {
+                        rows.push(std::mem::take(row));
+                    }
+                }
+                "tbl" => {
+                    if let Some((rows, _, _)) = tables.pop() {
+                        let table = markdown_table(&rows);
+                        match tables.last_mut() {
+                            // 嵌套表格压平到外层单元格 / nested tables flatten into the outer cell
+                            Some((_, _, cell)) => cell.push_str(&table.replace('\n', " ")),
+                            None => {
+                                if !out.is_empty() && !out.ends_with("\n\n") {
+                                    out.push('\n');
+                                }
+                                out.push_str(&table);
+                                out.push('\n');
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    let title = office_title(&zip);
+    Ok((
+        title.clone(),
+        vec![DocumentSection {
+            kind: "body",
+            title: title.unwrap_or_default(),
+            markdown: out.trim().to_string(),
+        }],
+    ))
+}
+
+/// 列字母转为序号（`A` → 0）
+/// Column letters to index (`A` → 0).
+fn column_index(cell_ref: &str) -> Option<usize> {
+    let letters: String = cell_ref
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    if letters.is_empty() {
+        return None;
+    }
+    Some(
+        letters
+            .to_ascii_uppercase()
+            .bytes()
+            .fold(0usize, |acc, b| acc * 26 + (b - b'A' + 1) as usize)
+            - 1,
+    )
+}
+
+/// XLSX：每个工作表一个表格
+/// XLSX: one table per sheet.
+fn extract_xlsx(data: &[u8]) -> Result<(Option<String>, Vec<DocumentSection>, bool), String> {
+    let zip = ZipArchive::open(data)?;
+
+    // 共享字符串 / shared strings
+    let mut shared = Vec::new();
+    if let Ok(xml) = zip.read_string("xl/sharedStrings.xml") {
+        let mut current: Option<String> = None;
+        let mut in_text = false;
+        // 注音（rPh）中的文字不属于单元格内容 / phonetic runs (rPh) are not cell text
+        let mut in_phonetic = false;
+        for node in xml_nodes(&xml)? {
+            match node {
+                XmlNode::Open(name, _) if name == "si" => current = Some(String::new()),
+                XmlNode::Open(name, _) if name == "rPh" => in_phonetic = true,
+                XmlNode::Close(name) if name == "rPh" => in_phonetic = false,
+                XmlNode::Open(name, _) if name == "t" => in_text = !in_phonetic,
+                XmlNode::Close(name) if name == "t" => in_text = false,
+                XmlNode::Text(text) if in_text => {
+                    if let Some(s) = current.as_mut() {
+                        s.push_str(&text);
+                    }
+                }
+                XmlNode::Close(name) if name == "si" => {
+                    shared.push(current.take().unwrap_or_default())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // 工作表顺序与路径 / sheet order and paths
+    let rels = relationships(&zip, "xl/_rels/workbook.xml.rels");
+    let sheets: Vec<(String, String)> = xml_nodes(&zip.read_string("xl/workbook.xml")?)?
+        .into_iter()
+        .filter_map(|node| match node {
+            XmlNode::Open(name, attrs) if name == "sheet" => {
+                let target = rels.get(relationship_id(&attrs)?)?;
+                Some((
+                    attr(&attrs, "name").unwrap_or("Sheet").to_string(),
+                    join_path("xl/workbook.xml", target),
+                ))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut truncated = false;
+    let mut sections = Vec::new();
+    for (sheet_name, path) in sheets {
+        let Ok(xml) = zip.read_string(&path) else {
+            continue;
+        };
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut row: Vec<String> = Vec::new();
+        let mut cell_type = String::new();
+        let mut cell_col = 0;
+        let mut value = String::new();
+        let mut in_value = false;
+        for node in xml_nodes(&xml)? {
+            match node {
+                XmlNode::Open(name, attrs) => match name.as_str() {
+                    "row" => row.clear(),
+                    "c" => {
+                        cell_type = attr(&attrs, "t").unwrap_or("n").to_string();
+                        cell_col = attr(&attrs, "r")
+                            .and_then(column_index)
+                            .unwrap_or(row.len());
+                        value.clear();
+                    }
+                    "v" | "t" => in_value = true,
+                    _ => {}
+                },
+                XmlNode::Text(text) if in_value => value.push_str(&text),
+                XmlNode::Close(name) => match name.as_str() {
+                    "v" | "t" => in_value = false,
+                    "c" => {
+                        let text = match cell_type.as_str() {
+                            "s" => value
+                                .trim()
+                                .parse::<usize>()
+                                .ok()
+                                .and_then(|i| shared.get(i).cloned())
+                                .unwrap_or_default(),
+                            "b" => (if value.trim() == "1" { "TRUE" } else { "FALSE" }).into(),
+                            _ => value.clone(),
+                        };
+                        if cell_col < MAX_SHEET_COLS {
+                            if row.len() <= cell_col {
+                                row.resize(cell_col + 1, String::new());
+                            }
+                            row[cell_col] = text;
+                        } else {
+                            truncated = true;
+                        }
+                    }
+                    "row" => {
+                        if rows.len() >= MAX_SHEET_ROWS {
+                            truncated = true;
+                        } else if row.iter().any(|c| !c.trim().is_empty()) {
+                            rows.push(std::mem::take(&mut row));
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        sections.push(DocumentSection {
+            kind: "sheet",
+            title: sheet_name,
+            markdown: markdown_table(&rows),
+        });
+    }
+    Ok((office_title(&zip), sections, truncated))
+}
+
+/// 解析 CSV（支持引号，自动识别逗号/分号/制表符）
+/// Parse CSV (quotes supported; comma, semicolon or tab detected).
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let first_line = text.lines().next().unwrap_or("");
+    let delimiter = [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| first_line.matches(*d).count())
+        .unwrap_or(',');
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// PPTX：按演示顺序每页一节
+/// PPTX: one section per slide in presentation order.
+fn extract_pptx(data: &[u8]) -> Result<(Option<String>, Vec<DocumentSection>), String> {
+    let zip = ZipArchive::open(data)?;
+    let rels = relationships(&zip, "ppt/_rels/presentation.xml.rels");
+    let slides: Vec<String> = xml_nodes(&zip.read_string("ppt/presentation.xml")?)?
+        .into_iter()
+        .filter_map(|node| match node {
+            XmlNode::Open(name, attrs) if name == "sldId" => rels
+                .get(relationship_id(&attrs)?)
+                .map(|t| join_path("ppt/presentation.xml", t)),
+            _ => None,
+        })
+        .collect();
+
+    let mut sections = Vec::new();
+    for (index, path) in slides.iter().enumerate() {
+        let Ok(xml) = zip.read_string(path) else {
+            continue;
+        };
+        let mut lines = Vec::new();
+        let mut paragraph = String::new();
+        let mut in_text = false;
+        let mut title: Option<String> = None;
+        let mut in_title_shape = false;
+        for node in xml_nodes(&xml)? {
+            match node {
+                XmlNode::Open(name, attrs) => match name.as_str() {
+                    "sp" => in_title_shape = false,
+                    "ph" => {
+                        in_title_shape =
+                            matches!(attr(&attrs, "type"), Some("title") | Some("ctrTitle"))
+                    }
+                    "p" => paragraph.clear(),
+                    "t" => in_text = true,
+                    "br" => paragraph.push('\n'),
+                    _ => {}
+                },
+                XmlNode::Text(text) if in_text => paragraph.push_str(&text),
+                XmlNode::Close(name) => match name.as_str() {
+                    "t" => in_text = false,
+                    "p" => {
+                        let text = paragraph.trim().to_string();
+                        if text.is_empty() {
+                            continue;
+                        }
+                        if in_title_shape && title.is_none() {
+                            title = Some(text);
+                        } else {
+                            lines.push(format!("- {}", text));
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        sections.push(DocumentSection {
+            kind: "slide",
+            title: title.unwrap_or_else(|| format!("Slide {}", index + 1)),
+            markdown: lines.join("\n"),
+        });
+    }
+    Ok((office_title(&zip), sections))
+}
+
+/// EPUB：按 spine 顺序每章一节
+/// EPUB: one section per chapter in spine order.
+fn extract_epub(data: &[u8]) -> Result<(Option<String>, Vec<DocumentSection>), String> {
+    let zip = ZipArchive::open(data)?;
+    let container = xml_nodes(&zip.read_string("META-INF/container.xml")?)?;
+    let opf_path = container
+        .iter()
+        .find_map(|node| match node {
+            XmlNode::Open(name, attrs) if name == "rootfile" => attr(attrs, "full-path"),
+            _ => None,
+        })
+        .ok_or("EPUB has no rootfile")?
+        .to_string();
+    let opf = zip.read_string(&opf_path)?;
+    let nodes = xml_nodes(&opf)?;
+
+    let mut manifest = HashMap::new();
+    let mut spine = Vec::new();
+    for node in &nodes {
+        if let XmlNode::Open(name, attrs) = node {
+            match name.as_str() {
+                "item" => {
+                    if let (Some(id), Some(href)) = (attr(attrs, "id"), attr(attrs, "href")) {
+                        manifest.insert(id.to_string(), href.to_string());
+                    }
+                }
+                "itemref" => spine.extend(attr(attrs, "idref").map(String::from)),
+                _ => {}
+            }
+        }
+    }
+
+    let base = Url::parse("file:///").map_err(|e| e.to_string())?;
+    let mut sections = Vec::new();
+    for (index, id) in spine.iter().enumerate() {
+        let Some(href) = manifest.get(id) else {
+            continue;
+        };
+        let path = join_path(&opf_path, href.split('#').next().unwrap_or(href));
+        let Ok(html) = zip.read_string(&path) else {
+            continue;
+        };
+        let (title, markdown) = html_to_markdown(&html, &base);
+        if markdown.trim().is_empty() {
+            continue;
+        }
+        sections.push(DocumentSection {
+            kind: "chapter",
+            title: title.unwrap_or_else(|| format!("Chapter {}", index + 1)),
+            markdown,
+        });
+    }
+    Ok((element_text(&opf, "title"), sections))
+}
+
+/// PDF：提取文本层（扫描件无文字）
+/// PDF: extract the text layer (scans have none).
+fn extract_pdf(data: &[u8]) -> Result<Vec<DocumentSection>, String> {
+    let text = pdf_extract::extract_text_from_mem(data).map_err(|e| e.to_string())?;
+    // 以换页符分页 / pages are separated by form feeds
+    Ok(text
+        .split('\u{c}')
+        .map(str::trim)
+        .filter(|page| !page.is_empty())
+        .enumerate()
+        .map(|(i, page)| DocumentSection {
+            kind: "page",
+            title: format!("Page {}", i + 1),
+            markdown: page.to_string(),
+        })
+        .collect())
+}
+
+/// 按扩展名识别格式
+/// Detect the format from the extension.
+fn detect_format(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "docx" | "docm" => "docx",
+        "xlsx" | "xlsm" => "xlsx",
+        "csv" | "tsv" => "csv",
+        "pptx" | "pptm" => "pptx",
+        "pdf" => "pdf",
+        "epub" => "epub",
+        "html" | "htm" | "xhtml" => "html",
+        _ => return None,
+    })
+}
+
+/// 提取文档（阻塞调用）
+/// Extract a document (blocking).
+fn extract_blocking(path: &Path, max_chars: usize) -> Result<ExtractedDocument, String> {
+    let format = detect_format(path).ok_or("Unsupported document type")?;
+    let bytes = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if bytes > MAX_FILE_BYTES {
+        return Err(format!("File is larger than {} bytes", MAX_FILE_BYTES));
+    }
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    let mut truncated = false;
+    let (title, sections) = match format {
+        "docx" => extract_docx(&data)?,
+        "xlsx" => {
+            let (title, sections, cut) = extract_xlsx(&data)?;
+            truncated = cut;
+            (title, sections)
+        }
+        "csv" => {
+            let mut rows = parse_csv(&String::from_utf8_lossy(&data));
+            truncated = rows.len() > MAX_SHEET_ROWS;
+            rows.truncate(MAX_SHEET_ROWS);
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let section = DocumentSection {
+                kind: "sheet",
+                title: name,
+                markdown: markdown_table(&rows),
+            };
+            (None, vec![section])
+        }
+        "pptx" => extract_pptx(&data)?,
+        "pdf" => (None, extract_pdf(&data)?),
+        "epub" => extract_epub(&data)?,
+        _ => {
+            let base = Url::parse("file:///").map_err(|e| e.to_string())?;
+            let (title, markdown) = html_to_markdown(&String::from_utf8_lossy(&data), &base);
+            let section = DocumentSection {
+                kind: "body",
+                title: title.clone().unwrap_or_default(),
+                markdown,
+            };
+            (title, vec![section])
+        }
+    };
+
+    // 多节文档按节加标题拼接，并按字符上限截断
+    // Join multi-section documents under headings, cut at the char limit
+    let multi = sections.len() > 1 || matches!(format, "xlsx" | "pptx" | "epub");
+    let mut markdown = String::new();
+    // 已输出的字符数 / characters written so far
+    let mut written = 0;
+    let mut kept = Vec::new();
+    for mut section in sections {
+        let remaining = max_chars.saturating_sub(written);
+        if remaining == 0 {
+            truncated = true;
+            break;
+        }
+        if let Some((index, _)) = section.markdown.char_indices().nth(remaining) {
+            section.markdown.truncate(index);
+            truncated = true;
+        }
+        if multi {
+            let heading = format!("## {}\n\n", section.title);
+            written += heading.chars().count();
+            markdown.push_str(&heading);
+        }
+        written += section.markdown.chars().count() + 2;
+        markdown.push_str(&section.markdown);
+        markdown.push_str("\n\n");
+        kept.push(section);
+    }
+
+    Ok(ExtractedDocument {
+        path: path.display().to_string(),
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        format,
+        bytes,
+        title,
+        markdown: markdown.trim().to_string(),
+        sections: kept,
+        truncated,
+    })
+}
+
+/// 将 DOCX / XLSX / CSV / PPTX / PDF / EPUB / HTML 转为 Markdown（在阻塞线程池中执行）
+/// Convert DOCX / XLSX / CSV / PPTX / PDF / EPUB / HTML to Markdown (on the blocking pool).
+#[command]
+pub async fn extract_document(
+    path: String,
+    max_chars: Option<usize>,
+) -> Result<ExtractedDocument, String> {
+    let max_chars = max_chars.unwrap_or(DEFAULT_MAX_CHARS);
+    let doc =
+        tauri::async_runtime::spawn_blocking(move || extract_blocking(Path::new(&path), max_chars))
+            .await
+            .map_err(|e| e.to_string())??;
+    info!(
+        file = %doc.file_name,
+        format = doc.format,
+        sections = doc.sections.len(),
+        "extracted document"
+    );
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// 测试用 ZIP 写入（不写 CRC，读取端不校验）
+    /// ZIP writer for tests (no CRC; the reader doesn't check it).
+    fn zip(entries: &[(&str, &str)], deflate: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        for (name, content) in entries {
+            let data = if deflate {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(content.as_bytes()).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                content.as_bytes().to_vec()
+            };
+            let method: u16 = if deflate { 8 } else { 0 };
+            let offset = out.len() as u32;
+            let sizes = [data.len() as u32, content.len() as u32];
+
+            out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            for field in [20u16, 0, method, 0, 0] {
+                out.extend_from_slice(&field.to_le_bytes());
+            }
+            for field in [0u32, sizes[0], sizes[1]] {
+                out.extend_from_slice(&field.to_le_bytes());
+            }
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&data);
+
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            for field in [20u16, 20, 0, method, 0, 0] {
+                directory.extend_from_slice(&field.to_le_bytes());
+            }
+            for field in [0u32, sizes[0], sizes[1]] {
+                directory.extend_from_slice(&field.to_le_bytes());
+            }
+            for field in [name.len() as u16, 0, 0, 0, 0] {
+                directory.extend_from_slice(&field.to_le_bytes());
+            }
+            directory.extend_from_slice(&0u32.to_le_bytes());
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = out.len() as u32;
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        for field in [0u16, 0, entries.len() as u16, entries.len() as u16] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn reads_stored_entries() {
+        let data = zip(&[("a.txt", "alpha"), ("dir/b.txt", "beta")], false);
+        let archive = ZipArchive::open(&data).unwrap();
+        assert_eq!(archive.read_string("a.txt").unwrap(), "alpha");
+        assert_eq!(archive.read_string("/dir/b.txt").unwrap(), "beta");
+        assert!(archive.read("missing.txt").is_err());
+    }
+
+    #[test]
+    fn reads_deflated_entries() {
+        let text = "deflate me ".repeat(200);
+        let data = zip(&[("big.txt", &text)], true);
+        assert!(data.len() < text.len());
+        let archive = ZipArchive::open(&data).unwrap();
+        assert_eq!(archive.read_string("big.txt").unwrap(), text);
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        let data = zip(&[("a.txt", "alpha")], false);
+        assert!(ZipArchive::open(&data[..data.len() - 10]).is_err());
+        assert!(ZipArchive::open(b"PK\x03\x04 not really").is_err());
+        assert!(ZipArchive::open(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_entries_beyond_the_end() {
+        let mut data = zip(&[("a.txt", "alpha")], false);
+        // 中央目录项中的本地头偏移（第 42 字节起）/ local header offset in the directory entry (byte 42)
+        let directory = data.len() - 22 - (46 + "a.txt".len());
+        data[directory + 42..directory + 46].copy_from_slice(&1_000_000u32.to_le_bytes());
+        let archive = ZipArchive::open(&data).unwrap();
+        assert!(archive
+            .read("a.txt")
+            .unwrap_err()
+            .starts_with("Corrupt archive entry"));
+    }
+
+    const CORE: &str = r#"<cp:coreProperties xmlns:cp="cp" xmlns:dc="dc"><dc:title>Quarterly</dc:title></cp:coreProperties>"#;
+
+    #[test]
+    fn docx_headings_lists_and_tables() {
+        let document = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Report</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Intro </w:t></w:r><w:r><w:t>text &amp; more.</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/></w:numPr></w:pPr><w:r><w:t>First</w:t></w:r></w:p>
+            <w:tbl>
+              <w:tr><w:tc><w:p><w:r><w:t>A</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>B</w:t></w:r></w:p></w:tc></w:tr>
+              <w:tr><w:tc><w:p><w:r><w:t>1</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>2</w:t></w:r></w:p></w:tc></w:tr>
+            </w:tbl>
+        </w:body></w:document>"#;
+        let data = zip(
+            &[("word/document.xml", document), ("docProps/core.xml", CORE)],
+            true,
+        );
+        let (title, sections) = extract_docx(&data).unwrap();
+        assert_eq!(title.as_deref(), Some("Quarterly"));
+        assert_eq!(
+            sections[0].markdown,
+            "# Report\n\nIntro text & more.\n\n- First\n\n| A | B |\n| --- | --- |\n| 1 | 2 |"
+        );
+    }
+
+    #[test]
+    fn xlsx_sheets_become_tables() {
+        let workbook = r#"<workbook xmlns:r="r"><sheets><sheet name="Data" sheetId="1" r:id="rId1"/></sheets></workbook>"#;
+        let rels = r#"<Relationships><Relationship Id="rId1" Target="worksheets/sheet1.xml"/></Relationships>"#;
+        let strings = r#"<sst><si><t>Name</t></si><si><t>Qty</t></si><si><r><t>Ap</t></r><r><t>ple</t></r><rPh><t>x</t></rPh></si></sst>"#;
+        let sheet = r#"<worksheet><sheetData>
+            <row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c></row>
+            <row r="2"><c r="A2" t="s"><v>2</v></c><c r="B2"><v>3</v></c></row>
+            <row r="3"><c r="A3" t="inlineStr"><is><t>pie|x</t></is></c></row>
+        </sheetData></worksheet>"#;
+        let data = zip(
+            &[
+                ("xl/workbook.xml", workbook),
+                ("xl/_rels/workbook.xml.rels", rels),
+                ("xl/sharedStrings.xml", strings),
+                ("xl/worksheets/sheet1.xml", sheet),
+            ],
+            false,
+        );
+        let (title, sections, truncated) = extract_xlsx(&data).unwrap();
+        assert_eq!(title, None);
+        assert!(!truncated);
+        assert_eq!(sections[0].title, "Data");
+        assert_eq!(
+            sections[0].markdown,
+            "| Name | Qty |\n| --- | --- |\n| Apple | 3 |\n| pie\\|x |  |\n"
+        );
+    }
+
+    #[test]
+    fn pptx_slides_in_presentation_order() {
+        let presentation = r#"<p:presentation xmlns:p="p" xmlns:r="r"><p:sldIdLst>
+            <p:sldId id="256" r:id="rId3"/><p:sldId id="257" r:id="rId2"/>
+        </p:sldIdLst></p:presentation>"#;
+        let rels = r#"<Relationships>
+            <Relationship Id="rId2" Target="slides/slide2.xml"/>
+            <Relationship Id="rId3" Target="slides/slide1.xml"/>
+        </Relationships>"#;
+        let titled = r#"<p:sld xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr>
+              <p:txBody><a:p><a:r><a:t>Roadmap</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:txBody><a:p><a:r><a:t>Ship v2</a:t></a:r></a:p><a:p><a:r><a:t>Hire</a:t></a:r></a:p></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:sld>"#;
+        let untitled = r#"<p:sld xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree>
+            <p:sp><p:txBody><a:p><a:r><a:t>Questions?</a:t></a:r></a:p></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:sld>"#;
+        let data = zip(
+            &[
+                ("ppt/presentation.xml", presentation),
+                ("ppt/_rels/presentation.xml.rels", rels),
+                ("ppt/slides/slide1.xml", titled),
+                ("ppt/slides/slide2.xml", untitled),
+                ("docProps/core.xml", CORE),
+            ],
+            true,
+        );
+        let (title, sections) = extract_pptx(&data).unwrap();
+        assert_eq!(title.as_deref(), Some("Quarterly"));
+        let slides: Vec<(&str, &str)> = sections
+            .iter()
+            .map(|s| (s.title.as_str(), s.markdown.as_str()))
+            .collect();
+        assert_eq!(
+            slides,
+            [
+                ("Roadmap", "- Ship v2\n- Hire"),
+                ("Slide 2", "- Questions?")
+            ]
+        );
+    }
+
+    #[test]
+    fn output_is_cut_at_max_chars() {
+        let dir = std::env::temp_dir().join(format!("sengine-docs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("table.csv");
+        std::fs::write(&path, "a,b\n1,2\n3,4\n").unwrap();
+        let full = extract_blocking(&path, DEFAULT_MAX_CHARS).unwrap();
+        assert!(!full.truncated);
+        assert_eq!(
+            full.markdown,
+            "| a | b |\n| --- | --- |\n| 1 | 2 |\n| 3 | 4 |"
+        );
+        let cut = extract_blocking(&path, 9).unwrap();
+        assert!(cut.truncated);
+        assert_eq!(cut.markdown, "| a | b |");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn csv_quotes_and_escapes() {
+        let rows =
+            parse_csv("\u{feff}name,note\r\n\"Smith, J\",\"said \"\"hi\"\"\nthen left\"\r\n");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["name", "note"],
+                vec!["Smith, J", "said \"hi\"\nthen left"],
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_detects_the_delimiter() {
+        assert_eq!(
+            parse_csv("a;b;c\n1;2,5;3"),
+            vec![vec!["a", "b", "c"], vec!["1", "2,5", "3"]]
+        );
+        assert_eq!(
+            parse_csv("a\tb\n1\t2\n"),
+            vec![vec!["a", "b"], vec!["1", "2"]]
+        );
+    }
+
+    #[test]
+    fn csv_keeps_empty_fields() {
+        assert_eq!(
+            parse_csv("a,,c\n,\n"),
+            vec![vec!["a", "", "c"], vec!["", ""]]
+        );
+        assert!(parse_csv("").is_empty());
+    }
+}
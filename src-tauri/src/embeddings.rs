@@ -0,0 +1,627 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{command, AppHandle, Manager, State};
+use tracing::{debug, info, warn};
+
+use crate::api::http_client;
+use crate::keys::{load_key_config, resolve_key};
+
+/// 默认的嵌入模型
+/// Default embedding model.
+pub const DEFAULT_EMBEDDING_MODEL: &str = "openai/text-embedding-3-small";
+/// 单次请求的文本条数上限
+/// Max texts per request.
+const MAX_BATCH: usize = 64;
+/// 可重试错误的最大重试次数
+/// Max retries of a retryable error.
+const MAX_RETRIES: u32 = 3;
+/// 首次重试前的等待，之后翻倍
+/// Wait before the first retry; doubles after that.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// `Retry-After` 的最长等待
+/// Longest `Retry-After` honored.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
+/// 嵌入缓存文件（位于应用数据目录）
+/// Embedding cache file (in the app data dir).
+const CACHE_FILE: &str = "embedding_cache.bin";
+/// 缓存条目上限，超出后淘汰最早的
+/// Max cached entries; the oldest go first.
+const MAX_CACHE_ENTRIES: usize = 5_000;
+
+/// 嵌入服务设置：Key 档案或自定义地址（如本地 Ollama / LM Studio）
+/// Embedding provider settings: a key profile or a custom base URL (e.g. local Ollama / LM Studio).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingProvider {
+    /// 模型，为空时用默认模型 / model; the default when empty
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 使用的 Key 档案，为空时用默认 Key / key profile; the default key when empty
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// OpenAI 兼容的嵌入服务地址，设置后优先 / OpenAI-compatible base URL; wins when set
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+/// 解析后的嵌入请求目标
+/// A resolved embedding target.
+#[derive(Debug, Clone)]
+pub struct EmbeddingTarget {
+    pub base_url: String,
+    /// 本地服务可不需要 Key / local servers may need no key
+    pub token: Option<String>,
+    pub model: String,
+}
+
+impl EmbeddingTarget {
+    /// 按设置解析：自定义地址配合可选 Key 档案，否则沿用聊天的 Key 解析
+    /// Resolve from settings: a custom URL with an optional key profile, otherwise the chat key.
+    pub fn resolve(app: &AppHandle, provider: &EmbeddingProvider) -> Result<Self, String> {
+        let model = provider
+            .model
+            .clone()
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string());
+        if let Some(base_url) = provider.base_url.as_deref().filter(|u| !u.is_empty()) {
+            let token = provider.key_id.as_deref().and_then(|id| {
+                load_key_config(app)
+                    .profiles
+                    .into_iter()
+                    .find(|p| p.id == id)
+                    .map(|p| p.key)
+            });
+            return Ok(Self {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                token,
+                model,
+            });
+        }
+        let key = resolve_key(app, provider.key_id.as_deref(), None, None)?;
+        Ok(Self {
+            base_url: key.base_url,
+            token: Some(key.token),
+            model,
+        })
+    }
+}
+
+/// 归一化为单位向量（之后点积即余弦相似度）
+/// Normalize to unit length (a dot product is then the cosine similarity).
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// 单次请求失败：是否可重试，以及服务端要求的等待
+/// A failed request: whether it may be retried, and any server-requested wait.
+struct BatchError {
+    message: String,
+    retryable: bool,
+    retry_after: Option<Duration>,
+}
+
+impl BatchError {
+    fn fatal(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retryable: false,
+            retry_after: None,
+        }
+    }
+}
+
+/// 请求一批文本的嵌入（已归一化，顺序与输入一致）
+/// Embed one batch of texts (normalized, in input order).
+async fn request_batch(
+    target: &EmbeddingTarget,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>, BatchError> {
+    let mut request = http_client()
+        .post(format!("{}/embeddings", target.base_url))
+        .json(&json!({ "model": target.model, "input": texts }));
+    if let Some(token) = &target.token {
+        request = request.bearer_auth(token);
+    }
+    // 网络错误可重试 / network errors are retryable
+    let response = request.send().await.map_err(|e| BatchError {
+        message: e.to_string(),
+        retryable: true,
+        retry_after: None,
+    })?;
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(|secs| Duration::from_secs(secs).min(MAX_RETRY_AFTER));
+    // 限流与服务端错误可重试，无论错误体是否为 JSON
+    // Rate limits and server errors are retryable, JSON error body or not.
+    let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+    let body: Value = response.json().await.map_err(|e| BatchError {
+        message: if status.is_success() {
+            e.to_string()
+        } else {
+            format!("Embedding request failed with status: {}", status)
+        },
+        retryable,
+        retry_after,
+    })?;
+    if !status.is_success() {
+        return Err(BatchError {
+            message: body
+                .pointer("/error/message")
+                .and_then(|m| m.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Embedding request failed with status: {}", status)),
+            retryable,
+            retry_after,
+        });
+    }
+
+    let mut rows: Vec<(usize, Vec<f32>)> = body
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| BatchError::fatal("Embedding response has no data"))?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let index = item
+                .get("index")
+                .and_then(|i| i.as_u64())
+                .map_or(i, |i| i as usize);
+            let vector = item
+                .get("embedding")
+                .and_then(|e| e.as_array())
+                .ok_or_else(|| BatchError::fatal("Embedding item has no vector"))?
+                .iter()
+                .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+                .collect();
+            Ok((index, vector))
+        })
+        .collect::<Result<_, BatchError>>()?;
+    if rows.len() != texts.len() {
+        return Err(BatchError::fatal(format!(
+            "Expected {} embeddings, got {}",
+            texts.len(),
+            rows.len()
+        )));
+    }
+    rows.sort_by_key(|(index, _)| *index);
+    Ok(rows
+        .into_iter()
+        .map(|(_, mut vector)| {
+            normalize(&mut vector);
+            vector
+        })
+        .collect())
+}
+
+/// 请求一批嵌入，限流、5xx 与网络错误按指数退避重试
+/// Embed one batch, retrying rate limits, 5xx and network errors with exponential backoff.
+async fn request_with_retry(
+    target: &EmbeddingTarget,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+    let mut attempt = 0;
+    loop {
+        match request_batch(target, texts).await {
+            Ok(vectors) => return Ok(vectors),
+            Err(e) if e.retryable && attempt < MAX_RETRIES => {
+                let delay = e
+                    .retry_after
+                    .unwrap_or(RETRY_BASE_DELAY * 2u32.pow(attempt));
+                warn!(
+                    model = %target.model,
+                    attempt = attempt + 1,
+                    "embedding request failed, retrying: {}",
+                    e.message
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.message),
+        }
+    }
+}
+
+/// 分批计算嵌入（不经缓存），每完成一批回调一次已完成条数
+/// Embed in batches (bypassing the cache), reporting the number done after each batch.
+pub async fn embed_texts(
+    target: &EmbeddingTarget,
+    texts: &[String],
+    mut progress: impl FnMut(usize),
+) -> Result<Vec<Vec<f32>>, String> {
+    let mut vectors = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(MAX_BATCH) {
+        vectors.extend(request_with_retry(target, batch).await?);
+        progress(vectors.len());
+    }
+    Ok(vectors)
+}
+
+type CacheKey = [u8; 32];
+
+/// 缓存键：服务地址、模型与文本的 SHA-256（不同服务的同名模型向量不同）
+/// Cache key: SHA-256 of the base URL, the model and the text (same-named models on different
+/// services give different vectors).
+fn cache_key(target: &EmbeddingTarget, text: &str) -> CacheKey {
+    let mut hasher = Sha256::new();
+    hasher.update(target.base_url.as_bytes());
+    hasher.update([0]);
+    hasher.update(target.model.as_bytes());
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+    hasher.finalize().into()
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    vectors: HashMap<CacheKey, Vec<f32>>,
+    /// 插入顺序，用于淘汰 / insertion order, for eviction
+    order: VecDeque<CacheKey>,
+    /// 文件中的记录数（含已淘汰的）/ records in the file, evicted ones included
+    records_on_disk: usize,
+}
+
+impl CacheEntries {
+    fn insert(&mut self, key: CacheKey, vector: Vec<f32>) {
+        if self.vectors.insert(key, vector).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > MAX_CACHE_ENTRIES {
+            if let Some(old) = self.order.pop_front() {
+                self.vectors.remove(&old);
+            }
+        }
+    }
+}
+
+/// 按文本哈希缓存的嵌入，追加写入二进制文件
+/// Embeddings cached by text hash, appended to a binary file.
+///
+/// 每条记录为 32 字节键、u32 维数与 f32 向量（小端）。
+/// Each record is a 32-byte key, a u32 dimension count and the f32 vector (little endian).
+pub struct EmbeddingCache {
+    path: PathBuf,
+    entries: Mutex<CacheEntries>,
+}
+
+fn encode_record(out: &mut Vec<u8>, key: &CacheKey, vector: &[f32]) {
+    out.extend_from_slice(key);
+    out.extend_from_slice(&(vector.len() as u32).to_le_bytes());
+    for v in vector {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+impl EmbeddingCache {
+    /// 从应用数据目录加载；记录过多时压缩文件
+    /// Load from the app data dir, compacting the file when it has grown.
+    pub fn load(data_dir: PathBuf) -> Self {
+        let path = data_dir.join(CACHE_FILE);
+        let mut entries = CacheEntries::default();
+        let bytes = std::fs::read(&path).unwrap_or_default();
+        let mut at = 0;
+        while let Some(header) = bytes.get(at..at + 36) {
+            let key: CacheKey = header[..32].try_into().unwrap();
+            let dims = u32::from_le_bytes(header[32..36].try_into().unwrap()) as usize;
+            let Some(body) = bytes.get(at + 36..at + 36 + dims * 4) else {
+                break;
+            };
+            let vector = body
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            entries.insert(key, vector);
+            entries.records_on_disk += 1;
+            at += 36 + dims * 4;
+        }
+        let cache = Self {
+            path,
+            entries: Mutex::new(entries),
+        };
+        let mut entries = cache.entries.lock().unwrap();
+        if entries.records_on_disk > entries.order.len() || at < bytes.len() {
+            cache.rewrite(&mut entries);
+        }
+        drop(entries);
+        cache
+    }
+
+    /// 重写文件，仅保留现存条目
+    /// Rewrite the file with only the live entries.
+    fn rewrite(&self, entries: &mut CacheEntries) {
+        let mut out = Vec::new();
+        for key in &entries.order {
+            encode_record(&mut out, key, &entries.vectors[key]);
+        }
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&self.path, out));
+        match result {
+            Ok(()) => entries.records_on_disk = entries.order.len(),
+            Err(e) => warn!("Failed to write embedding cache: {}", e),
+        }
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Vec<f32>> {
+        self.entries.lock().unwrap().vectors.get(key).cloned()
+    }
+
+    fn insert(&self, new: Vec<(CacheKey, Vec<f32>)>) {
+        if new.is_empty() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let mut out = Vec::new();
+        for (key, vector) in new {
+            encode_record(&mut out, &key, &vector);
+            entries.insert(key, vector);
+            entries.records_on_disk += 1;
+        }
+        // 淘汰的记录累积到一定量后压缩 / compact once evicted records pile up
+        if entries.records_on_disk > MAX_CACHE_ENTRIES * 2 {
+            self.rewrite(&mut entries);
+            return;
+        }
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+            })
+            .and_then(|mut file| file.write_all(&out));
+        if let Err(e) = result {
+            warn!("Failed to append to embedding cache: {}", e);
+        }
+    }
+
+    fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        *entries = CacheEntries::default();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 计算嵌入，先查缓存，仅请求未命中的文本；返回向量与命中数
+/// Embed via the cache, requesting only the misses; returns the vectors and the hit count.
+pub async fn embed_cached(
+    app: &AppHandle,
+    target: &EmbeddingTarget,
+    texts: &[String],
+) -> Result<(Vec<Vec<f32>>, usize), String> {
+    let cache = app.state::<EmbeddingCache>();
+    let keys: Vec<CacheKey> = texts.iter().map(|t| cache_key(target, t)).collect();
+    let mut vectors: Vec<Option<Vec<f32>>> = keys.iter().map(|k| cache.get(k)).collect();
+    let hits = vectors.iter().filter(|v| v.is_some()).count();
+
+    // 相同文本只请求一次 / identical texts are requested once
+    let mut misses: Vec<usize> = Vec::new();
+    let mut seen = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        if vectors[i].is_none() && seen.insert(*key, i).is_none() {
+            misses.push(i);
+        }
+    }
+    if !misses.is_empty() {
+        let pending: Vec<String> = misses.iter().map(|&i| texts[i].clone()).collect();
+        let fresh = embed_texts(target, &pending, |_| {}).await?;
+        let mut new = Vec::with_capacity(fresh.len());
+        for (&i, vector) in misses.iter().zip(fresh) {
+            new.push((keys[i], vector.clone()));
+            vectors[i] = Some(vector);
+        }
+        cache.insert(new);
+        for (i, key) in keys.iter().enumerate() {
+            if vectors[i].is_none() {
+                vectors[i] = vectors[seen[key]].clone();
+            }
+        }
+    }
+    debug!(model = %target.model, total = texts.len(), hits, "embedded texts");
+    Ok((vectors.into_iter().flatten().collect(), hits))
+}
+
+/// 余弦相似度
+/// Cosine similarity.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// 按余弦相似度取最相近的 `top_k` 项，返回 (下标, 分数)
+/// The `top_k` closest items by cosine similarity, as (position, score).
+pub fn cosine_top_k<'a>(
+    query: &[f32],
+    candidates: impl IntoIterator<Item = &'a [f32]>,
+    top_k: usize,
+) -> Vec<(usize, f32)> {
+    let mut scored: Vec<(usize, f32)> = candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(_, v)| v.len() == query.len())
+        .map(|(i, v)| (i, cosine_similarity(query, v)))
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(top_k);
+    scored
+}
+
+/// `embed` 的返回
+/// Result of `embed`.
+#[derive(Debug, Serialize)]
+pub struct EmbedResult {
+    pub model: String,
+    pub dims: usize,
+    /// 单位向量，顺序与输入一致 / unit vectors in input order
+    pub vectors: Vec<Vec<f32>>,
+    /// 命中缓存的条数 / texts served from the cache
+    pub cached: usize,
+}
+
+/// 计算文本嵌入（分批、重试、按文本哈希缓存）
+/// Compute text embeddings (batched, retried, cached by text hash).
+///
+/// 使用 `key_id` 指定的 Key 档案（为空时用默认 Key）及其服务地址。
+/// Uses the key profile named by `key_id` (the default key when empty) and its base URL.
+#[command]
+pub async fn embed(
+    app: AppHandle,
+    texts: Vec<String>,
+    model: Option<String>,
+    key_id: Option<String>,
+) -> Result<EmbedResult, String> {
+    if texts.is_empty() {
+        return Err("No texts to embed".into());
+    }
+    let provider = EmbeddingProvider {
+        model,
+        key_id,
+        base_url: None,
+    };
+    let target = EmbeddingTarget::resolve(&app, &provider)?;
+    let (vectors, cached) = embed_cached(&app, &target, &texts).await?;
+    info!(model = %target.model, count = texts.len(), cached, "embed");
+    Ok(EmbedResult {
+        model: target.model,
+        dims: vectors.first().map_or(0, Vec::len),
+        vectors,
+        cached,
+    })
+}
+
+/// 待检索的向量
+/// A vector to search.
+#[derive(Debug, Deserialize)]
+pub struct StoredVector {
+    pub id: String,
+    pub vector: Vec<f32>,
+}
+
+/// 检索命中
+/// A search hit.
+#[derive(Debug, Serialize)]
+pub struct VectorHit {
+    pub id: String,
+    pub score: f32,
+}
+
+/// 在给定向量中按余弦相似度检索
+/// Search the given vectors by cosine similarity.
+#[command]
+pub fn search_vectors(query: Vec<f32>, vectors: Vec<StoredVector>, top_k: usize) -> Vec<VectorHit> {
+    cosine_top_k(&query, vectors.iter().map(|v| v.vector.as_slice()), top_k)
+        .into_iter()
+        .map(|(i, score)| VectorHit {
+            id: vectors[i].id.clone(),
+            score,
+        })
+        .collect()
+}
+
+/// 清空嵌入缓存
+/// Clear the embedding cache.
+#[command]
+pub fn clear_embedding_cache(cache: State<'_, EmbeddingCache>) {
+    cache.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, serve_routes};
+
+    fn target(base_url: &str, model: &str) -> EmbeddingTarget {
+        EmbeddingTarget {
+            base_url: base_url.into(),
+            token: None,
+            model: model.into(),
+        }
+    }
+
+    #[test]
+    fn cache_keys_depend_on_service_and_model() {
+        let remote = target("https://openrouter.ai/api/v1", "m");
+        let local = target("http://localhost:11434/v1", "m");
+        assert_eq!(
+            cache_key(&remote, "text"),
+            cache_key(&remote.clone(), "text")
+        );
+        assert_ne!(cache_key(&remote, "text"), cache_key(&local, "text"));
+        assert_ne!(
+            cache_key(&remote, "text"),
+            cache_key(&target(&remote.base_url, "n"), "text")
+        );
+        assert_ne!(cache_key(&remote, "text"), cache_key(&remote, "other"));
+    }
+
+    #[test]
+    fn cosine_similarity_ignores_magnitude() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[3.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 2.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn top_k_is_sorted_and_skips_mismatched_dims() {
+        let candidates: Vec<Vec<f32>> = vec![
+            vec![0.0, 1.0],
+            vec![1.0, 0.0, 0.0],
+            vec![1.0, 0.1],
+            vec![1.0, 1.0],
+        ];
+        let top = cosine_top_k(&[1.0, 0.0], candidates.iter().map(Vec::as_slice), 2);
+        let positions: Vec<usize> = top.iter().map(|(i, _)| *i).collect();
+        assert_eq!(positions, [2, 3]);
+        assert!(top[0].1 > top[1].1);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_retryable_without_a_json_body() {
+        let server = serve_routes(vec![(
+            "/v1/embeddings",
+            response("429 Too Many Requests", "Retry-After: 2\r\n", "slow down"),
+        )]);
+        let limited = target(&format!("{}/v1", server.base), "m");
+        let error = request_batch(&limited, &["text".into()]).await.unwrap_err();
+        assert!(error.retryable);
+        assert_eq!(error.retry_after, Some(Duration::from_secs(2)));
+        assert!(error.message.contains("429"));
+
+        let server = serve_routes(vec![(
+            "/v1/embeddings",
+            response("400 Bad Request", "", "<html>bad</html>"),
+        )]);
+        let rejected = target(&format!("{}/v1", server.base), "m");
+        let error = request_batch(&rejected, &["text".into()])
+            .await
+            .unwrap_err();
+        assert!(!error.retryable);
+    }
+}
@@ -42,3 +42,12 @@ pub fn monitor_size_in_dip(monitor: &tauri::Monitor, scale: f64) -> (f64, f64) {
         phys_to_dip_u32(s.height, scale),
     )
 }
+
+/// 当前 Unix 时间戳（毫秒）
+/// Current Unix timestamp in milliseconds.
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,434 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Manager};
+use tracing::info;
+
+use crate::attachments::{AttachmentInfo, AttachmentStore, ATTACHMENT_SCHEME};
+use crate::models::ModelCatalog;
+use crate::settings::{read_setting, write_setting};
+
+/// 图片预处理配置在 store 中的字段名
+/// Store field holding the image pipeline config.
+pub const IMAGE_PIPELINE_FIELD: &str = "image_pipeline";
+
+/// 输入图片的最大字节数
+/// Max size of an input image.
+const MAX_INPUT_BYTES: usize = 50 * 1024 * 1024;
+/// 模型生成图片的最大字节数
+/// Max size of an image generated by a model.
+const MAX_OUTPUT_BYTES: usize = 50 * 1024 * 1024;
+/// 解码允许的最大边长
+/// Max edge length accepted by the decoder.
+const MAX_DECODE_EDGE: u32 = 16_384;
+
+/// 图片预处理配置
+/// Image pipeline config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImagePipelineConfig {
+    /// 最长边上限，为空时按模型选择 / max long edge; chosen per model when empty
+    #[serde(default)]
+    pub max_dimension: Option<u32>,
+    /// JPEG 质量（1-100）/ JPEG quality (1-100)
+    #[serde(default = "default_quality")]
+    pub jpeg_quality: u8,
+    /// 透明图也压成 JPEG（铺白底）/ flatten transparent images onto white and send JPEG
+    #[serde(default)]
+    pub flatten_alpha: bool,
+}
+
+fn default_quality() -> u8 {
+    85
+}
+
+impl Default for ImagePipelineConfig {
+    fn default() -> Self {
+        Self {
+            max_dimension: None,
+            jpeg_quality: default_quality(),
+            flatten_alpha: false,
+        }
+    }
+}
+
+impl ImagePipelineConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.jpeg_quality) {
+            return Err("jpeg_quality must be between 1 and 100".into());
+        }
+        if self
+            .max_dimension
+            .is_some_and(|d| !(64..=8192).contains(&d))
+        {
+            return Err("max_dimension must be between 64 and 8192".into());
+        }
+        Ok(())
+    }
+}
+
+/// 单次调用覆盖的参数
+/// Per-call overrides.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImageOptions {
+    /// 目标模型，用于选择尺寸与估算 token / target model, for sizing and the token estimate
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub max_dimension: Option<u32>,
+    #[serde(default)]
+    pub quality: Option<u8>,
+}
+
+/// 处理后的图片
+/// A processed image.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedImage {
+    pub data_url: String,
+    pub mime: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub original_bytes: usize,
+    pub bytes: usize,
+    /// 按模型计费规则估算的图片 token / image tokens estimated with the model's rules
+    pub estimated_tokens: u64,
+}
+
+/// 视觉模型的图片计费家族
+/// Image billing family of a vision model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VisionFamily {
+    /// 按像素：宽 × 高 / 750 / per pixel: w × h / 750
+    Anthropic,
+    /// 512 像素瓦片：85 + 170 × 瓦片数 / 512 px tiles: 85 + 170 × tiles
+    OpenAi,
+    /// 768 像素瓦片，每块 258 / 768 px tiles, 258 each
+    Gemini,
+}
+
+impl VisionFamily {
+    fn of(model: Option<&str>) -> Self {
+        let model = model.unwrap_or("").to_ascii_lowercase();
+        if model.starts_with("anthropic/") || model.contains("claude") {
+            Self::Anthropic
+        } else if model.starts_with("google/") || model.contains("gemini") {
+            Self::Gemini
+        } else {
+            Self::OpenAi
+        }
+    }
+
+    /// 超过后服务端会再缩放的最长边
+    /// Long edge beyond which the provider downsizes anyway.
+    fn max_dimension(self) -> u32 {
+        match self {
+            Self::Anthropic => 1568,
+            Self::OpenAi => 2048,
+            Self::Gemini => 3072,
+        }
+    }
+
+    fn estimate_tokens(self, width: u32, height: u32) -> u64 {
+        let (w, h) = (width.max(1) as u64, height.max(1) as u64);
+        match self {
+            Self::Anthropic => (w * h).div_ceil(750),
+            Self::OpenAi => {
+                // 先缩放到 2048 内，再把短边缩到 768
+                // Fit within 2048, then scale the short side down to 768
+                let (w, h) = fit_within(w, h, 2048);
+                let (w, h) = if w.min(h) > 768 {
+                    let scale = 768.0 / w.min(h) as f64;
+                    ((w as f64 * scale) as u64, (h as f64 * scale) as u64)
+                } else {
+                    (w, h)
+                };
+                85 + 170 * w.div_ceil(512) * h.div_ceil(512)
+            }
+            Self::Gemini => {
+                if w <= 384 && h <= 384 {
+                    258
+                } else {
+                    258 * w.div_ceil(768) * h.div_ceil(768)
+                }
+            }
+        }
+    }
+}
+
+/// 等比缩放到最长边不超过 `max`
+/// Scale proportionally so the long edge is at most `max`.
+fn fit_within(w: u64, h: u64, max: u64) -> (u64, u64) {
+    if w.max(h) <= max {
+        return (w, h);
+    }
+    let scale = max as f64 / w.max(h) as f64;
+    (
+        ((w as f64 * scale).round() as u64).max(1),
+        ((h as f64 * scale).round() as u64).max(1),
+    )
+}
+
+/// 读取来源：data URL 或文件路径，超过 `max_bytes` 时报错
+/// Read the source, a data URL or a file path; errors past `max_bytes`.
+pub fn read_source(source: &str, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let bytes = if let Some(rest) = source.strip_prefix("data:") {
+        let (_, payload) = rest.split_once(";base64,").ok_or("Unsupported data URL")?;
+        STANDARD
+            .decode(payload.trim())
+            .map_err(|e| format!("Invalid base64: {}", e))?
+    } else {
+        let size = std::fs::metadata(source).map_err(|e| e.to_string())?.len();
+        if size > max_bytes as u64 {
+            return Err(format!("File is larger than {} bytes", max_bytes));
+        }
+        std::fs::read(Path::new(source)).map_err(|e| e.to_string())?
+    };
+    if bytes.len() > max_bytes {
+        return Err(format!("File is larger than {} bytes", max_bytes));
+    }
+    Ok(bytes)
+}
+
+/// 解码（GIF 取首帧）并按 EXIF 方向摆正；重新编码即去除 EXIF
+/// Decode (first frame of a GIF) and apply the EXIF orientation; re-encoding drops EXIF.
+fn decode(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    match reader.format() {
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif) => {}
+        Some(other) => return Err(format!("Unsupported image format: {:?}", other)),
+        None => return Err("Unrecognized image format".into()),
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_EDGE);
+    limits.max_image_height = Some(MAX_DECODE_EDGE);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// 是否有实际透明像素（不只是带 alpha 通道）
+/// Whether any pixel is actually transparent (not just an alpha channel).
+fn is_transparent(image: &DynamicImage) -> bool {
+    image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p[3] < 255)
+}
+
+/// 铺白底去除透明
+/// Flatten transparency onto white.
+fn flatten(image: &DynamicImage) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let mut out = RgbaImage::from_pixel(rgba.width(), rgba.height(), Rgba([255, 255, 255, 255]));
+    image::imageops::overlay(&mut out, &rgba, 0, 0);
+    DynamicImage::ImageRgba8(out)
+}
+
+/// 执行预处理（阻塞调用）
+/// Run the pipeline (blocking).
+fn prepare_blocking(
+    source: &str,
+    config: &ImagePipelineConfig,
+    options: &ImageOptions,
+) -> Result<PreparedImage, String> {
+    let bytes = read_source(source, MAX_INPUT_BYTES)?;
+    let image = decode(&bytes)?;
+    let (original_width, original_height) = (image.width(), image.height());
+
+    let family = VisionFamily::of(options.model.as_deref());
+    let max = options
+        .max_dimension
+        .or(config.max_dimension)
+        .unwrap_or_else(|| family.max_dimension());
+    let (w, h) = fit_within(original_width as u64, original_height as u64, max as u64);
+    let image = if (w as u32, h as u32) != (original_width, original_height) {
+        image.resize_exact(w as u32, h as u32, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let (width, height) = (image.width(), image.height());
+
+    let quality = options.quality.unwrap_or(config.jpeg_quality).clamp(1, 100);
+    let keep_alpha = !config.flatten_alpha && is_transparent(&image);
+    let mut out = Vec::new();
+    let mime = if keep_alpha {
+        image
+            .to_rgba8()
+            .write_with_encoder(PngEncoder::new_with_quality(
+                &mut out,
+                CompressionType::Best,
+                PngFilter::Adaptive,
+            ))
+            .map_err(|e| e.to_string())?;
+        "image/png"
+    } else {
+        let image = if image.color().has_alpha() {
+            flatten(&image)
+        } else {
+            image
+        };
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality))
+            .map_err(|e| e.to_string())?;
+        "image/jpeg"
+    };
+
+    Ok(PreparedImage {
+        data_url: format!("data:{};base64,{}", mime, STANDARD.encode(&out)),
+        mime,
+        width,
+        height,
+        original_width,
+        original_height,
+        original_bytes: bytes.len(),
+        bytes: out.len(),
+        estimated_tokens: family.estimate_tokens(width, height),
+    })
+}
+
+/// 预处理视觉输入图片：解码 PNG/JPEG/WebP/GIF、去除 EXIF、缩放并重新编码
+/// Preprocess a vision input: decode PNG/JPEG/WebP/GIF, strip EXIF, downsize and re-encode.
+///
+/// `source` 为 data URL 或文件路径。返回 data URL 及估算的图片 token。
+/// `source` is a data URL or a file path. Returns a data URL and the estimated image tokens.
+#[command]
+pub async fn prepare_image(
+    app: AppHandle,
+    source: String,
+    options: Option<ImageOptions>,
+) -> Result<PreparedImage, String> {
+    let config: ImagePipelineConfig = read_setting(&app, IMAGE_PIPELINE_FIELD).unwrap_or_default();
+    let options = options.unwrap_or_default();
+    let prepared =
+        tauri::async_runtime::spawn_blocking(move || prepare_blocking(&source, &config, &options))
+            .await
+            .map_err(|e| e.to_string())??;
+    info!(
+        original_bytes = prepared.original_bytes,
+        bytes = prepared.bytes,
+        width = prepared.width,
+        height = prepared.height,
+        tokens = prepared.estimated_tokens,
+        "prepared image"
+    );
+    Ok(prepared)
+}
+
+/// 模型是否能输出图片（`architecture.output_modalities` 含 `image`）
+/// Whether the model can output images (`image` in `architecture.output_modalities`).
+async fn supports_image_output(app: &AppHandle, model: &str) -> bool {
+    app.state::<ModelCatalog>()
+        .get(model)
+        .await
+        .and_then(|m| {
+            m.pointer("/architecture/output_modalities")?
+                .as_array()
+                .cloned()
+        })
+        .is_some_and(|modalities| modalities.iter().any(|m| m == "image"))
+}
+
+/// 为能输出图片的模型请求图片与文本模态（请求体已指定时不改），返回是否请求了图片
+/// Request image and text modalities from models that can output images (unless the body
+/// already sets them); returns whether images were requested.
+pub async fn apply_image_modalities(app: &AppHandle, body: &mut Value, model: &str) -> bool {
+    if let Some(modalities) = body.get("modalities").and_then(|m| m.as_array()) {
+        return modalities.iter().any(|m| m == "image");
+    }
+    if !supports_image_output(app, model).await {
+        return false;
+    }
+    if let Some(object) = body.as_object_mut() {
+        object.insert("modalities".into(), json!(["image", "text"]));
+    }
+    true
+}
+
+/// 从流式块中收集模型生成的图片（`delta.images`）
+/// Collects model-generated images from stream chunks (`delta.images`).
+#[derive(Default)]
+pub struct ImageOutputCollector {
+    count: usize,
+}
+
+impl ImageOutputCollector {
+    /// 返回此块中新出现的图片地址及其序号
+    /// Return the image URLs this chunk adds, with their indexes.
+    pub fn feed(&mut self, chunk: &Value) -> Vec<(usize, String)> {
+        let Some(images) = chunk
+            .pointer("/choices/0/delta/images")
+            .and_then(|i| i.as_array())
+        else {
+            return Vec::new();
+        };
+        images
+            .iter()
+            .filter_map(|image| image.pointer("/image_url/url")?.as_str())
+            .map(|url| {
+                self.count += 1;
+                (self.count - 1, url.to_string())
+            })
+            .collect()
+    }
+}
+
+/// 将生成的图片存入附件存储，返回 `attachment://` 引用
+/// Save a generated image to the attachment store; returns its `attachment://` reference.
+pub fn save_generated_image(
+    app: &AppHandle,
+    url: &str,
+    index: usize,
+    owner: &str,
+) -> Result<AttachmentInfo, String> {
+    if !url.starts_with("data:") {
+        return Err("Generated image is not a data URL".into());
+    }
+    let mime = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split(';').next())
+        .filter(|m| m.starts_with("image/"))
+        .unwrap_or("image/png")
+        .to_string();
+    let bytes = read_source(url, MAX_OUTPUT_BYTES)?;
+    let extension = mime.strip_prefix("image/").unwrap_or("png").to_string();
+    let name = format!("generated-{}.{}", index + 1, extension);
+    let meta = app
+        .state::<AttachmentStore>()
+        .put(&bytes, mime, Some(name), owner)?;
+    Ok(AttachmentInfo {
+        uri: format!("{}{}", ATTACHMENT_SCHEME, meta.hash),
+        meta,
+    })
+}
+
+/// 读取图片预处理配置
+/// Get the image pipeline config.
+#[command]
+pub fn get_image_pipeline_config(app: AppHandle) -> ImagePipelineConfig {
+    read_setting(&app, IMAGE_PIPELINE_FIELD).unwrap_or_default()
+}
+
+/// 保存图片预处理配置
+/// Save the image pipeline config.
+#[command]
+pub fn save_image_pipeline_config(
+    app: AppHandle,
+    config: ImagePipelineConfig,
+) -> Result<(), String> {
+    config.validate()?;
+    write_setting(&app, IMAGE_PIPELINE_FIELD, &config)
+}
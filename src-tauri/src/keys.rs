@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, AppHandle, State};
+
+use crate::api::{http_client, OPENROUTER_API_BASE};
+use crate::helpers::now_millis;
+use crate::settings::{read_setting, write_setting};
+
+/// 旧版单 Key 在 store 中的字段名（与前端 `API_KEY_FIELD` 一致）
+/// Store field of the legacy single key (same as frontend `API_KEY_FIELD`).
+pub const LEGACY_KEY_FIELD: &str = "api_key";
+/// Key 档案在 store 中的字段名
+/// Store field holding the key profiles.
+pub const KEY_PROFILES_FIELD: &str = "key_profiles";
+
+/// 额度信息缓存有效期（毫秒）
+/// How long cached credit/limit info stays fresh (ms).
+const KEY_INFO_TTL_MS: u64 = 60_000;
+
+/// 命名 Key 档案
+/// A named API key profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyProfile {
+    /// 唯一 ID / unique id
+    pub id: String,
+    /// 显示名称 / display name
+    pub name: String,
+    /// 明文 Key / plain-text key
+    pub key: String,
+    /// OpenAI 兼容服务地址（默认 OpenRouter）/ OpenAI-compatible base URL (defaults to OpenRouter)
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+/// 持久化的 Key 配置
+/// Persisted key configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyConfig {
+    #[serde(default)]
+    pub profiles: Vec<KeyProfile>,
+    /// 默认 Key 的 ID / id of the default key
+    #[serde(default)]
+    pub default_key: Option<String>,
+    /// 会话 → Key ID 覆盖 / conversation → key id overrides
+    #[serde(default)]
+    pub conversation_keys: HashMap<String, String>,
+}
+
+/// 返回给前端的 Key 档案（隐藏明文）
+/// Key profile as returned to the frontend (key masked).
+#[derive(Debug, Serialize)]
+pub struct KeyProfileView {
+    pub id: String,
+    pub name: String,
+    pub masked_key: String,
+    pub base_url: Option<String>,
+    pub is_default: bool,
+}
+
+/// Key 列表视图
+/// Key list view.
+#[derive(Debug, Serialize)]
+pub struct KeyConfigView {
+    pub profiles: Vec<KeyProfileView>,
+    pub default_key: Option<String>,
+    pub conversation_keys: HashMap<String, String>,
+}
+
+/// 新建/更新 Key 档案的入参；`key` 为空时保留原值
+/// Input for creating/updating a profile; an empty `key` keeps the old one.
+#[derive(Debug, Deserialize)]
+pub struct KeyProfileInput {
+    pub id: Option<String>,
+    pub name: String,
+    pub key: Option<String>,
+    pub base_url: Option<String>,
+}
+
+/// 从 `/credits` 与 `/key` 获取的额度信息
+/// Credit and limit info fetched from `/credits` and `/key`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KeyInfo {
+    pub key_id: String,
+    /// 账户总充值 / account total credits
+    pub total_credits: Option<f64>,
+    /// 账户总消耗 / account total usage
+    pub total_usage: Option<f64>,
+    /// Key 标签 / key label
+    pub label: Option<String>,
+    /// 该 Key 已用额度 / usage of this key
+    pub usage: Option<f64>,
+    /// 该 Key 限额 / limit of this key
+    pub limit: Option<f64>,
+    /// 剩余限额 / remaining limit
+    pub limit_remaining: Option<f64>,
+    pub is_free_tier: Option<bool>,
+    /// 获取时间（毫秒）/ fetch time (ms)
+    pub fetched_at: u64,
+}
+
+/// 实际用于请求的 Key
+/// The key actually used for a request.
+#[derive(Debug, Clone)]
+pub struct ResolvedKey {
+    /// 档案 ID；旧版单 Key 为 `api_key` / profile id, `api_key` for the legacy key
+    pub id: String,
+    pub token: String,
+    pub base_url: String,
+}
+
+/// Key 管理器：缓存各 Key 的额度信息
+/// Key manager: caches credit info per key.
+#[derive(Default)]
+pub struct KeyManager {
+    info_cache: Mutex<HashMap<String, KeyInfo>>,
+}
+
+impl KeyManager {
+    fn cached(&self, key_id: &str) -> Option<KeyInfo> {
+        let cache = self.info_cache.lock().unwrap();
+        cache
+            .get(key_id)
+            .filter(|info| now_millis().saturating_sub(info.fetched_at) < KEY_INFO_TTL_MS)
+            .cloned()
+    }
+
+    fn store(&self, info: KeyInfo) {
+        self.info_cache
+            .lock()
+            .unwrap()
+            .insert(info.key_id.clone(), info);
+    }
+
+    fn invalidate(&self, key_id: &str) {
+        self.info_cache.lock().unwrap().remove(key_id);
+    }
+}
+
+/// 读取 Key 配置
+/// Load the key configuration.
+pub fn load_key_config(app: &AppHandle) -> KeyConfig {
+    read_setting(app, KEY_PROFILES_FIELD).unwrap_or_default()
+}
+
+fn save_key_config(app: &AppHandle, config: &KeyConfig) -> Result<(), String> {
+    write_setting(app, KEY_PROFILES_FIELD, config)
+}
+
+/// 隐藏 Key 中间部分
+/// Mask the middle part of a key.
+fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..8].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+fn view_of(config: &KeyConfig) -> KeyConfigView {
+    KeyConfigView {
+        profiles: config
+            .profiles
+            .iter()
+            .map(|p| KeyProfileView {
+                id: p.id.clone(),
+                name: p.name.clone(),
+                masked_key: mask_key(&p.key),
+                base_url: p.base_url.clone(),
+                is_default: config.default_key.as_deref() == Some(p.id.as_str()),
+            })
+            .collect(),
+        default_key: config.default_key.clone(),
+        conversation_keys: config.conversation_keys.clone(),
+    }
+}
+
+/// 解析请求使用的 Key
+/// Resolve which key a request should use.
+///
+/// 优先级：显式 `key_id` > 会话覆盖 > 默认档案 > 前端传入的 token > store 中的旧版 Key。
+/// Priority: explicit `key_id` > conversation override > default profile >
+/// token passed by the frontend > legacy key in the store.
+pub fn resolve_key(
+    app: &AppHandle,
+    key_id: Option<&str>,
+    conversation_id: Option<&str>,
+    fallback_token: Option<&str>,
+) -> Result<ResolvedKey, String> {
+    let config = load_key_config(app);
+
+    let wanted = key_id
+        .map(str::to_string)
+        .or_else(|| conversation_id.and_then(|c| config.conversation_keys.get(c).cloned()))
+        .or_else(|| config.default_key.clone());
+
+    if let Some(id) = wanted {
+        if id != LEGACY_KEY_FIELD {
+            let profile = config
+                .profiles
+                .iter()
+                .find(|p| p.id == id)
+                .ok_or_else(|| format!("Key profile not found: {}", id))?;
+            return Ok(ResolvedKey {
+                id: profile.id.clone(),
+                token: profile.key.clone(),
+                base_url: profile
+                    .base_url
+                    .clone()
+                    .unwrap_or_else(|| OPENROUTER_API_BASE.to_string()),
+            });
+        }
+    }
+
+    let token = fallback_token
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .or_else(|| read_setting::<_, String>(app, LEGACY_KEY_FIELD))
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| "No API key configured".to_string())?;
+
+    Ok(ResolvedKey {
+        id: LEGACY_KEY_FIELD.to_string(),
+        token,
+        base_url: OPENROUTER_API_BASE.to_string(),
+    })
+}
+
+/// GET 一个 OpenRouter 接口并取出 `data`
+/// GET an OpenRouter endpoint and return its `data` field.
+async fn get_data(key: &ResolvedKey, path: &str) -> Result<Value, String> {
+    let response = http_client()
+        .get(format!("{}{}", key.base_url, path))
+        .bearer_auth(&key.token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    let json: Value = response.json().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        let msg = json
+            .pointer("/error/message")
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Request failed with status: {}", status));
+        return Err(msg);
+    }
+    Ok(json.get("data").cloned().unwrap_or(Value::Null))
+}
+
+/// 列出所有 Key 档案
+/// List all key profiles.
+#[command]
+pub fn list_key_profiles(app: AppHandle) -> KeyConfigView {
+    view_of(&load_key_config(&app))
+}
+
+/// 新建或更新 Key 档案；第一个档案自动成为默认
+/// Create or update a key profile; the first profile becomes the default.
+#[command]
+pub fn save_key_profile(
+    app: AppHandle,
+    manager: State<'_, KeyManager>,
+    profile: KeyProfileInput,
+) -> Result<KeyConfigView, String> {
+    let mut config = load_key_config(&app);
+    let name = profile.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Key name is empty".into());
+    }
+    let new_key = profile
+        .key
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty());
+    let base_url = profile
+        .base_url
+        .map(|u| u.trim().trim_end_matches('/').to_string())
+        .filter(|u| !u.is_empty());
+
+    match profile
+        .id
+        .as_deref()
+        .and_then(|id| config.profiles.iter_mut().find(|p| p.id == id))
+    {
+        Some(existing) => {
+            existing.name = name;
+            existing.base_url = base_url;
+            if let Some(key) = new_key {
+                existing.key = key;
+            }
+            manager.invalidate(&existing.id);
+        }
+        None => {
+            let key = new_key.ok_or_else(|| "Key is empty".to_string())?;
+            let id = profile
+                .id
+                .unwrap_or_else(|| format!("key-{}", now_millis()));
+            config.profiles.push(KeyProfile {
+                id,
+                name,
+                key,
+                base_url,
+            });
+        }
+    }
+
+    if config.default_key.is_none() {
+        config.default_key = config.profiles.first().map(|p| p.id.clone());
+    }
+    save_key_config(&app, &config)?;
+    Ok(view_of(&config))
+}
+
+/// 删除 Key 档案，并清理引用它的默认值与会话覆盖
+/// Delete a key profile and drop the default/overrides pointing to it.
+#[command]
+pub fn delete_key_profile(
+    app: AppHandle,
+    manager: State<'_, KeyManager>,
+    id: String,
+) -> Result<KeyConfigView, String> {
+    let mut config = load_key_config(&app);
+    config.profiles.retain(|p| p.id != id);
+    config.conversation_keys.retain(|_, key_id| *key_id != id);
+    if config.default_key.as_deref() == Some(id.as_str()) {
+        config.default_key = config.profiles.first().map(|p| p.id.clone());
+    }
+    manager.invalidate(&id);
+    save_key_config(&app, &config)?;
+    Ok(view_of(&config))
+}
+
+/// 设置默认 Key
+/// Set the default key.
+#[command]
+pub fn set_default_key(app: AppHandle, id: String) -> Result<KeyConfigView, String> {
+    let mut config = load_key_config(&app);
+    if id != LEGACY_KEY_FIELD && !config.profiles.iter().any(|p| p.id == id) {
+        return Err(format!("Key profile not found: {}", id));
+    }
+    config.default_key = Some(id);
+    save_key_config(&app, &config)?;
+    Ok(view_of(&config))
+}
+
+/// 设置/清除某个会话使用的 Key
+/// Set or clear the key used by a conversation.
+#[command]
+pub fn set_conversation_key(
+    app: AppHandle,
+    conversation_id: String,
+    key_id: Option<String>,
+) -> Result<KeyConfigView, String> {
+    let mut config = load_key_config(&app);
+    match key_id {
+        Some(id) => {
+            if id != LEGACY_KEY_FIELD && !config.profiles.iter().any(|p| p.id == id) {
+                return Err(format!("Key profile not found: {}", id));
+            }
+            config.conversation_keys.insert(conversation_id, id);
+        }
+        None => {
+            config.conversation_keys.remove(&conversation_id);
+        }
+    }
+    save_key_config(&app, &config)?;
+    Ok(view_of(&config))
+}
+
+/// 获取某个 Key 的额度与限额信息（带缓存）
+/// Get credit and limit info for a key (cached).
+///
+/// `key_id` 为空时使用默认 Key；`refresh` 为 true 时跳过缓存。
+/// Uses the default key when `key_id` is empty; `refresh` bypasses the cache.
+#[command]
+pub async fn get_key_info(
+    app: AppHandle,
+    manager: State<'_, KeyManager>,
+    key_id: Option<String>,
+    refresh: Option<bool>,
+) -> Result<KeyInfo, String> {
+    let key = resolve_key(&app, key_id.as_deref(), None, None)?;
+
+    if !refresh.unwrap_or(false) {
+        if let Some(info) = manager.cached(&key.id) {
+            return Ok(info);
+        }
+    }
+
+    let (credits, key_data) = tokio::join!(get_data(&key, "/credits"), get_data(&key, "/key"));
+    let key_data = key_data?;
+    // `/credits` 在部分 Key 上不可用，失败时仅缺少账户信息
+    // `/credits` is unavailable for some keys; on failure only account totals are missing
+    let credits = credits.unwrap_or(Value::Null);
+
+    let info = KeyInfo {
+        key_id: key.id.clone(),
+        total_credits: credits.get("total_credits").and_then(Value::as_f64),
+        total_usage: credits.get("total_usage").and_then(Value::as_f64),
+        label: key_data
+            .get("label")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        usage: key_data.get("usage").and_then(Value::as_f64),
+        limit: key_data.get("limit").and_then(Value::as_f64),
+        limit_remaining: key_data.get("limit_remaining").and_then(Value::as_f64),
+        is_free_tier: key_data.get("is_free_tier").and_then(Value::as_bool),
+        fetched_at: now_millis(),
+    };
+    manager.store(info.clone());
+    Ok(info)
+}
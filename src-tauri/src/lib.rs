@@ -2,6 +2,31 @@
 
 mod helpers;
 mod api;
+mod attachments;
+mod audio;
+mod budget;
+mod caching;
+mod compare;
+mod completion;
+mod documents;
+mod embeddings;
+mod images;
+mod keys;
+mod logging;
+mod mcp;
+mod models;
+mod plugins;
+mod rag;
+mod request;
+mod routing;
+mod settings;
+mod stream;
+mod structured;
+#[cfg(test)]
+mod test_support;
+mod tools;
+mod usage;
+mod web;
 mod windows;
 
 use tauri::{Emitter, LogicalPosition, Manager};
@@ -15,10 +40,97 @@ pub fn run() {
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_plugin_shell::init())
+        .manage(keys::KeyManager::default())
+        .manage(models::ModelCatalog::default())
+        .manage(stream::StreamRegistry::default())
+        .manage(mcp::McpManager::default())
+        .manage(tools::approval::ApprovalManager::default())
+        .manage(web::RobotsCache::default())
+        .manage({
+            let registry = tools::ToolRegistry::default();
+            tools::register_builtin_tools(&registry);
+            registry
+        })
         .invoke_handler(tauri::generate_handler![
             api::get_open_router_models,
             api::fetch_chat_title,
             api::proxy_stream,
+            stream::cancel_stream,
+            compare::compare_stream,
+            completion::proxy_completion_stream,
+            keys::list_key_profiles,
+            keys::save_key_profile,
+            keys::delete_key_profile,
+            keys::set_default_key,
+            keys::set_conversation_key,
+            keys::get_key_info,
+            budget::list_budgets,
+            budget::save_budget_rules,
+            budget::get_budget_ledger,
+            usage::get_usage_stats,
+            usage::get_conversation_usage,
+            usage::list_request_logs,
+            usage::export_usage_csv,
+            logging::set_request_capture,
+            logging::get_request_traces,
+            logging::clear_request_traces,
+            tools::list_tools,
+            tools::approval::approve_tool_call,
+            tools::approval::deny_tool_call,
+            tools::approval::list_pending_approvals,
+            tools::approval::list_allowed_tools,
+            tools::approval::revoke_tool_approval,
+            tools::fs::list_approved_folders,
+            tools::fs::add_approved_folder,
+            tools::fs::remove_approved_folder,
+            tools::shell::get_shell_tool_config,
+            tools::shell::save_shell_tool_config,
+            plugins::get_file_parser_config,
+            plugins::save_file_parser_config,
+            plugins::clear_file_annotations,
+            plugins::get_web_search_config,
+            plugins::save_web_search_config,
+            web::fetch_url,
+            web::fetch_url_context,
+            documents::extract_document,
+            images::prepare_image,
+            images::get_image_pipeline_config,
+            images::save_image_pipeline_config,
+            audio::prepare_audio,
+            audio::get_audio_input_config,
+            audio::save_audio_input_config,
+            attachments::store_attachment,
+            attachments::retain_attachment,
+            attachments::release_attachments,
+            attachments::get_attachment,
+            attachments::list_attachments,
+            attachments::gc_attachments,
+            embeddings::embed,
+            embeddings::search_vectors,
+            embeddings::clear_embedding_cache,
+            rag::index_folder,
+            rag::list_folder_indexes,
+            rag::delete_folder_index,
+            rag::search_folder_indexes,
+            rag::get_rag_config,
+            rag::save_rag_config,
+            request::validate_chat_request,
+            caching::get_prompt_cache_config,
+            caching::save_prompt_cache_config,
+            routing::get_provider_routing,
+            routing::save_provider_routing,
+            routing::set_conversation_provider_routing,
+            structured::validate_structured_output,
+            web::get_web_fetch_config,
+            web::save_web_fetch_config,
+            mcp::list_mcp_servers,
+            mcp::save_mcp_servers,
+            mcp::reconnect_mcp_server,
+            mcp::list_mcp_resources,
+            mcp::read_mcp_resource,
+            mcp::list_mcp_prompts,
+            mcp::get_mcp_prompt,
             windows::exit,
             windows::show_chat_window,
             windows::hide_chat_window,
@@ -27,6 +139,21 @@ pub fn run() {
             windows::reset_main_window
         ])
         .setup(|app| {
+            let log_dir = app.path().app_log_dir()?;
+            app.manage(logging::init_logging(log_dir));
+            let capture = settings::read_setting(app.handle(), logging::CAPTURE_TRACES_FIELD)
+                .unwrap_or(false);
+            app.manage(logging::TraceStore::new(capture));
+
+            let data_dir = app.path().app_data_dir()?;
+            app.manage(budget::BudgetManager::load(data_dir.clone()));
+            app.manage(usage::UsageLog::new(data_dir.clone()));
+            app.manage(plugins::FileAnnotationStore::load(data_dir.clone()));
+            app.manage(attachments::AttachmentStore::load(data_dir.clone()));
+            app.manage(embeddings::EmbeddingCache::load(data_dir.clone()));
+            app.manage(rag::RagStore::new(data_dir));
+            tauri::async_runtime::spawn(mcp::connect_all(app.handle().clone()));
+
             // ========== main 窗口初始化：左下角定位（DIP） / place main at bottom-left ==========
             let main_window = app.get_webview_window("main").unwrap();
 
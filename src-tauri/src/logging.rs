@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::{command, AppHandle, State};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::helpers::now_millis;
+use crate::settings::write_setting;
+
+/// 是否捕获请求详情的 store 字段
+/// Store field toggling per-request capture.
+pub const CAPTURE_TRACES_FIELD: &str = "capture_request_traces";
+
+/// 滚动日志文件名前缀
+/// Rolling log file name prefix.
+const LOG_FILE_PREFIX: &str = "sengine.log";
+/// 内存中保留的请求详情数量
+/// Number of request traces kept in memory.
+const MAX_TRACES: usize = 50;
+/// 单个请求保留的原始流上限（字节）
+/// Max raw stream bytes kept per trace.
+const MAX_RAW_STREAM_BYTES: usize = 256 * 1024;
+/// 超过该长度的附件字段会被脱敏
+/// Attachment fields longer than this are redacted.
+const REDACT_MIN_LEN: usize = 64;
+
+/// 日志写入守卫：需在应用生命周期内保持存活
+/// Log writer guard; must stay alive for the app lifetime.
+pub struct LogGuard {
+    _guard: WorkerGuard,
+}
+
+/// 初始化 tracing：按天滚动写入应用日志目录，调试构建同时输出到控制台
+/// Init tracing: daily rolling file in the app log dir, plus stdout in debug builds.
+///
+/// 日志级别可通过 `RUST_LOG` 覆盖。
+/// The level can be overridden with `RUST_LOG`.
+pub fn init_logging(log_dir: PathBuf) -> LogGuard {
+    let appender = tracing_appender::rolling::daily(log_dir, LOG_FILE_PREFIX);
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info,sengine_lib=debug"));
+    let file_layer = fmt::layer().with_writer(writer).with_ansi(false);
+    let stdout_layer = cfg!(debug_assertions).then(fmt::layer);
+
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(file_layer)
+        .with(stdout_layer)
+        .try_init();
+
+    LogGuard { _guard: guard }
+}
+
+/// 单次请求的详细记录（已脱敏）
+/// Detailed capture of one request (redacted).
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestTrace {
+    pub request_id: String,
+    pub model: String,
+    pub url: String,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    /// 发送的请求体 / request body as sent
+    pub request_body: Value,
+    /// 收到的原始 SSE 文本 / raw SSE text received
+    pub raw_stream: String,
+    pub raw_truncated: bool,
+    pub error: Option<String>,
+    #[serde(skip)]
+    token: String,
+}
+
+impl RequestTrace {
+    /// 追加原始流数据
+    /// Append raw stream data.
+    pub fn push_raw(&mut self, text: &str) {
+        if self.raw_truncated {
+            return;
+        }
+        let room = MAX_RAW_STREAM_BYTES.saturating_sub(self.raw_stream.len());
+        if text.len() <= room {
+            self.raw_stream.push_str(text);
+        } else {
+            let mut cut = room;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            self.raw_stream.push_str(&text[..cut]);
+            self.raw_truncated = true;
+        }
+    }
+}
+
+/// 请求详情存储：开关 + 最近 N 条
+/// Trace store: on/off switch plus the last N traces.
+pub struct TraceStore {
+    enabled: AtomicBool,
+    traces: Mutex<VecDeque<RequestTrace>>,
+}
+
+impl TraceStore {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            traces: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// 开始记录；未开启时返回 `None`
+    /// Begin a capture; returns `None` when disabled.
+    pub fn begin(
+        &self,
+        request_id: &str,
+        model: &str,
+        url: &str,
+        body: &Value,
+        token: &str,
+    ) -> Option<RequestTrace> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(RequestTrace {
+            request_id: request_id.to_string(),
+            model: model.to_string(),
+            url: url.to_string(),
+            started_at: now_millis(),
+            finished_at: None,
+            request_body: redact_value(body, token),
+            raw_stream: String::new(),
+            raw_truncated: false,
+            error: None,
+            token: token.to_string(),
+        })
+    }
+
+    /// 结束记录：脱敏原始流并保存
+    /// Finish a capture: redact the raw stream and keep it.
+    pub fn finish(&self, trace: Option<RequestTrace>, error: Option<&String>) {
+        let Some(mut trace) = trace else {
+            return;
+        };
+        trace.finished_at = Some(now_millis());
+        trace.error = error.cloned();
+        trace.raw_stream = redact_sse(&trace.raw_stream, &trace.token);
+
+        let mut traces = self.traces.lock().unwrap();
+        traces.push_back(trace);
+        while traces.len() > MAX_TRACES {
+            traces.pop_front();
+        }
+    }
+}
+
+fn redact_string(s: &str, token: &str) -> String {
+    if !token.is_empty() && s.contains(token) {
+        s.replace(token, "[REDACTED_KEY]")
+    } else {
+        s.to_string()
+    }
+}
+
+/// 脱敏 JSON：API Key、data URL 与附件 base64
+/// Redact JSON: the API key, data URLs and attachment base64.
+pub fn redact_value(value: &Value, token: &str) -> Value {
+    match value {
+        Value::String(s) if s.starts_with("data:") && s.len() > REDACT_MIN_LEN => {
+            Value::String(format!("[REDACTED data URL, {} bytes]", s.len()))
+        }
+        Value::String(s) => Value::String(redact_string(s, token)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| redact_value(v, token)).collect()),
+        Value::Object(map) => {
+            let mut out = Map::new();
+            for (k, v) in map {
+                let is_attachment = matches!(k.as_str(), "file_data" | "data")
+                    && v.as_str().is_some_and(|s| s.len() > REDACT_MIN_LEN);
+                let redacted = if is_attachment {
+                    Value::String(format!(
+                        "[REDACTED {} bytes]",
+                        v.as_str().unwrap_or("").len()
+                    ))
+                } else {
+                    redact_value(v, token)
+                };
+                out.insert(k.clone(), redacted);
+            }
+            Value::Object(out)
+        }
+        other => other.clone(),
+    }
+}
+
+/// 逐行脱敏 SSE 文本中的 JSON 数据
+/// Redact the JSON payloads of SSE text line by line.
+///
+/// 无法解析的 `data:` 行（例如在上限处被截断）整行替换为占位符，不原样保留。
+/// `data:` lines that don't parse (cut at the size cap, for one) are replaced with a
+/// placeholder rather than kept verbatim.
+fn redact_sse(raw: &str, token: &str) -> String {
+    raw.split_inclusive('\n')
+        .map(|line| {
+            let trimmed = line.trim_end_matches(['\r', '\n']);
+            let ending = &line[trimmed.len()..];
+            let Some(data) = trimmed.strip_prefix("data:").map(str::trim_start) else {
+                return redact_string(line, token);
+            };
+            if data == "[DONE]" {
+                return format!("data: [DONE]{}", ending);
+            }
+            match serde_json::from_str::<Value>(data) {
+                Ok(json) => format!("data: {}{}", redact_value(&json, token), ending),
+                Err(_) => format!(
+                    "data: [REDACTED unparsed data, {} bytes]{}",
+                    data.len(),
+                    ending
+                ),
+            }
+        })
+        .collect()
+}
+
+/// 开关请求详情捕获
+/// Toggle request capture.
+#[command]
+pub fn set_request_capture(
+    app: AppHandle,
+    store: State<'_, TraceStore>,
+    enabled: bool,
+) -> Result<(), String> {
+    store.enabled.store(enabled, Ordering::Relaxed);
+    write_setting(&app, CAPTURE_TRACES_FIELD, &enabled)
+}
+
+/// 获取最近 N 条请求详情（新的在前）
+/// Get the last N request traces (newest first).
+#[command]
+pub fn get_request_traces(store: State<'_, TraceStore>, limit: Option<usize>) -> Vec<RequestTrace> {
+    let traces = store.traces.lock().unwrap();
+    traces
+        .iter()
+        .rev()
+        .take(limit.unwrap_or(MAX_TRACES))
+        .cloned()
+        .collect()
+}
+
+/// 清空请求详情
+/// Clear request traces.
+#[command]
+pub fn clear_request_traces(store: State<'_, TraceStore>) {
+    store.traces.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY: &str = "sk-or-v1-secret";
+
+    #[test]
+    fn values_lose_keys_and_attachments() {
+        let image = format!("data:image/png;base64,{}", "A".repeat(200));
+        let audio = "B".repeat(200);
+        let body = json!({
+            "model": "m",
+            "headers": { "Authorization": format!("Bearer {}", KEY) },
+            "messages": [{ "role": "user", "content": [
+                { "type": "text", "text": "hello" },
+                { "type": "image_url", "image_url": { "url": image } },
+                { "type": "input_audio", "input_audio": { "data": audio, "format": "wav" } },
+                { "type": "file", "file": { "filename": "a.pdf", "file_data": "short" } },
+            ] }],
+        });
+        let redacted = redact_value(&body, KEY);
+        let text = redacted.to_string();
+        assert!(!text.contains(KEY) && !text.contains("AAAA") && !text.contains("BBBB"));
+        assert_eq!(
+            redacted["headers"]["Authorization"],
+            "Bearer [REDACTED_KEY]"
+        );
+        let parts = &redacted["messages"][0]["content"];
+        assert_eq!(parts[0]["text"], "hello");
+        assert_eq!(
+            parts[1]["image_url"]["url"],
+            "[REDACTED data URL, 222 bytes]"
+        );
+        assert_eq!(parts[2]["input_audio"]["data"], "[REDACTED 200 bytes]");
+        assert_eq!(parts[3]["file"]["file_data"], "short");
+    }
+
+    #[test]
+    fn sse_json_lines_are_redacted() {
+        let image = format!("data:image/png;base64,{}", "A".repeat(200));
+        let chunk =
+            json!({ "choices": [{ "delta": { "images": [{ "image_url": { "url": image } }] } }] });
+        let raw = format!(
+            ": OPENROUTER PROCESSING\n\ndata: {}\r\n\r\ndata:{}\n\ndata: [DONE]\n\n",
+            chunk, chunk
+        );
+        let redacted = redact_sse(&raw, KEY);
+        assert!(!redacted.contains("AAAA"));
+        assert!(redacted.starts_with(": OPENROUTER PROCESSING\n\ndata: {"));
+        assert_eq!(redacted.matches("[REDACTED data URL").count(), 2);
+        assert!(redacted.contains("\r\n\r\ndata: {"));
+        assert!(redacted.ends_with("data: [DONE]\n\n"));
+    }
+
+    #[test]
+    fn sse_unparsed_data_is_dropped() {
+        let raw = format!(
+            "data: {{\"choices\":[{{\"delta\":{{\"content\":\"my secret prompt {}",
+            "A".repeat(100)
+        );
+        let redacted = redact_sse(&raw, KEY);
+        assert_eq!(
+            redacted,
+            format!("data: [REDACTED unparsed data, {} bytes]", raw.len() - 6)
+        );
+        let other = redact_sse(&format!("event: x\n: note {}\n", KEY), KEY);
+        assert_eq!(other, "event: x\n: note [REDACTED_KEY]\n");
+    }
+
+    #[test]
+    fn raw_stream_is_capped_on_a_char_boundary() {
+        let store = TraceStore::new(true);
+        let mut trace = store.begin("r", "m", "u", &json!({}), KEY).unwrap();
+        trace.push_raw(&"a".repeat(MAX_RAW_STREAM_BYTES - 1));
+        trace.push_raw("é");
+        assert!(trace.raw_truncated);
+        assert_eq!(trace.raw_stream.len(), MAX_RAW_STREAM_BYTES - 1);
+        trace.push_raw("more");
+        assert_eq!(trace.raw_stream.len(), MAX_RAW_STREAM_BYTES - 1);
+    }
+}
@@ -0,0 +1,1133 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+use tracing::{debug, error, info, warn};
+
+use crate::api::http_client;
+use crate::settings::{read_setting, write_setting};
+use crate::stream::{SseEvent, SseParser};
+use crate::tools::{ToolContext, ToolHandler, ToolRegistry, ToolScope};
+
+/// MCP 服务器配置在 store 中的字段名
+/// Store field holding the MCP server configs.
+pub const MCP_SERVERS_FIELD: &str = "mcp_servers";
+
+/// 客户端声明的协议版本
+/// Protocol version announced by the client.
+const PROTOCOL_VERSION: &str = "2025-03-26";
+/// 建立连接（启动 + 初始化 + 列表）的超时
+/// Timeout for connecting (spawn + initialize + listing).
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// 单个请求的超时
+/// Timeout of a single request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// 列表分页的最大页数
+/// Max pages followed when listing.
+const MAX_LIST_PAGES: usize = 20;
+/// 暴露给模型的工具名前缀
+/// Prefix of the tool names exposed to the model.
+const TOOL_PREFIX: &str = "mcp__";
+
+/// MCP 传输方式
+/// MCP transport.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "snake_case")]
+pub enum McpTransport {
+    /// 本地子进程，按行收发 JSON-RPC / local child process, line-delimited JSON-RPC
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+        #[serde(default)]
+        cwd: Option<String>,
+    },
+    /// Streamable HTTP 端点 / streamable HTTP endpoint
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+/// 一个 MCP 服务器配置
+/// One MCP server config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// 唯一 ID，同时用于工具名 / unique id, also used in tool names
+    pub id: String,
+    /// 显示名称 / display name
+    pub name: String,
+    #[serde(flatten)]
+    pub transport: McpTransport,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 服务器连接状态
+/// Server connection state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpState {
+    Disabled,
+    Connecting,
+    Connected,
+    Error,
+}
+
+/// 服务器提供的工具
+/// A tool offered by a server.
+#[derive(Debug, Clone, Serialize)]
+pub struct McpToolInfo {
+    /// 服务器内的名称 / name on the server
+    pub name: String,
+    /// 暴露给模型的名称 / name exposed to the model
+    pub exposed_name: String,
+    pub description: String,
+    #[serde(skip)]
+    input_schema: Value,
+}
+
+/// 返回给前端的服务器状态
+/// Server status returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct McpServerStatus {
+    pub id: String,
+    pub name: String,
+    /// `stdio` / `http`
+    pub transport: &'static str,
+    pub enabled: bool,
+    pub state: McpState,
+    pub error: Option<String>,
+    /// `initialize` 返回的 serverInfo / serverInfo from `initialize`
+    pub server_info: Option<Value>,
+    pub tools: Vec<McpToolInfo>,
+    pub resource_count: usize,
+    pub prompt_count: usize,
+}
+
+impl McpServerStatus {
+    fn new(config: &McpServerConfig, state: McpState) -> Self {
+        Self {
+            id: config.id.clone(),
+            name: config.name.clone(),
+            transport: match config.transport {
+                McpTransport::Stdio { .. } => "stdio",
+                McpTransport::Http { .. } => "http",
+            },
+            enabled: config.enabled,
+            state,
+            error: None,
+            server_info: None,
+            tools: Vec::new(),
+            resource_count: 0,
+            prompt_count: 0,
+        }
+    }
+}
+
+/// 等待响应的请求
+/// Requests waiting for a response.
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// stdio 传输
+/// Stdio transport.
+struct StdioTransport {
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    closed: Arc<AtomicBool>,
+    child: tokio::sync::Mutex<Child>,
+}
+
+/// Streamable HTTP 传输
+/// Streamable HTTP transport.
+struct HttpTransport {
+    url: String,
+    headers: HashMap<String, String>,
+    /// `Mcp-Session-Id`
+    session: Mutex<Option<String>>,
+}
+
+enum Transport {
+    Stdio(StdioTransport),
+    Http(HttpTransport),
+}
+
+/// JSON-RPC 客户端
+/// JSON-RPC client.
+pub struct McpClient {
+    server_id: String,
+    next_id: AtomicU64,
+    transport: Transport,
+}
+
+/// 取 JSON-RPC 响应中的 `result`，`error` 转为错误字符串
+/// Take `result` from a JSON-RPC response; `error` becomes an error string.
+fn rpc_result(message: Value) -> Result<Value, String> {
+    if let Some(err) = message.get("error") {
+        let text = err
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("Unknown error");
+        return Err(match err.get("code") {
+            Some(code) => format!("{} ({})", text, code),
+            None => text.to_string(),
+        });
+    }
+    Ok(message.get("result").cloned().unwrap_or(Value::Null))
+}
+
+async fn write_line(stdin: &tokio::sync::Mutex<ChildStdin>, message: &Value) -> Result<(), String> {
+    let mut line = message.to_string();
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    stdin.flush().await.map_err(|e| e.to_string())
+}
+
+impl McpClient {
+    /// 启动 stdio 服务器子进程
+    /// Spawn a stdio server process.
+    fn spawn_stdio(
+        server_id: &str,
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        cwd: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut cmd = Command::new(command);
+        cmd.args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        // 不弹出控制台窗口 / don't pop up a console window
+        #[cfg(windows)]
+        cmd.creation_flags(0x0800_0000);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", command, e))?;
+        let stdin = Arc::new(tokio::sync::Mutex::new(
+            child.stdin.take().ok_or("No stdin")?,
+        ));
+        let stdout = child.stdout.take().ok_or("No stdout")?;
+        let stderr = child.stderr.take().ok_or("No stderr")?;
+        let pending: Pending = Arc::default();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        // 读取响应；服务端的 ping 直接回应，其余请求回 "method not found"
+        // Read responses; answer server pings, reject other server requests.
+        let reader_pending = pending.clone();
+        let reader_closed = closed.clone();
+        let reader_stdin = stdin.clone();
+        let id = server_id.to_string();
+        tauri::async_runtime::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    debug!(server = %id, "non-JSON stdout line: {}", line);
+                    continue;
+                };
+                let request_id = message.get("id").cloned();
+                match (message.get("method").and_then(|m| m.as_str()), request_id) {
+                    (Some(method), Some(request_id)) => {
+                        let reply = if method == "ping" {
+                            json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                        } else {
+                            json!({
+                                "jsonrpc": "2.0",
+                                "id": request_id,
+                                "error": { "code": -32601, "message": "Method not found" },
+                            })
+                        };
+                        let _ = write_line(&reader_stdin, &reply).await;
+                    }
+                    (Some(method), None) => debug!(server = %id, "notification {}", method),
+                    (None, Some(request_id)) => {
+                        let sender = request_id
+                            .as_u64()
+                            .and_then(|n| reader_pending.lock().unwrap().remove(&n));
+                        if let Some(sender) = sender {
+                            let _ = sender.send(message);
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+            reader_closed.store(true, Ordering::SeqCst);
+            // 丢弃等待者，使其立即失败 / drop waiters so they fail right away
+            reader_pending.lock().unwrap().clear();
+            warn!(server = %id, "MCP server stdout closed");
+        });
+
+        let id = server_id.to_string();
+        tauri::async_runtime::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!(server = %id, "stderr: {}", line);
+            }
+        });
+
+        Ok(Self {
+            server_id: server_id.to_string(),
+            next_id: AtomicU64::new(1),
+            transport: Transport::Stdio(StdioTransport {
+                stdin,
+                pending,
+                closed,
+                child: tokio::sync::Mutex::new(child),
+            }),
+        })
+    }
+
+    fn http(server_id: &str, url: &str, headers: &HashMap<String, String>) -> Self {
+        Self {
+            server_id: server_id.to_string(),
+            next_id: AtomicU64::new(1),
+            transport: Transport::Http(HttpTransport {
+                url: url.to_string(),
+                headers: headers.clone(),
+                session: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// 连接是否已断开（仅 stdio 可感知）
+    /// Whether the connection is gone (only detectable for stdio).
+    pub fn is_closed(&self) -> bool {
+        match &self.transport {
+            Transport::Stdio(t) => t.closed.load(Ordering::SeqCst),
+            Transport::Http(_) => false,
+        }
+    }
+
+    /// 发送请求并等待结果
+    /// Send a request and wait for its result.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        debug!(server = %self.server_id, method = %method, id, "MCP request");
+        let response = match &self.transport {
+            Transport::Stdio(t) => {
+                if t.closed.load(Ordering::SeqCst) {
+                    return Err("MCP server has exited".into());
+                }
+                let (tx, rx) = oneshot::channel();
+                t.pending.lock().unwrap().insert(id, tx);
+                if let Err(e) = write_line(&t.stdin, &message).await {
+                    t.pending.lock().unwrap().remove(&id);
+                    return Err(e);
+                }
+                match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+                    Ok(Ok(response)) => response,
+                    Ok(Err(_)) => return Err("MCP server has exited".into()),
+                    Err(_) => {
+                        t.pending.lock().unwrap().remove(&id);
+                        return Err(format!("{} timed out", method));
+                    }
+                }
+            }
+            Transport::Http(t) => tokio::time::timeout(REQUEST_TIMEOUT, t.post(&message, Some(id)))
+                .await
+                .map_err(|_| format!("{} timed out", method))??
+                .ok_or("Empty response")?,
+        };
+        rpc_result(response)
+    }
+
+    /// 发送通知（无响应）
+    /// Send a notification (no response).
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        match &self.transport {
+            Transport::Stdio(t) => write_line(&t.stdin, &message).await,
+            Transport::Http(t) => t.post(&message, None).await.map(|_| ()),
+        }
+    }
+
+    /// 逐页拉取列表（`tools/list` 等）
+    /// Fetch a paginated list (`tools/list` and friends).
+    async fn list_all(&self, method: &str, field: &str) -> Result<Vec<Value>, String> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let params = match &cursor {
+                Some(c) => json!({ "cursor": c }),
+                None => json!({}),
+            };
+            let result = self.request(method, params).await?;
+            if let Some(list) = result.get(field).and_then(|l| l.as_array()) {
+                items.extend(list.iter().cloned());
+            }
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(String::from);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// 关闭连接：结束子进程或删除 HTTP 会话
+    /// Close: kill the child process or delete the HTTP session.
+    async fn shutdown(&self) {
+        match &self.transport {
+            Transport::Stdio(t) => {
+                let _ = t.child.lock().await.kill().await;
+            }
+            Transport::Http(t) => {
+                let session = t.session.lock().unwrap().clone();
+                if let Some(session) = session {
+                    let _ = http_client()
+                        .delete(&t.url)
+                        .header("Mcp-Session-Id", session)
+                        .send()
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+impl HttpTransport {
+    /// POST 一条消息；请求时等待对应 ID 的响应（JSON 或 SSE）
+    /// POST one message; for requests, wait for the matching response (JSON or SSE).
+    async fn post(&self, message: &Value, id: Option<u64>) -> Result<Option<Value>, String> {
+        let mut req = http_client()
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        for (name, value) in &self.headers {
+            req = req.header(name, value);
+        }
+        let session = self.session.lock().unwrap().clone();
+        if let Some(session) = session {
+            req = req.header("Mcp-Session-Id", session);
+        }
+
+        let res = req.send().await.map_err(|e| e.to_string())?;
+        if let Some(session) = res
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session.lock().unwrap() = Some(session.to_string());
+        }
+        let status = res.status();
+        if !status.is_success() {
+            let text = res.text().await.unwrap_or_default();
+            return Err(format!("HTTP {}: {}", status, text));
+        }
+        let Some(id) = id else {
+            return Ok(None);
+        };
+
+        let is_sse = res
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/event-stream"));
+        if !is_sse {
+            return res
+                .json::<Value>()
+                .await
+                .map(Some)
+                .map_err(|e| e.to_string());
+        }
+
+        let matches = |data: &str| {
+            serde_json::from_str::<Value>(data).ok().filter(|m| {
+                m.get("id").and_then(|i| i.as_u64()) == Some(id) && m.get("method").is_none()
+            })
+        };
+        let mut parser = SseParser::default();
+        let mut stream = res.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            for event in parser.push(&chunk) {
+                if let SseEvent::Data(data) = event {
+                    if let Some(message) = matches(&data) {
+                        return Ok(Some(message));
+                    }
+                }
+            }
+        }
+        for event in parser.finish() {
+            if let SseEvent::Data(data) = event {
+                if let Some(message) = matches(&data) {
+                    return Ok(Some(message));
+                }
+            }
+        }
+        Err("Stream ended without a response".into())
+    }
+}
+
+/// 生成暴露给模型的工具名（仅 `[A-Za-z0-9_-]`，最长 64）
+/// Build the tool name exposed to the model (`[A-Za-z0-9_-]` only, max 64).
+fn exposed_tool_name(server_id: &str, tool: &str) -> String {
+    format!("{}{}__{}", TOOL_PREFIX, server_id, tool)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect()
+}
+
+/// 一个已连接服务器的会话数据
+/// Session data of a connected server.
+struct McpSession {
+    client: Arc<McpClient>,
+    server_info: Option<Value>,
+    tools: Vec<McpToolInfo>,
+    resources: Vec<Value>,
+    prompts: Vec<Value>,
+}
+
+/// 启动/连接并完成握手与列表
+/// Spawn/connect, handshake and list.
+async fn establish(config: &McpServerConfig) -> Result<McpSession, String> {
+    let client = Arc::new(match &config.transport {
+        McpTransport::Stdio {
+            command,
+            args,
+            env,
+            cwd,
+        } => McpClient::spawn_stdio(&config.id, command, args, env, cwd.as_deref())?,
+        McpTransport::Http { url, headers } => McpClient::http(&config.id, url, headers),
+    });
+
+    let init = client
+        .request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "sengine", "version": env!("CARGO_PKG_VERSION") },
+            }),
+        )
+        .await;
+    let init = match init {
+        Ok(init) => init,
+        Err(e) => {
+            client.shutdown().await;
+            return Err(e);
+        }
+    };
+    client
+        .notify("notifications/initialized", json!({}))
+        .await?;
+
+    let capabilities = init.get("capabilities").cloned().unwrap_or_default();
+    let tools = if capabilities.get("tools").is_some() {
+        client.list_all("tools/list", "tools").await?
+    } else {
+        Vec::new()
+    };
+    // 资源与提示词为可选能力，失败不影响连接
+    // Resources and prompts are optional; failures don't fail the connection.
+    let resources = if capabilities.get("resources").is_some() {
+        client
+            .list_all("resources/list", "resources")
+            .await
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let prompts = if capabilities.get("prompts").is_some() {
+        client
+            .list_all("prompts/list", "prompts")
+            .await
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let tools = tools
+        .into_iter()
+        .filter_map(|t| {
+            let name = t.get("name")?.as_str()?.to_string();
+            Some(McpToolInfo {
+                exposed_name: exposed_tool_name(&config.id, &name),
+                description: t
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                input_schema: t
+                    .get("inputSchema")
+                    .cloned()
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+                name,
+            })
+        })
+        .collect();
+
+    Ok(McpSession {
+        client,
+        server_info: init.get("serverInfo").cloned(),
+        tools,
+        resources,
+        prompts,
+    })
+}
+
+/// 一个服务器的运行时条目
+/// Runtime entry of one server.
+struct McpEntry {
+    status: McpServerStatus,
+    session: Option<McpSession>,
+}
+
+/// MCP 服务器管理器
+/// MCP server manager.
+#[derive(Default)]
+pub struct McpManager {
+    servers: Mutex<HashMap<String, McpEntry>>,
+}
+
+impl McpManager {
+    fn client(&self, server_id: &str) -> Result<Arc<McpClient>, String> {
+        let servers = self.servers.lock().unwrap();
+        let session = servers
+            .get(server_id)
+            .and_then(|e| e.session.as_ref())
+            .ok_or_else(|| format!("MCP server not connected: {}", server_id))?;
+        Ok(session.client.clone())
+    }
+
+    fn set_status(&self, app: &AppHandle, status: McpServerStatus, session: Option<McpSession>) {
+        let _ = app.emit("mcp-status", &status);
+        self.servers
+            .lock()
+            .unwrap()
+            .insert(status.id.clone(), McpEntry { status, session });
+    }
+
+    /// 当前所有服务器状态（已退出的 stdio 服务器标记为错误）
+    /// Current status of all servers (exited stdio servers reported as errors).
+    pub fn statuses(&self, configs: &[McpServerConfig]) -> Vec<McpServerStatus> {
+        let servers = self.servers.lock().unwrap();
+        configs
+            .iter()
+            .map(|config| match servers.get(&config.id) {
+                Some(entry) => {
+                    let mut status = entry.status.clone();
+                    if entry.session.as_ref().is_some_and(|s| s.client.is_closed()) {
+                        status.state = McpState::Error;
+                        status.error = Some("MCP server has exited".into());
+                    }
+                    status
+                }
+                None => McpServerStatus::new(
+                    config,
+                    if config.enabled {
+                        McpState::Connecting
+                    } else {
+                        McpState::Disabled
+                    },
+                ),
+            })
+            .collect()
+    }
+
+    /// 调用服务器上的工具
+    /// Call a tool on a server.
+    pub async fn call_tool(
+        &self,
+        server_id: &str,
+        tool: &str,
+        args: Value,
+    ) -> Result<Value, String> {
+        let client = self.client(server_id)?;
+        let result = client
+            .request("tools/call", json!({ "name": tool, "arguments": args }))
+            .await?;
+        tool_output(result)
+    }
+
+    /// 请求任意方法（资源 / 提示词）
+    /// Request an arbitrary method (resources / prompts).
+    pub async fn request(
+        &self,
+        server_id: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, String> {
+        self.client(server_id)?.request(method, params).await
+    }
+}
+
+/// 把 `tools/call` 结果转为工具输出；纯文本内容直接拼接
+/// Turn a `tools/call` result into tool output; text-only content is joined.
+fn tool_output(result: Value) -> Result<Value, String> {
+    let content = result
+        .get("content")
+        .and_then(|c| c.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let texts: Vec<&str> = content
+        .iter()
+        .filter_map(|c| c.get("text").and_then(|t| t.as_str()))
+        .collect();
+    if result
+        .get("isError")
+        .and_then(|e| e.as_bool())
+        .unwrap_or(false)
+    {
+        return Err(if texts.is_empty() {
+            "MCP tool reported an error".to_string()
+        } else {
+            texts.join("\n")
+        });
+    }
+    if let Some(structured) = result.get("structuredContent") {
+        return Ok(structured.clone());
+    }
+    if texts.len() == content.len() {
+        return Ok(Value::String(texts.join("\n")));
+    }
+    Ok(Value::Array(content))
+}
+
+/// 把 MCP 工具接入工具调用循环
+/// Bridges an MCP tool into the tool-calling loop.
+struct McpToolHandler {
+    server_id: String,
+    /// 服务器内的工具名 / tool name on the server
+    tool: String,
+    exposed_name: String,
+    description: String,
+    input_schema: Value,
+}
+
+#[async_trait]
+impl ToolHandler for McpToolHandler {
+    fn name(&self) -> &str {
+        &self.exposed_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::External
+    }
+
+    fn parameters(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        ctx.app
+            .state::<McpManager>()
+            .call_tool(&self.server_id, &self.tool, args)
+            .await
+    }
+}
+
+fn load_configs(app: &AppHandle) -> Vec<McpServerConfig> {
+    read_setting(app, MCP_SERVERS_FIELD).unwrap_or_default()
+}
+
+/// 断开服务器并移除其工具
+/// Disconnect a server and unregister its tools.
+async fn disconnect(app: &AppHandle, server_id: &str) {
+    app.state::<ToolRegistry>()
+        .unregister_prefix(&exposed_tool_name(server_id, ""));
+    let entry = app
+        .state::<McpManager>()
+        .servers
+        .lock()
+        .unwrap()
+        .remove(server_id);
+    if let Some(session) = entry.and_then(|e| e.session) {
+        session.client.shutdown().await;
+        info!(server = %server_id, "MCP server disconnected");
+    }
+}
+
+/// 连接一个服务器并注册其工具
+/// Connect one server and register its tools.
+async fn connect(app: &AppHandle, config: &McpServerConfig) -> McpServerStatus {
+    disconnect(app, &config.id).await;
+    let manager = app.state::<McpManager>();
+    if !config.enabled {
+        let status = McpServerStatus::new(config, McpState::Disabled);
+        manager.set_status(app, status.clone(), None);
+        return status;
+    }
+    manager.set_status(
+        app,
+        McpServerStatus::new(config, McpState::Connecting),
+        None,
+    );
+
+    let result = tokio::time::timeout(CONNECT_TIMEOUT, establish(config))
+        .await
+        .unwrap_or_else(|_| Err("Connection timed out".into()));
+    let mut status = McpServerStatus::new(config, McpState::Connected);
+    match result {
+        Ok(session) => {
+            let registry = app.state::<ToolRegistry>();
+            for info in &session.tools {
+                registry.register(Arc::new(McpToolHandler {
+                    server_id: config.id.clone(),
+                    tool: info.name.clone(),
+                    exposed_name: info.exposed_name.clone(),
+                    description: info.description.clone(),
+                    input_schema: info.input_schema.clone(),
+                }));
+            }
+            status.server_info = session.server_info.clone();
+            status.tools = session.tools.clone();
+            status.resource_count = session.resources.len();
+            status.prompt_count = session.prompts.len();
+            info!(server = %config.id, tools = status.tools.len(), "MCP server connected");
+            manager.set_status(app, status.clone(), Some(session));
+        }
+        Err(e) => {
+            error!(server = %config.id, "MCP server failed to connect: {}", e);
+            status.state = McpState::Error;
+            status.error = Some(e);
+            manager.set_status(app, status.clone(), None);
+        }
+    }
+    status
+}
+
+/// 启动时连接所有已启用的服务器
+/// Connect all enabled servers at startup.
+pub async fn connect_all(app: AppHandle) {
+    let configs = load_configs(&app);
+    let tasks = configs.iter().map(|config| connect(&app, config));
+    futures_util::future::join_all(tasks).await;
+}
+
+/// 列出服务器及其状态
+/// List the servers with their status.
+#[command]
+pub fn list_mcp_servers(app: AppHandle, manager: State<'_, McpManager>) -> Vec<McpServerStatus> {
+    manager.statuses(&load_configs(&app))
+}
+
+/// 保存服务器配置并重新连接
+/// Save the server configs and reconnect.
+#[command]
+pub async fn save_mcp_servers(
+    app: AppHandle,
+    servers: Vec<McpServerConfig>,
+) -> Result<Vec<McpServerStatus>, String> {
+    let mut seen = std::collections::HashSet::new();
+    for server in &servers {
+        if server.id.trim().is_empty() || !seen.insert(server.id.as_str()) {
+            return Err(format!("Invalid or duplicate server id: '{}'", server.id));
+        }
+    }
+    let previous = load_configs(&app);
+    write_setting(&app, MCP_SERVERS_FIELD, &servers)?;
+    for old in previous
+        .iter()
+        .filter(|o| !servers.iter().any(|s| s.id == o.id))
+    {
+        disconnect(&app, &old.id).await;
+    }
+    let tasks = servers.iter().map(|config| connect(&app, config));
+    Ok(futures_util::future::join_all(tasks).await)
+}
+
+/// 重新连接一个服务器
+/// Reconnect one server.
+#[command]
+pub async fn reconnect_mcp_server(
+    app: AppHandle,
+    server_id: String,
+) -> Result<McpServerStatus, String> {
+    let config = load_configs(&app)
+        .into_iter()
+        .find(|c| c.id == server_id)
+        .ok_or_else(|| format!("Unknown MCP server: {}", server_id))?;
+    Ok(connect(&app, &config).await)
+}
+
+/// 列出服务器的资源
+/// List a server's resources.
+#[command]
+pub fn list_mcp_resources(
+    manager: State<'_, McpManager>,
+    server_id: String,
+) -> Result<Vec<Value>, String> {
+    let servers = manager.servers.lock().unwrap();
+    servers
+        .get(&server_id)
+        .and_then(|e| e.session.as_ref())
+        .map(|s| s.resources.clone())
+        .ok_or_else(|| format!("MCP server not connected: {}", server_id))
+}
+
+/// 读取一个资源
+/// Read one resource.
+#[command]
+pub async fn read_mcp_resource(
+    manager: State<'_, McpManager>,
+    server_id: String,
+    uri: String,
+) -> Result<Value, String> {
+    manager
+        .request(&server_id, "resources/read", json!({ "uri": uri }))
+        .await
+}
+
+/// 列出服务器的提示词
+/// List a server's prompts.
+#[command]
+pub fn list_mcp_prompts(
+    manager: State<'_, McpManager>,
+    server_id: String,
+) -> Result<Vec<Value>, String> {
+    let servers = manager.servers.lock().unwrap();
+    servers
+        .get(&server_id)
+        .and_then(|e| e.session.as_ref())
+        .map(|s| s.prompts.clone())
+        .ok_or_else(|| format!("MCP server not connected: {}", server_id))
+}
+
+/// 获取一个提示词（已填充参数）
+/// Get one prompt with its arguments filled in.
+#[command]
+pub async fn get_mcp_prompt(
+    manager: State<'_, McpManager>,
+    server_id: String,
+    name: String,
+    arguments: Option<HashMap<String, String>>,
+) -> Result<Value, String> {
+    manager
+        .request(
+            &server_id,
+            "prompts/get",
+            json!({ "name": name, "arguments": arguments.unwrap_or_default() }),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, serve, StubRequest, StubServer};
+
+    #[test]
+    fn rpc_errors_become_strings() {
+        let ok = json!({ "jsonrpc": "2.0", "id": 1, "result": { "tools": [] } });
+        assert_eq!(rpc_result(ok).unwrap(), json!({ "tools": [] }));
+        let err = json!({ "id": 1, "error": { "code": -32601, "message": "Method not found" } });
+        assert_eq!(rpc_result(err).unwrap_err(), "Method not found (-32601)");
+        assert_eq!(rpc_result(json!({ "id": 1 })).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn exposed_names_are_sanitized_and_capped() {
+        assert_eq!(exposed_tool_name("fs", "read_file"), "mcp__fs__read_file");
+        assert_eq!(
+            exposed_tool_name("my server", "a.b/c"),
+            "mcp__my_server__a_b_c"
+        );
+        assert_eq!(exposed_tool_name("x", &"t".repeat(100)).len(), 64);
+    }
+
+    #[test]
+    fn tool_output_prefers_text_and_structured_content() {
+        let text = json!({ "content": [{ "type": "text", "text": "a" }, { "type": "text", "text": "b" }] });
+        assert_eq!(tool_output(text).unwrap(), json!("a\nb"));
+        let structured = json!({ "content": [], "structuredContent": { "n": 1 } });
+        assert_eq!(tool_output(structured).unwrap(), json!({ "n": 1 }));
+        let mixed = json!({ "content": [{ "type": "image", "data": "..." }] });
+        assert!(tool_output(mixed).unwrap().is_array());
+        let failed = json!({ "isError": true, "content": [{ "type": "text", "text": "boom" }] });
+        assert_eq!(tool_output(failed).unwrap_err(), "boom");
+    }
+
+    /// stdio 桩服务器：按顺序应答 initialize、tools/list、tools/call，并在调用期间 ping 客户端
+    /// Stdio stub server: answers initialize, tools/list and tools/call in order, pinging the client mid-call.
+    #[cfg(unix)]
+    const STDIO_STUB: &str = r#"
+read init
+printf '%s\n' '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"stub"}}}'
+read initialized
+read list
+printf '%s\n' 'not json' '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"echo","description":"Echo back","inputSchema":{"type":"object"}}]}}'
+read call
+printf '%s\n' '{"jsonrpc":"2.0","id":"srv-1","method":"ping"}'
+read pong
+case "$pong" in *srv-1*result*) reply=pong ;; *) reply=missing ;; esac
+printf '%s\n' "{\"jsonrpc\":\"2.0\",\"id\":3,\"result\":{\"content\":[{\"type\":\"text\",\"text\":\"$reply\"}]}}"
+"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stdio_transport_handshakes_and_calls_tools() {
+        let config = McpServerConfig {
+            id: "stub".into(),
+            name: "Stub".into(),
+            transport: McpTransport::Stdio {
+                command: "sh".into(),
+                args: vec!["-c".into(), STDIO_STUB.into()],
+                env: HashMap::new(),
+                cwd: None,
+            },
+            enabled: true,
+        };
+        let session = establish(&config).await.unwrap();
+        assert_eq!(session.server_info, Some(json!({ "name": "stub" })));
+        assert_eq!(session.tools.len(), 1);
+        assert_eq!(session.tools[0].exposed_name, "mcp__stub__echo");
+        assert_eq!(session.tools[0].description, "Echo back");
+
+        let result = session
+            .client
+            .request("tools/call", json!({ "name": "echo", "arguments": {} }))
+            .await
+            .unwrap();
+        assert_eq!(tool_output(result).unwrap(), json!("pong"));
+
+        // 桩服务器退出后，后续请求立即失败 / once the stub exits, requests fail right away
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !session.client.is_closed() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let err = session.client.request("ping", json!({})).await.unwrap_err();
+        assert_eq!(err, "MCP server has exited");
+    }
+
+    fn json_body(request: &StubRequest) -> Value {
+        serde_json::from_slice(&request.body).unwrap_or_default()
+    }
+
+    /// Streamable HTTP 桩服务器：JSON 应答 initialize，SSE 应答第一页 tools/list
+    /// Streamable HTTP stub: JSON for initialize, SSE for the first tools/list page.
+    fn serve_mcp() -> StubServer {
+        serve(|request| {
+            let body = json_body(request);
+            let id = body.get("id").cloned().unwrap_or_default();
+            match body.get("method").and_then(|m| m.as_str()) {
+                Some("initialize") => response(
+                    "200 OK",
+                    "Content-Type: application/json\r\nMcp-Session-Id: s-1\r\n",
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": { "capabilities": { "tools": {} }, "serverInfo": { "name": "http-stub" } },
+                    })
+                    .to_string(),
+                ),
+                Some("tools/list") if body["params"].get("cursor").is_none() => {
+                    let progress = json!({ "jsonrpc": "2.0", "method": "notifications/progress", "params": {} });
+                    let page = json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": { "tools": [{ "name": "first" }], "nextCursor": "p2" },
+                    });
+                    response(
+                        "200 OK",
+                        "Content-Type: text/event-stream\r\n",
+                        &format!("data: {}\n\ndata: {}\n\n", progress, page),
+                    )
+                }
+                Some("tools/list") => response(
+                    "200 OK",
+                    "Content-Type: application/json\r\n",
+                    &json!({ "jsonrpc": "2.0", "id": id, "result": { "tools": [{ "name": "second" }] } })
+                        .to_string(),
+                ),
+                Some("tools/call") => response("500 Internal Server Error", "", "broken"),
+                _ => response("202 Accepted", "", ""),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn http_transport_handles_sessions_sse_and_paging() {
+        let server = serve_mcp();
+        let config = McpServerConfig {
+            id: "web".into(),
+            name: "Web".into(),
+            transport: McpTransport::Http {
+                url: format!("{}/mcp", server.base),
+                headers: HashMap::new(),
+            },
+            enabled: true,
+        };
+        let session = establish(&config).await.unwrap();
+        assert_eq!(session.server_info, Some(json!({ "name": "http-stub" })));
+        let names: Vec<&str> = session.tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+
+        let err = session
+            .client
+            .request("tools/call", json!({ "name": "first" }))
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("HTTP 500"), "{}", err);
+        session.client.shutdown().await;
+
+        let seen = server.requests();
+        let bodies: Vec<Value> = seen.iter().map(json_body).collect();
+        let methods: Vec<&str> = seen
+            .iter()
+            .zip(&bodies)
+            .map(|(s, body)| body["method"].as_str().unwrap_or(s.method.as_str()))
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                "initialize",
+                "notifications/initialized",
+                "tools/list",
+                "tools/list",
+                "tools/call",
+                "DELETE"
+            ]
+        );
+        // 初始化之后的每个请求都带上会话 ID / every request after initialize carries the session id
+        assert_eq!(seen[0].header("Mcp-Session-Id"), None);
+        assert!(seen[1..]
+            .iter()
+            .all(|s| s.header("Mcp-Session-Id") == Some("s-1")));
+        assert_eq!(bodies[3]["params"]["cursor"], "p2");
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use serde_json::Value;
+
+use crate::api::{http_client, OPENROUTER_API_BASE};
+use crate::helpers::now_millis;
+
+/// 模型列表缓存有效期（毫秒）
+/// How long the cached model list stays fresh (ms).
+const CATALOG_TTL_MS: u64 = 60 * 60 * 1000;
+
+/// 模型单价（美元/Token，美元/次）
+/// Model prices (USD per token, USD per request).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModelPricing {
+    pub prompt: f64,
+    pub completion: f64,
+    pub request: f64,
+    /// 命中缓存的输入单价 / price of a cached prompt token
+    pub cache_read: Option<f64>,
+    /// 写入缓存的输入单价 / price of a prompt token written to the cache
+    pub cache_write: Option<f64>,
+}
+
+#[derive(Default)]
+struct CatalogInner {
+    models: HashMap<String, Value>,
+    fetched_at: u64,
+}
+
+/// 后端模型目录：缓存 `/models` 返回的元数据（价格、支持参数、模态）
+/// Backend model catalog: caches `/models` metadata (pricing, parameters, modalities).
+#[derive(Default)]
+pub struct ModelCatalog {
+    inner: RwLock<CatalogInner>,
+}
+
+impl ModelCatalog {
+    fn is_fresh(&self) -> bool {
+        let inner = self.inner.read().unwrap();
+        !inner.models.is_empty() && now_millis().saturating_sub(inner.fetched_at) < CATALOG_TTL_MS
+    }
+
+    /// 必要时刷新目录
+    /// Refresh the catalog when empty or stale.
+    pub async fn ensure_loaded(&self) -> Result<(), String> {
+        if self.is_fresh() {
+            return Ok(());
+        }
+        let json: Value = http_client()
+            .get(format!("{}/models", OPENROUTER_API_BASE))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let models = json
+            .get("data")
+            .and_then(|d| d.as_array())
+            .map(|list| {
+                list.iter()
+                    .filter_map(|m| Some((m.get("id")?.as_str()?.to_string(), m.clone())))
+                    .collect::<HashMap<_, _>>()
+            })
+            .ok_or_else(|| "Malformed model list".to_string())?;
+
+        let mut inner = self.inner.write().unwrap();
+        inner.models = models;
+        inner.fetched_at = now_millis();
+        Ok(())
+    }
+
+    /// 获取模型元数据；会忽略 `:online` 之类的变体后缀
+    /// Get model metadata; variant suffixes such as `:online` are ignored.
+    pub async fn get(&self, model: &str) -> Option<Value> {
+        if let Err(e) = self.ensure_loaded().await {
+            tracing::warn!("Failed to load model catalog: {}", e);
+        }
+        let inner = self.inner.read().unwrap();
+        inner.models.get(model).cloned().or_else(|| {
+            let (base, _) = model.rsplit_once(':')?;
+            inner.models.get(base).cloned()
+        })
+    }
+}
+
+fn price_field(model: &Value, field: &str) -> Option<f64> {
+    model
+        .get("pricing")?
+        .get(field)?
+        .as_str()?
+        .parse::<f64>()
+        .ok()
+}
+
+/// 解析模型价格
+/// Parse model pricing.
+pub fn pricing_of(model: &Value) -> ModelPricing {
+    ModelPricing {
+        prompt: price_field(model, "prompt").unwrap_or(0.0),
+        completion: price_field(model, "completion").unwrap_or(0.0),
+        request: price_field(model, "request").unwrap_or(0.0),
+        cache_read: price_field(model, "input_cache_read"),
+        cache_write: price_field(model, "input_cache_write"),
+    }
+}
+
+impl ModelPricing {
+    /// 缓存节省的花费：命中按输入价减去缓存价，写入扣除额外费用
+    /// Cost saved by caching: reads save the prompt price minus the cache price, writes
+    /// subtract their surcharge.
+    pub fn cache_savings(&self, cached_tokens: u64, cache_write_tokens: u64) -> f64 {
+        let read = self
+            .cache_read
+            .map_or(0.0, |p| (self.prompt - p) * cached_tokens as f64);
+        let write = self
+            .cache_write
+            .map_or(0.0, |p| (p - self.prompt) * cache_write_tokens as f64);
+        read - write
+    }
+}
+
+/// 模型支持的请求参数（`supported_parameters`）
+/// Request parameters the model supports (`supported_parameters`).
+pub fn supported_parameters(model: &Value) -> Option<Vec<String>> {
+    Some(
+        model
+            .get("supported_parameters")?
+            .as_array()?
+            .iter()
+            .filter_map(|p| p.as_str().map(str::to_string))
+            .collect(),
+    )
+}
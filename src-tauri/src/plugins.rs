@@ -0,0 +1,627 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, State};
+use tracing::{debug, warn};
+
+use crate::settings::{read_setting, write_setting};
+
+/// 联网搜索配置在 store 中的字段名
+/// Store field holding the web search config.
+pub const WEB_SEARCH_FIELD: &str = "web_search";
+
+/// 文件解析配置在 store 中的字段名
+/// Store field holding the file parser config.
+pub const FILE_PARSER_FIELD: &str = "file_parser";
+/// 文件注解缓存文件（位于应用数据目录）
+/// File annotation cache file (in the app data dir).
+const FILE_ANNOTATIONS_FILE: &str = "file_annotations.json";
+/// file-parser 插件支持的 PDF 引擎
+/// PDF engines supported by the file-parser plugin.
+const PDF_ENGINES: &[&str] = &["pdf-text", "mistral-ocr", "native"];
+
+/// 等价于联网插件的模型后缀
+/// Model suffix equivalent to the web plugin.
+const ONLINE_SUFFIX: &str = ":online";
+
+/// OpenRouter 联网搜索插件配置
+/// OpenRouter web search plugin config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebSearchConfig {
+    /// 是否默认开启 / on by default
+    #[serde(default)]
+    pub enabled: bool,
+    /// 结果条数（1-25，默认 5）/ number of results (1-25, default 5)
+    #[serde(default)]
+    pub max_results: Option<u32>,
+    /// 附加到结果前的提示词 / prompt placed before the results
+    #[serde(default)]
+    pub search_prompt: Option<String>,
+    /// `native` / `exa`，为空时由 OpenRouter 选择 / left to OpenRouter when empty
+    #[serde(default)]
+    pub engine: Option<String>,
+    /// 原生搜索的上下文量：`low` / `medium` / `high`
+    /// Context size for native search: `low` / `medium` / `high`
+    #[serde(default)]
+    pub search_context_size: Option<String>,
+}
+
+impl WebSearchConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(n) = self.max_results {
+            if !(1..=25).contains(&n) {
+                return Err("max_results must be between 1 and 25".into());
+            }
+        }
+        if let Some(engine) = self.engine.as_deref() {
+            if !matches!(engine, "native" | "exa") {
+                return Err(format!("Unknown search engine: {}", engine));
+            }
+        }
+        if let Some(size) = self.search_context_size.as_deref() {
+            if !matches!(size, "low" | "medium" | "high") {
+                return Err(format!("Unknown search context size: {}", size));
+            }
+        }
+        Ok(())
+    }
+
+    /// 生成 `plugins` 中的 `web` 项
+    /// Build the `web` entry of `plugins`.
+    fn plugin(&self) -> Value {
+        let mut plugin = json!({ "id": "web" });
+        if let Some(n) = self.max_results {
+            plugin["max_results"] = json!(n);
+        }
+        if let Some(prompt) = self
+            .search_prompt
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+        {
+            plugin["search_prompt"] = json!(prompt);
+        }
+        if let Some(engine) = &self.engine {
+            plugin["engine"] = json!(engine);
+        }
+        plugin
+    }
+}
+
+/// 设置请求体 `plugins` 中的插件（同 ID 覆盖）
+/// Set a plugin in the body's `plugins` (replacing one with the same id).
+pub fn set_plugin(body: &mut Value, plugin: Value) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    let plugins = obj.entry("plugins").or_insert_with(|| json!([]));
+    if let Some(list) = plugins.as_array_mut() {
+        list.retain(|p| p.get("id") != plugin.get("id"));
+        list.push(plugin);
+    }
+}
+
+/// 按设置或单次覆盖写入联网搜索参数
+/// Apply the web search parameters from settings or a per-request override.
+///
+/// 模型带 `:online` 后缀时视为开启并去掉后缀，使配置的参数生效。
+/// A `:online` model suffix counts as enabled and is stripped so the configured
+/// parameters apply.
+pub fn apply_web_search(
+    app: &AppHandle,
+    body: &mut Value,
+    model: &mut String,
+    request: Option<WebSearchConfig>,
+) -> Result<(), String> {
+    let config = match request {
+        Some(config) => config,
+        None => read_setting(app, WEB_SEARCH_FIELD).unwrap_or_default(),
+    };
+    apply_web_search_config(body, model, config)
+}
+
+/// 按给定配置写入联网搜索参数
+/// Apply the web search parameters of a given config.
+fn apply_web_search_config(
+    body: &mut Value,
+    model: &mut String,
+    mut config: WebSearchConfig,
+) -> Result<(), String> {
+    config.validate()?;
+    if let Some(base) = model.strip_suffix(ONLINE_SUFFIX) {
+        *model = base.to_string();
+        config.enabled = true;
+    }
+    if !config.enabled {
+        return Ok(());
+    }
+    set_plugin(body, config.plugin());
+    if let (Some(size), Some(obj)) = (&config.search_context_size, body.as_object_mut()) {
+        obj.insert(
+            "web_search_options".into(),
+            json!({ "search_context_size": size }),
+        );
+    }
+    Ok(())
+}
+
+/// 来源引用（`url_citation` 注解）
+/// A source citation (`url_citation` annotation).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Citation {
+    pub url: String,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub start_index: Option<u64>,
+    pub end_index: Option<u64>,
+}
+
+impl Citation {
+    fn parse(annotation: &Value) -> Option<Self> {
+        if annotation.get("type")?.as_str()? != "url_citation" {
+            return None;
+        }
+        let c = annotation.get("url_citation")?;
+        let text = |key: &str| c.get(key).and_then(|v| v.as_str()).map(String::from);
+        Some(Self {
+            url: text("url")?,
+            title: text("title"),
+            content: text("content"),
+            start_index: c.get("start_index").and_then(|v| v.as_u64()),
+            end_index: c.get("end_index").and_then(|v| v.as_u64()),
+        })
+    }
+}
+
+/// 收集流式块中的注解
+/// Collects the annotations of stream chunks.
+#[derive(Default)]
+pub struct AnnotationCollector {
+    citations: Vec<Citation>,
+    /// 已解析文件的 `file` 注解 / `file` annotations of parsed files
+    files: Vec<Value>,
+}
+
+impl AnnotationCollector {
+    /// 读取一个流式块（`delta` 或 `message` 中的 `annotations`）
+    /// Feed one stream chunk (`annotations` in `delta` or `message`).
+    pub fn feed(&mut self, chunk: &Value) {
+        let annotations = [
+            "/choices/0/delta/annotations",
+            "/choices/0/message/annotations",
+        ]
+        .iter()
+        .filter_map(|p| chunk.pointer(p).and_then(|a| a.as_array()))
+        .flatten();
+        for annotation in annotations {
+            if annotation.get("type").and_then(|t| t.as_str()) == Some("file") {
+                if !self.files.contains(annotation) {
+                    self.files.push(annotation.clone());
+                }
+            } else if let Some(citation) = Citation::parse(annotation) {
+                if !self.citations.contains(&citation) {
+                    self.citations.push(citation);
+                }
+            }
+        }
+    }
+
+    /// 取出已收集的引用
+    /// Take the collected citations.
+    pub fn take_citations(&mut self) -> Vec<Citation> {
+        std::mem::take(&mut self.citations)
+    }
+
+    /// 取出已收集的文件注解
+    /// Take the collected file annotations.
+    pub fn take_files(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.files)
+    }
+}
+
+/// 文件解析配置
+/// File parser config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileParserConfig {
+    /// `pdf-text` / `mistral-ocr` / `native`，为空时由 OpenRouter 选择
+    /// `pdf-text` / `mistral-ocr` / `native`; left to OpenRouter when empty
+    #[serde(default)]
+    pub pdf_engine: Option<String>,
+}
+
+fn validate_pdf_engine(engine: &str) -> Result<(), String> {
+    if PDF_ENGINES.contains(&engine) {
+        Ok(())
+    } else {
+        Err(format!("Unknown PDF engine: {}", engine))
+    }
+}
+
+/// 消息中的 `file` 内容块的文件名
+/// File names of the `file` parts of a message.
+fn file_names(message: &Value) -> Vec<&str> {
+    message
+        .get("content")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("file"))
+        .filter_map(|part| part.pointer("/file/filename").and_then(|f| f.as_str()))
+        .collect()
+}
+
+/// 请求体中是否含有文件
+/// Whether the body carries any file.
+fn has_files(body: &Value) -> bool {
+    body.get("messages")
+        .and_then(|m| m.as_array())
+        .is_some_and(|messages| messages.iter().any(|m| !file_names(m).is_empty()))
+}
+
+/// 含文件时写入 file-parser 插件的 PDF 引擎（单次覆盖优先于设置）
+/// When files are attached, set the file-parser PDF engine (per-request wins over settings).
+pub fn apply_file_parser(
+    app: &AppHandle,
+    body: &mut Value,
+    engine: Option<String>,
+) -> Result<(), String> {
+    if !has_files(body) {
+        return Ok(());
+    }
+    let engine = engine.or_else(|| {
+        read_setting::<_, FileParserConfig>(app, FILE_PARSER_FIELD).and_then(|c| c.pdf_engine)
+    });
+    let Some(engine) = engine else {
+        return Ok(());
+    };
+    validate_pdf_engine(&engine)?;
+    set_plugin(
+        body,
+        json!({ "id": "file-parser", "pdf": { "engine": engine } }),
+    );
+    Ok(())
+}
+
+/// 按会话缓存的文件解析结果，后续轮次回传以免重复解析与计费
+/// Parsed-file annotations cached per conversation, sent back on later turns so files
+/// aren't parsed and billed again.
+pub struct FileAnnotationStore {
+    /// 会话 ID → `file` 注解 / conversation id → `file` annotations
+    entries: Mutex<HashMap<String, Vec<Value>>>,
+    path: PathBuf,
+}
+
+impl FileAnnotationStore {
+    /// 从应用数据目录加载
+    /// Load from the app data dir.
+    pub fn load(data_dir: PathBuf) -> Self {
+        let path = data_dir.join(FILE_ANNOTATIONS_FILE);
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            entries: Mutex::new(entries),
+            path,
+        }
+    }
+
+    fn save(&self, entries: &HashMap<String, Vec<Value>>) {
+        let result = serde_json::to_string(entries)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&self.path, json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to save file annotations: {}", e);
+        }
+    }
+
+    /// 记录会话的文件注解（按文件名替换旧项）
+    /// Remember a conversation's file annotations (replacing older ones by name).
+    pub fn remember(&self, conversation_id: &str, annotations: Vec<Value>) {
+        if annotations.is_empty() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let list = entries.entry(conversation_id.to_string()).or_default();
+        for annotation in annotations {
+            let name = annotation.pointer("/file/name").cloned();
+            list.retain(|a| a.pointer("/file/name") != name.as_ref());
+            list.push(annotation);
+        }
+        self.save(&entries);
+    }
+
+    /// 把缓存的注解挂到对应文件之后的第一条 assistant 消息上
+    /// Attach cached annotations to the first assistant message after the matching file.
+    pub fn reattach(&self, conversation_id: &str, body: &mut Value) {
+        let cached = match self.entries.lock().unwrap().get(conversation_id) {
+            Some(list) => list.clone(),
+            None => return,
+        };
+        let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+            return;
+        };
+        for annotation in cached {
+            let Some(name) = annotation.pointer("/file/name").and_then(|n| n.as_str()) else {
+                continue;
+            };
+            let Some(file_at) = messages.iter().position(|m| file_names(m).contains(&name)) else {
+                continue;
+            };
+            let Some(reply) = messages[file_at + 1..]
+                .iter_mut()
+                .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("assistant"))
+            else {
+                continue;
+            };
+            let Some(obj) = reply.as_object_mut() else {
+                continue;
+            };
+            let list = obj.entry("annotations").or_insert_with(|| json!([]));
+            if let Some(list) = list.as_array_mut() {
+                if !list.contains(&annotation) {
+                    debug!(conversation = %conversation_id, file = %name, "reusing parsed file");
+                    list.push(annotation.clone());
+                }
+            }
+        }
+    }
+}
+
+/// 读取文件解析配置
+/// Get the file parser config.
+#[command]
+pub fn get_file_parser_config(app: AppHandle) -> FileParserConfig {
+    read_setting(&app, FILE_PARSER_FIELD).unwrap_or_default()
+}
+
+/// 保存文件解析配置
+/// Save the file parser config.
+#[command]
+pub fn save_file_parser_config(app: AppHandle, config: FileParserConfig) -> Result<(), String> {
+    if let Some(engine) = config.pdf_engine.as_deref() {
+        validate_pdf_engine(engine)?;
+    }
+    write_setting(&app, FILE_PARSER_FIELD, &config)
+}
+
+/// 清除会话缓存的文件解析结果（如会话被删除时）
+/// Forget a conversation's parsed files (e.g. when it is deleted).
+#[command]
+pub fn clear_file_annotations(store: State<'_, FileAnnotationStore>, conversation_id: String) {
+    let mut entries = store.entries.lock().unwrap();
+    if entries.remove(&conversation_id).is_some() {
+        store.save(&entries);
+    }
+}
+
+/// 读取联网搜索配置
+/// Get the web search config.
+#[command]
+pub fn get_web_search_config(app: AppHandle) -> WebSearchConfig {
+    read_setting(&app, WEB_SEARCH_FIELD).unwrap_or_default()
+}
+
+/// 保存联网搜索配置
+/// Save the web search config.
+#[command]
+pub fn save_web_search_config(app: AppHandle, config: WebSearchConfig) -> Result<(), String> {
+    config.validate()?;
+    write_setting(&app, WEB_SEARCH_FIELD, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(
+        max_results: Option<u32>,
+        engine: Option<&str>,
+        size: Option<&str>,
+    ) -> WebSearchConfig {
+        WebSearchConfig {
+            enabled: true,
+            max_results,
+            search_prompt: None,
+            engine: engine.map(str::to_string),
+            search_context_size: size.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn web_search_config_is_validated() {
+        assert!(config(Some(1), Some("native"), Some("low"))
+            .validate()
+            .is_ok());
+        assert!(config(Some(25), Some("exa"), Some("high"))
+            .validate()
+            .is_ok());
+        assert!(WebSearchConfig::default().validate().is_ok());
+        assert!(config(Some(0), None, None).validate().is_err());
+        assert!(config(Some(26), None, None).validate().is_err());
+        assert!(config(None, Some("bing"), None).validate().is_err());
+        assert!(config(None, None, Some("huge")).validate().is_err());
+    }
+
+    #[test]
+    fn web_plugin_carries_only_set_fields() {
+        assert_eq!(WebSearchConfig::default().plugin(), json!({ "id": "web" }));
+        let mut full = config(Some(3), Some("exa"), Some("low"));
+        full.search_prompt = Some("Sources:".into());
+        assert_eq!(
+            full.plugin(),
+            json!({ "id": "web", "max_results": 3, "search_prompt": "Sources:", "engine": "exa" })
+        );
+        // 空白提示词不写入 / a blank prompt is left out
+        full.search_prompt = Some("  ".into());
+        assert!(full.plugin().get("search_prompt").is_none());
+    }
+
+    #[test]
+    fn online_suffix_enables_search_and_is_stripped() {
+        let mut body = json!({ "plugins": [{ "id": "web", "max_results": 9 }, { "id": "other" }] });
+        let mut model = "openai/gpt-4o:online".to_string();
+        let disabled = WebSearchConfig {
+            max_results: Some(2),
+            search_context_size: Some("high".into()),
+            ..Default::default()
+        };
+        apply_web_search_config(&mut body, &mut model, disabled).unwrap();
+        assert_eq!(model, "openai/gpt-4o");
+        assert_eq!(
+            body,
+            json!({
+                "plugins": [{ "id": "other" }, { "id": "web", "max_results": 2 }],
+                "web_search_options": { "search_context_size": "high" },
+            })
+        );
+
+        // 未开启且无后缀时不改动请求 / untouched when off and unsuffixed
+        let mut body = json!({});
+        let mut model = "openai/gpt-4o".to_string();
+        apply_web_search_config(&mut body, &mut model, WebSearchConfig::default()).unwrap();
+        assert_eq!(body, json!({}));
+
+        let mut model = "m:online".to_string();
+        let invalid = config(Some(99), None, None);
+        assert!(apply_web_search_config(&mut body, &mut model, invalid).is_err());
+    }
+
+    fn citation(url: &str, title: &str) -> Value {
+        json!({
+            "type": "url_citation",
+            "url_citation": { "url": url, "title": title, "start_index": 1, "end_index": 5 },
+        })
+    }
+
+    #[test]
+    fn collector_parses_and_dedups_annotations() {
+        let file = json!({ "type": "file", "file": { "name": "a.pdf", "content": [] } });
+        let mut collector = AnnotationCollector::default();
+        collector.feed(&json!({ "choices": [{ "delta": {
+            "annotations": [citation("https://a", "A"), file.clone(), { "type": "unknown" }],
+        } }] }));
+        // 同一注解可在 delta 与最终 message 中重复出现 / the same annotation may repeat in the final message
+        collector.feed(&json!({ "choices": [{ "message": {
+            "annotations": [
+                citation("https://a", "A"),
+                citation("https://b", "B"),
+                file.clone(),
+                { "type": "url_citation", "url_citation": { "title": "no url" } },
+            ],
+        } }] }));
+
+        let citations = collector.take_citations();
+        assert_eq!(
+            citations[0],
+            Citation {
+                url: "https://a".into(),
+                title: Some("A".into()),
+                content: None,
+                start_index: Some(1),
+                end_index: Some(5),
+            }
+        );
+        let urls: Vec<&str> = citations.iter().map(|c| c.url.as_str()).collect();
+        assert_eq!(urls, ["https://a", "https://b"]);
+        assert_eq!(collector.take_files(), [file]);
+        assert!(collector.take_citations().is_empty());
+        assert!(collector.take_files().is_empty());
+    }
+
+    fn store(name: &str) -> (FileAnnotationStore, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("sengine-files-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        (FileAnnotationStore::load(dir.clone()), dir)
+    }
+
+    fn parsed(name: &str, text: &str) -> Value {
+        json!({ "type": "file", "file": { "name": name, "content": [{ "type": "text", "text": text }] } })
+    }
+
+    fn with_file(name: &str) -> Value {
+        json!({ "role": "user", "content": [
+            { "type": "text", "text": "Summarize" },
+            { "type": "file", "file": { "filename": name, "file_data": "data:application/pdf;base64," } },
+        ] })
+    }
+
+    #[test]
+    fn remember_replaces_by_name_and_persists() {
+        let (store, dir) = store("remember");
+        store.remember("c", vec![parsed("a.pdf", "old"), parsed("b.pdf", "b")]);
+        store.remember("c", vec![parsed("a.pdf", "new")]);
+        store.remember("c", Vec::new());
+        let expected = vec![parsed("b.pdf", "b"), parsed("a.pdf", "new")];
+        assert_eq!(store.entries.lock().unwrap()["c"], expected);
+        assert_eq!(
+            FileAnnotationStore::load(dir.clone())
+                .entries
+                .lock()
+                .unwrap()["c"],
+            expected
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reattach_to_the_reply_after_the_matching_file() {
+        let (store, dir) = store("match");
+        store.remember("c", vec![parsed("a.pdf", "A"), parsed("other.pdf", "O")]);
+        let mut body = json!({ "messages": [
+            { "role": "assistant", "content": "Hi" },
+            with_file("a.pdf"),
+            { "role": "assistant", "content": "Summary" },
+            { "role": "user", "content": "More" },
+        ] });
+        store.reattach("c", &mut body);
+        assert!(body.pointer("/messages/0/annotations").is_none());
+        assert_eq!(
+            body["messages"][2]["annotations"],
+            json!([parsed("a.pdf", "A")])
+        );
+        // 其他会话不受影响 / other conversations are untouched
+        let mut other = body.clone();
+        store.reattach("unknown", &mut other);
+        assert_eq!(other, body);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reattach_skips_a_missing_reply() {
+        let (store, dir) = store("missing");
+        store.remember("c", vec![parsed("a.pdf", "A")]);
+        // 文件所在的消息是最后一条，尚无 assistant 回复 / the file is in the last message, no reply yet
+        let mut body =
+            json!({ "messages": [{ "role": "assistant", "content": "Hi" }, with_file("a.pdf")] });
+        let before = body.clone();
+        store.reattach("c", &mut body);
+        assert_eq!(body, before);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reattach_keeps_an_annotation_already_present() {
+        let (store, dir) = store("present");
+        store.remember("c", vec![parsed("a.pdf", "A")]);
+        let mut body = json!({ "messages": [
+            with_file("a.pdf"),
+            { "role": "assistant", "content": "Summary", "annotations": [parsed("a.pdf", "A")] },
+        ] });
+        store.reattach("c", &mut body);
+        store.reattach("c", &mut body);
+        assert_eq!(
+            body["messages"][1]["annotations"],
+            json!([parsed("a.pdf", "A")])
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,760 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{command, AppHandle, Emitter, Manager, State, Window};
+use tracing::{info, warn};
+
+use crate::embeddings::{
+    cosine_top_k, embed_cached, embed_texts, EmbeddingProvider, EmbeddingTarget,
+};
+use crate::helpers::now_millis;
+use crate::settings::{read_setting, write_setting};
+
+/// 检索配置在 store 中的字段名
+/// Store field holding the retrieval config.
+pub const RAG_FIELD: &str = "rag";
+/// 索引目录（位于应用数据目录）
+/// Index directory (in the app data dir).
+const INDEX_DIR: &str = "rag";
+/// 索引 ID 的长度（根目录 SHA-256 的十六进制前缀）
+/// Length of an index id (hex prefix of the root's SHA-256).
+const INDEX_ID_LEN: usize = 16;
+
+/// 可作为文本索引的扩展名（与前端 `SUPPORTED_TEXTABLE_EXT` 一致）
+/// Extensions indexed as text (same as the frontend `SUPPORTED_TEXTABLE_EXT`).
+const TEXTABLE_EXTS: &str = "\
+    txt text log err out md mdx markdown rst adoc asciidoc tex ltx bib org csv tsv psv ssv \
+    tab json jsonl ndjson json5 jsonc hjson map yaml yml toml ini cfg conf config cnf \
+    properties prop env lock tmpl template xml dtd xsd xsl xslt svg html htm xhtml shtml css \
+    scss sass less styl js mjs cjs jsx ts tsx vue svelte astro py pyi pyw rb gemspec rake \
+    erb haml php phtml php3 php4 php5 pl pm t lua r jl go rs ron java gradle groovy kt kts \
+    scala sbt c h i ii cpp cxx cc hpp hh hxx m mm cs csx vb fs fsi fsx sql psql graphql gql \
+    proto thrift avdl avsc hcl tf tfvars cue rego nix bzl bazel cmake mk mak make diff patch \
+    eml ics";
+
+/// 检索配置
+/// Retrieval config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RagConfig {
+    /// 嵌入服务 / embedding provider
+    #[serde(default)]
+    pub embedding: EmbeddingProvider,
+    /// 每块的目标字符数 / target characters per chunk
+    #[serde(default = "default_chunk_chars")]
+    pub chunk_chars: usize,
+    /// 相邻块的重叠字符数 / characters shared by adjacent chunks
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: usize,
+    /// 注入的块数 / chunks injected per request
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// 超过此大小的文件跳过 / files larger than this are skipped
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// 单个索引的文件数上限 / max files per index
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+fn default_chunk_chars() -> usize {
+    1500
+}
+
+fn default_chunk_overlap() -> usize {
+    200
+}
+
+fn default_top_k() -> usize {
+    6
+}
+
+fn default_max_file_bytes() -> u64 {
+    512 * 1024
+}
+
+fn default_max_files() -> usize {
+    10_000
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self {
+            embedding: EmbeddingProvider::default(),
+            chunk_chars: default_chunk_chars(),
+            chunk_overlap: default_chunk_overlap(),
+            top_k: default_top_k(),
+            max_file_bytes: default_max_file_bytes(),
+            max_files: default_max_files(),
+        }
+    }
+}
+
+impl RagConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(200..=20_000).contains(&self.chunk_chars) {
+            return Err("chunk_chars must be between 200 and 20000".into());
+        }
+        if self.chunk_overlap >= self.chunk_chars / 2 {
+            return Err("chunk_overlap must be less than half of chunk_chars".into());
+        }
+        if !(1..=50).contains(&self.top_k) {
+            return Err("top_k must be between 1 and 50".into());
+        }
+        Ok(())
+    }
+}
+
+/// 单次请求的检索参数
+/// Retrieval parameters of one request.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RetrievalOptions {
+    /// 要检索的索引 ID / index ids to search
+    pub index_ids: Vec<String>,
+    /// 覆盖设置中的块数 / overrides the configured chunk count
+    #[serde(default)]
+    pub top_k: Option<usize>,
+}
+
+/// 文本块
+/// A text chunk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    /// 相对索引根目录的路径 / path relative to the index root
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    size: u64,
+    modified: u64,
+    first_chunk: usize,
+    chunk_count: usize,
+}
+
+/// 索引概况
+/// Index summary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderIndexInfo {
+    pub id: String,
+    pub root: String,
+    pub model: String,
+    /// 嵌入服务地址；旧索引为空 / embedding base URL; empty for older indexes
+    #[serde(default)]
+    pub base_url: String,
+    pub dims: usize,
+    pub file_count: usize,
+    pub chunk_count: usize,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FolderIndexMeta {
+    info: FolderIndexInfo,
+    files: BTreeMap<String, IndexedFile>,
+    chunks: Vec<IndexedChunk>,
+}
+
+/// 内存中的索引：元数据 + 连续存放的单位向量
+/// An index in memory: metadata plus contiguous unit vectors.
+struct FolderIndex {
+    meta: FolderIndexMeta,
+    vectors: Vec<f32>,
+}
+
+impl FolderIndex {
+    fn vector(&self, chunk: usize) -> &[f32] {
+        let dims = self.meta.info.dims;
+        &self.vectors[chunk * dims..(chunk + 1) * dims]
+    }
+}
+
+/// 检索命中的块
+/// A retrieved chunk.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetrievedChunk {
+    pub index_id: String,
+    pub root: String,
+    #[serde(flatten)]
+    pub chunk: IndexedChunk,
+    pub score: f32,
+}
+
+/// 本地文件夹索引存储
+/// Local folder index store.
+pub struct RagStore {
+    dir: PathBuf,
+    /// 已加载的索引 / loaded indexes
+    loaded: Mutex<HashMap<String, Arc<FolderIndex>>>,
+    /// 正在建立的索引 / indexes being built
+    indexing: Mutex<HashSet<String>>,
+}
+
+impl RagStore {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            dir: data_dir.join(INDEX_DIR),
+            loaded: Mutex::new(HashMap::new()),
+            indexing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn meta_path(&self, id: &str) -> Result<PathBuf, String> {
+        check_index_id(id)?;
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn vectors_path(&self, id: &str) -> Result<PathBuf, String> {
+        check_index_id(id)?;
+        Ok(self.dir.join(format!("{}.vec", id)))
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<FolderIndex>, String> {
+        if let Some(index) = self.loaded.lock().unwrap().get(id) {
+            return Ok(index.clone());
+        }
+        let meta: FolderIndexMeta = std::fs::read_to_string(self.meta_path(id)?)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .ok_or_else(|| format!("Index not found: {}", id))?;
+        let bytes = std::fs::read(self.vectors_path(id)?).map_err(|e| e.to_string())?;
+        let vectors: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if vectors.len() != meta.chunks.len() * meta.info.dims {
+            return Err(format!("Index {} is corrupt; rebuild it", id));
+        }
+        let index = Arc::new(FolderIndex { meta, vectors });
+        self.loaded
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), index.clone());
+        Ok(index)
+    }
+
+    fn save(&self, index: FolderIndex) -> Result<FolderIndexInfo, String> {
+        let id = index.meta.info.id.clone();
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let bytes: Vec<u8> = index.vectors.iter().flat_map(|v| v.to_le_bytes()).collect();
+        std::fs::write(self.vectors_path(&id)?, bytes).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string(&index.meta).map_err(|e| e.to_string())?;
+        std::fs::write(self.meta_path(&id)?, json).map_err(|e| e.to_string())?;
+        let info = index.meta.info.clone();
+        self.loaded.lock().unwrap().insert(id, Arc::new(index));
+        Ok(info)
+    }
+
+    fn list(&self) -> Vec<FolderIndexInfo> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut list: Vec<FolderIndexInfo> = entries
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|x| x == "json"))
+            .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+            .filter_map(|s| serde_json::from_str::<Value>(&s).ok())
+            .filter_map(|v| serde_json::from_value(v.get("info")?.clone()).ok())
+            .collect();
+        list.sort_by(|a, b| a.root.cmp(&b.root));
+        list
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        let (meta, vectors) = (self.meta_path(id)?, self.vectors_path(id)?);
+        self.loaded.lock().unwrap().remove(id);
+        let _ = std::fs::remove_file(meta);
+        let _ = std::fs::remove_file(vectors);
+        Ok(())
+    }
+}
+
+/// 索引 ID 只能是 `index_id` 生成的 16 位十六进制，防止路径穿越
+/// An index id must be the 16 hex digits `index_id` produces, which rules out path traversal.
+fn check_index_id(id: &str) -> Result<(), String> {
+    if id.len() == INDEX_ID_LEN && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(format!("Invalid index id: {}", id))
+    }
+}
+
+/// 由根目录计算稳定的索引 ID
+/// Stable index id derived from the root folder.
+fn index_id(root: &Path) -> String {
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    format!("{:x}", digest)[..INDEX_ID_LEN].to_string()
+}
+
+fn is_textable(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        TEXTABLE_EXTS
+            .split_whitespace()
+            .any(|x| x.eq_ignore_ascii_case(e))
+    })
+}
+
+/// 按行切块，块间保留约 `overlap` 字符的重叠
+/// Split by lines, keeping about `overlap` characters shared between chunks.
+fn chunk_text(path: &str, text: &str, max_chars: usize, overlap: usize) -> Vec<IndexedChunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        let mut size = 0;
+        while end < lines.len() && (end == start || size + lines[end].len() < max_chars) {
+            size += lines[end].len() + 1;
+            end += 1;
+        }
+        let body = lines[start..end].join("\n");
+        if !body.trim().is_empty() {
+            // 超长单行按字符截断 / cut a single overlong line
+            let body = match body.char_indices().nth(max_chars) {
+                Some((cut, _)) => body[..cut].to_string(),
+                None => body,
+            };
+            chunks.push(IndexedChunk {
+                path: path.to_string(),
+                start_line: start + 1,
+                end_line: end,
+                text: body,
+            });
+        }
+        if end >= lines.len() {
+            break;
+        }
+        // 向前回退若干行形成重叠 / step back a few lines for the overlap
+        let mut back = end;
+        let mut shared = 0;
+        while back > start + 1 && shared + lines[back - 1].len() < overlap {
+            shared += lines[back - 1].len() + 1;
+            back -= 1;
+        }
+        start = back;
+    }
+    chunks
+}
+
+fn modified_millis(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// 遍历文件夹（遵循 .gitignore）并切块；未变化的文件沿用旧索引
+/// Walk the folder (honoring .gitignore) and chunk it; unchanged files reuse the old index.
+fn collect_chunks(
+    root: &Path,
+    config: &RagConfig,
+    previous: Option<&FolderIndex>,
+) -> (
+    BTreeMap<String, IndexedFile>,
+    Vec<IndexedChunk>,
+    Vec<Option<usize>>,
+) {
+    let mut files = BTreeMap::new();
+    let mut chunks = Vec::new();
+    // 每个块在旧索引中的位置（可复用向量）/ each chunk's position in the old index, if reusable
+    let mut reuse = Vec::new();
+    let walker = WalkBuilder::new(root)
+        .hidden(true)
+        .git_ignore(true)
+        .require_git(false)
+        .build();
+    for entry in walker.flatten() {
+        if files.len() >= config.max_files {
+            warn!(root = %root.display(), "file limit reached while indexing");
+            break;
+        }
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|t| t.is_file()) || !is_textable(path) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.len() > config.max_file_bytes {
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let modified = modified_millis(&metadata);
+
+        let old = previous.and_then(|p| {
+            p.meta
+                .files
+                .get(&relative)
+                .filter(|f| f.size == metadata.len() && f.modified == modified)
+        });
+        let file_chunks: Vec<(IndexedChunk, Option<usize>)> = match old {
+            Some(old) => (old.first_chunk..old.first_chunk + old.chunk_count)
+                .map(|i| (previous.unwrap().meta.chunks[i].clone(), Some(i)))
+                .collect(),
+            None => {
+                let Ok(bytes) = std::fs::read(path) else {
+                    continue;
+                };
+                // 含 NUL 视为二进制 / NUL bytes mean binary
+                if bytes.contains(&0) {
+                    continue;
+                }
+                let text = String::from_utf8_lossy(&bytes);
+                chunk_text(&relative, &text, config.chunk_chars, config.chunk_overlap)
+                    .into_iter()
+                    .map(|c| (c, None))
+                    .collect()
+            }
+        };
+        files.insert(
+            relative,
+            IndexedFile {
+                size: metadata.len(),
+                modified,
+                first_chunk: chunks.len(),
+                chunk_count: file_chunks.len(),
+            },
+        );
+        for (chunk, old_index) in file_chunks {
+            chunks.push(chunk);
+            reuse.push(old_index);
+        }
+    }
+    (files, chunks, reuse)
+}
+
+/// 读取检索配置
+/// Load the retrieval config.
+fn load_config(app: &AppHandle) -> RagConfig {
+    read_setting(app, RAG_FIELD).unwrap_or_default()
+}
+
+/// 建立或更新文件夹索引
+/// Build or update a folder index.
+async fn build_index(
+    app: &AppHandle,
+    window: &Window,
+    root: PathBuf,
+) -> Result<FolderIndexInfo, String> {
+    let config = load_config(app);
+    let target = EmbeddingTarget::resolve(app, &config.embedding)?;
+    let store = app.state::<RagStore>();
+    let id = index_id(&root);
+
+    let previous = store
+        .get(&id)
+        .ok()
+        .filter(|p| p.meta.info.model == target.model && p.meta.info.base_url == target.base_url);
+    let (files, chunks, reuse) = {
+        let (root, config, previous) = (root.clone(), config.clone(), previous.clone());
+        tauri::async_runtime::spawn_blocking(move || {
+            collect_chunks(&root, &config, previous.as_deref())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    };
+
+    let pending: Vec<usize> = (0..chunks.len()).filter(|&i| reuse[i].is_none()).collect();
+    let texts: Vec<String> = pending
+        .iter()
+        .map(|&i| format!("{}\n{}", chunks[i].path, chunks[i].text))
+        .collect();
+    let total = texts.len();
+    let fresh = embed_texts(&target, &texts, |done| {
+        let _ = window.emit(
+            "rag-progress",
+            json!({ "index_id": id, "done": done, "total": total }),
+        );
+    })
+    .await?;
+
+    let dims = fresh
+        .first()
+        .map(Vec::len)
+        .or_else(|| previous.as_ref().map(|p| p.meta.info.dims))
+        .unwrap_or(0);
+    let mut fresh = fresh.into_iter();
+    let mut vectors = Vec::with_capacity(chunks.len() * dims);
+    for old in &reuse {
+        match (old, previous.as_ref()) {
+            (Some(i), Some(previous)) => vectors.extend_from_slice(previous.vector(*i)),
+            _ => {
+                let vector = fresh.next().ok_or("Missing embedding")?;
+                if vector.len() != dims {
+                    return Err("Embedding dimensions changed mid-index".into());
+                }
+                vectors.extend(vector);
+            }
+        }
+    }
+
+    let info = FolderIndexInfo {
+        id: id.clone(),
+        root: root.display().to_string(),
+        model: target.model,
+        base_url: target.base_url,
+        dims,
+        file_count: files.len(),
+        chunk_count: chunks.len(),
+        updated_at: now_millis(),
+    };
+    info!(
+        index_id = %id,
+        files = info.file_count,
+        chunks = info.chunk_count,
+        embedded = total,
+        "folder indexed"
+    );
+    store.save(FolderIndex {
+        meta: FolderIndexMeta {
+            info,
+            files,
+            chunks,
+        },
+        vectors,
+    })
+}
+
+/// 在若干索引中检索与查询最相近的块
+/// Find the chunks closest to a query across indexes.
+pub async fn search(
+    app: &AppHandle,
+    index_ids: &[String],
+    query: &str,
+    top_k: usize,
+) -> Result<Vec<RetrievedChunk>, String> {
+    let config = load_config(app);
+    let store = app.state::<RagStore>();
+    let indexes = index_ids
+        .iter()
+        .map(|id| store.get(id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(model) = indexes.first().map(|i| i.meta.info.model.clone()) else {
+        return Ok(Vec::new());
+    };
+    if indexes.iter().any(|i| i.meta.info.model != model) {
+        return Err("Indexes were built with different embedding models".into());
+    }
+    let mut provider = config.embedding.clone();
+    provider.model = Some(model);
+    let target = EmbeddingTarget::resolve(app, &provider)?;
+    // 查询向量须与索引来自同一服务 / the query must be embedded by the service that built the index
+    if let Some(index) = indexes
+        .iter()
+        .find(|i| !i.meta.info.base_url.is_empty() && i.meta.info.base_url != target.base_url)
+    {
+        return Err(format!(
+            "Index {} was built with a different embedding service; rebuild it",
+            index.meta.info.id
+        ));
+    }
+    let (mut vectors, _) = embed_cached(app, &target, &[query.to_string()]).await?;
+    let query = vectors.pop().ok_or("Missing query embedding")?;
+
+    let mut chunks: Vec<(&FolderIndex, usize)> = Vec::new();
+    for index in &indexes {
+        if index.meta.info.dims != query.len() {
+            return Err(format!(
+                "Index {} has mismatched dimensions",
+                index.meta.info.id
+            ));
+        }
+        chunks.extend((0..index.meta.chunks.len()).map(|i| (index.as_ref(), i)));
+    }
+    let hits = cosine_top_k(
+        &query,
+        chunks.iter().map(|(index, i)| index.vector(*i)),
+        top_k,
+    );
+    Ok(hits
+        .into_iter()
+        .map(|(pos, score)| (score, chunks[pos].0, chunks[pos].1))
+        .map(|(score, index, i)| RetrievedChunk {
+            index_id: index.meta.info.id.clone(),
+            root: index.meta.info.root.clone(),
+            chunk: index.meta.chunks[i].clone(),
+            score,
+        })
+        .collect())
+}
+
+/// 消息的纯文本内容
+/// Plain text of a message.
+fn message_text(message: &Value) -> String {
+    match message.get("content") {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 以最后一条用户消息检索，并把命中的块作为 system 消息插到它之前；返回命中的块
+/// Search with the last user message and insert the hits as a system message before it;
+/// returns the hits.
+pub async fn apply_retrieval(
+    app: &AppHandle,
+    body: &mut Value,
+    options: &RetrievalOptions,
+) -> Result<Vec<RetrievedChunk>, String> {
+    if options.index_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return Ok(Vec::new());
+    };
+    let Some(at) = messages
+        .iter()
+        .rposition(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+    else {
+        return Ok(Vec::new());
+    };
+    let query = message_text(&messages[at]);
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let top_k = options.top_k.unwrap_or_else(|| load_config(app).top_k);
+    let hits = search(app, &options.index_ids, &query, top_k).await?;
+    if hits.is_empty() {
+        return Ok(hits);
+    }
+
+    let mut context = String::from(
+        "The following excerpts from the user's local files may be relevant. \
+         Use them when helpful and cite the file path when you do.\n",
+    );
+    for hit in &hits {
+        context.push_str(&format!(
+            "\n<source path=\"{}\" lines=\"{}-{}\">\n{}\n</source>\n",
+            hit.chunk.path, hit.chunk.start_line, hit.chunk.end_line, hit.chunk.text
+        ));
+    }
+    messages.insert(at, json!({ "role": "system", "content": context }));
+    Ok(hits)
+}
+
+/// 为文件夹建立（或增量更新）索引，进度通过 `rag-progress` 发送
+/// Index a folder (or update it incrementally); progress goes out on `rag-progress`.
+#[command]
+pub async fn index_folder(
+    app: AppHandle,
+    window: Window,
+    path: String,
+) -> Result<FolderIndexInfo, String> {
+    let root = std::fs::canonicalize(&path).map_err(|e| e.to_string())?;
+    if !root.is_dir() {
+        return Err(format!("{} is not a directory", root.display()));
+    }
+    let id = index_id(&root);
+    let store = app.state::<RagStore>();
+    if !store.indexing.lock().unwrap().insert(id.clone()) {
+        return Err("This folder is already being indexed".into());
+    }
+    let result = build_index(&app, &window, root).await;
+    store.indexing.lock().unwrap().remove(&id);
+    result
+}
+
+/// 列出已建立的索引
+/// List the built indexes.
+#[command]
+pub fn list_folder_indexes(store: State<'_, RagStore>) -> Vec<FolderIndexInfo> {
+    store.list()
+}
+
+/// 删除索引
+/// Delete an index.
+#[command]
+pub fn delete_folder_index(store: State<'_, RagStore>, id: String) -> Result<(), String> {
+    store.delete(&id)
+}
+
+/// 直接检索索引（用于调试或展示来源）
+/// Search indexes directly (for debugging or showing sources).
+#[command]
+pub async fn search_folder_indexes(
+    app: AppHandle,
+    index_ids: Vec<String>,
+    query: String,
+    top_k: Option<usize>,
+) -> Result<Vec<RetrievedChunk>, String> {
+    let top_k = top_k.unwrap_or_else(|| load_config(&app).top_k);
+    search(&app, &index_ids, &query, top_k).await
+}
+
+/// 读取检索配置
+/// Get the retrieval config.
+#[command]
+pub fn get_rag_config(app: AppHandle) -> RagConfig {
+    load_config(&app)
+}
+
+/// 保存检索配置
+/// Save the retrieval config.
+#[command]
+pub fn save_rag_config(app: AppHandle, config: RagConfig) -> Result<(), String> {
+    config.validate()?;
+    write_setting(&app, RAG_FIELD, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_ids_must_be_hex() {
+        let id = index_id(Path::new("/tmp/project"));
+        assert!(check_index_id(&id).is_ok());
+        assert!(check_index_id("../../etc/passwd").is_err());
+        assert!(check_index_id("0123456789abcdeg").is_err());
+        assert!(check_index_id("0123456789abcde").is_err());
+    }
+
+    #[test]
+    fn store_rejects_traversal_ids() {
+        let store = RagStore::new(std::env::temp_dir());
+        assert!(store.delete("../x").is_err());
+        assert!(store.get("../../x").is_err());
+    }
+
+    #[test]
+    fn chunks_cover_every_line_with_overlap() {
+        let text: String = (1..=50).map(|i| format!("line {:02}\n", i)).collect();
+        let chunks = chunk_text("a.txt", &text, 100, 20);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks.last().unwrap().end_line, 50);
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start_line <= pair[0].end_line);
+            assert!(pair[1].start_line > pair[0].start_line);
+        }
+    }
+
+    #[test]
+    fn overlong_lines_are_cut() {
+        let chunks = chunk_text("a.txt", &"x".repeat(500), 100, 10);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text.len(), 100);
+    }
+
+    #[test]
+    fn blank_text_has_no_chunks() {
+        assert!(chunk_text("a.txt", "\n\n  \n", 100, 10).is_empty());
+    }
+}
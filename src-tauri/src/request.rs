@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{command, AppHandle, Manager};
+
+use crate::models::{supported_parameters, ModelCatalog};
+
+/// 停止序列：单个字符串或字符串数组
+/// Stop sequences: one string or a list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Stop {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// 回答详略程度
+/// Response verbosity.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    Low,
+    Medium,
+    High,
+}
+
+/// 带类型的聊天请求体；未列出的字段（`tools`、`plugins` 等）原样保留
+/// Typed chat request body; unlisted fields (`tools`, `plugins`, ...) pass through unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_a: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Stop>,
+    /// 词元 ID 到偏置（-100 到 100）/ token id to bias (-100 to 100)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<BTreeMap<String, f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<Verbosity>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn check_range(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<(), String> {
+    match value {
+        Some(v) if !(min..=max).contains(&v) => Err(format!(
+            "{} must be between {} and {}, got {}",
+            name, min, max, v
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl ChatRequest {
+    /// 从请求体解析（类型错误时报出字段）
+    /// Parse from a request body (type errors name the field).
+    pub fn from_body(body: &Value) -> Result<Self, String> {
+        serde_json::from_value(body.clone()).map_err(|e| format!("Invalid request body: {}", e))
+    }
+
+    /// 转回请求体
+    /// Convert back into a request body.
+    pub fn into_body(self) -> Result<Value, String> {
+        serde_json::to_value(self).map_err(|e| e.to_string())
+    }
+
+    /// 检查各采样参数的取值范围
+    /// Check the range of each sampling parameter.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.messages.is_empty() {
+            return Err("messages must not be empty".into());
+        }
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("min_p", self.min_p, 0.0, 1.0)?;
+        check_range("top_a", self.top_a, 0.0, 1.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        check_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        check_range("repetition_penalty", self.repetition_penalty, 0.0, 2.0)?;
+        if self.repetition_penalty == Some(0.0) {
+            return Err("repetition_penalty must be greater than 0".into());
+        }
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens must be at least 1".into());
+        }
+        if let Some(Stop::Many(list)) = &self.stop {
+            if list.iter().any(String::is_empty) {
+                return Err("stop sequences must not be empty".into());
+            }
+        }
+        for (token, bias) in self.logit_bias.iter().flatten() {
+            if token.parse::<u64>().is_err() {
+                return Err(format!("logit_bias key {:?} is not a token id", token));
+            }
+            check_range("logit_bias value", Some(*bias), -100.0, 100.0)?;
+        }
+        if let Some(top) = self.top_logprobs {
+            if top > 20 {
+                return Err("top_logprobs must be between 0 and 20".into());
+            }
+            if self.logprobs != Some(true) {
+                return Err("top_logprobs requires logprobs to be true".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// 已设置的采样参数名（与 `supported_parameters` 同名）
+    /// Names of the sampling parameters that are set (as in `supported_parameters`).
+    pub fn set_parameters(&self) -> Vec<&'static str> {
+        [
+            ("temperature", self.temperature.is_some()),
+            ("top_p", self.top_p.is_some()),
+            ("top_k", self.top_k.is_some()),
+            ("min_p", self.min_p.is_some()),
+            ("top_a", self.top_a.is_some()),
+            ("frequency_penalty", self.frequency_penalty.is_some()),
+            ("presence_penalty", self.presence_penalty.is_some()),
+            ("repetition_penalty", self.repetition_penalty.is_some()),
+            ("seed", self.seed.is_some()),
+            ("max_tokens", self.max_tokens.is_some()),
+            ("stop", self.stop.is_some()),
+            ("logit_bias", self.logit_bias.is_some()),
+            ("logprobs", self.logprobs.is_some()),
+            ("top_logprobs", self.top_logprobs.is_some()),
+            ("verbosity", self.verbosity.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+
+    /// 检查已设置的参数是否都在模型的 `supported_parameters` 中
+    /// Check that every set parameter is in the model's `supported_parameters`.
+    pub fn check_supported(&self, model: &str, supported: &[String]) -> Result<(), String> {
+        let unsupported: Vec<&str> = self
+            .set_parameters()
+            .into_iter()
+            .filter(|name| !supported.iter().any(|s| s == name))
+            .collect();
+        if unsupported.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} does not support: {}",
+                model,
+                unsupported.join(", ")
+            ))
+        }
+    }
+}
+
+/// 解析并校验请求体；模型在目录中时再按其 `supported_parameters` 检查
+/// Parse and validate a request body, and check it against the model's `supported_parameters`
+/// when the model is in the catalog.
+pub async fn validate_body(app: &AppHandle, body: &Value, model: &str) -> Result<Value, String> {
+    let request = ChatRequest::from_body(body)?;
+    request.validate()?;
+    if let Some(supported) = app
+        .state::<ModelCatalog>()
+        .get(model)
+        .await
+        .and_then(|m| supported_parameters(&m))
+    {
+        request.check_supported(model, &supported)?;
+    }
+    request.into_body()
+}
+
+/// 校验聊天请求体（不发送）
+/// Validate a chat request body without sending it.
+#[command]
+pub async fn validate_chat_request(
+    app: AppHandle,
+    body: Value,
+    model: String,
+) -> Result<(), String> {
+    validate_body(&app, &body, &model).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(extra: Value) -> Result<ChatRequest, String> {
+        let mut body = json!({ "model": "m", "messages": [{ "role": "user", "content": "hi" }] });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        ChatRequest::from_body(&body)
+    }
+
+    #[test]
+    fn accepts_values_in_range() {
+        let req = request(json!({
+            "temperature": 2.0,
+            "top_p": 0.5,
+            "repetition_penalty": 1.1,
+            "stop": ["\n\n"],
+            "logit_bias": { "50256": -100 },
+            "logprobs": true,
+            "top_logprobs": 5,
+        }))
+        .unwrap();
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        for extra in [
+            json!({ "temperature": 2.5 }),
+            json!({ "top_p": -0.1 }),
+            json!({ "repetition_penalty": 0.0 }),
+            json!({ "max_tokens": 0 }),
+            json!({ "stop": ["a", ""] }),
+            json!({ "logit_bias": { "word": 1 } }),
+            json!({ "logit_bias": { "1": 101 } }),
+            json!({ "top_logprobs": 5 }),
+            json!({ "logprobs": true, "top_logprobs": 21 }),
+        ] {
+            let req = request(extra.clone()).unwrap();
+            assert!(req.validate().is_err(), "{} should be rejected", extra);
+        }
+        let empty = ChatRequest::from_body(&json!({ "messages": [] })).unwrap();
+        assert_eq!(empty.validate().unwrap_err(), "messages must not be empty");
+    }
+
+    #[test]
+    fn type_errors_name_the_field() {
+        let err = request(json!({ "temperature": "hot" })).unwrap_err();
+        assert!(err.contains("Invalid request body"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_fields_round_trip() {
+        let req = request(json!({ "tools": [{ "type": "function" }], "seed": 7 })).unwrap();
+        let body = req.into_body().unwrap();
+        assert_eq!(body["tools"], json!([{ "type": "function" }]));
+        assert_eq!(body["seed"], 7);
+        assert!(body.get("temperature").is_none());
+    }
+
+    #[test]
+    fn unsupported_parameters_are_listed() {
+        let req = request(json!({ "temperature": 1.0, "seed": 1, "top_k": 40 })).unwrap();
+        let supported = vec!["temperature".to_string(), "seed".to_string()];
+        assert_eq!(
+            req.check_supported("m", &supported).unwrap_err(),
+            "m does not support: top_k"
+        );
+        let supported = vec!["temperature".into(), "seed".into(), "top_k".into()];
+        assert!(req.check_supported("m", &supported).is_ok());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, AppHandle};
+
+use crate::settings::{read_setting, write_setting};
+
+/// 供应商路由配置在 store 中的字段名
+/// Store field holding the provider routing config.
+pub const PROVIDER_ROUTING_FIELD: &str = "provider_routing";
+
+/// OpenRouter 接受的量化级别
+/// Quantization levels OpenRouter accepts.
+const QUANTIZATIONS: &[&str] = &[
+    "int4", "int8", "fp4", "fp6", "fp8", "fp16", "bf16", "fp32", "unknown",
+];
+
+/// 是否允许会保留数据的供应商
+/// Whether providers that retain data are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataCollection {
+    Allow,
+    Deny,
+}
+
+/// 供应商排序依据
+/// What providers are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderSort {
+    Price,
+    Throughput,
+    Latency,
+}
+
+/// 可接受的最高单价（美元/百万 Token，美元/次）
+/// Highest acceptable prices (USD per million tokens, USD per request).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaxPrice {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<f64>,
+}
+
+/// OpenRouter 请求体中的 `provider` 对象；未设置的字段由 OpenRouter 决定
+/// The `provider` object of an OpenRouter request; unset fields are left to OpenRouter.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderPreferences {
+    /// 依次尝试的供应商 / providers to try in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// 只用支持全部请求参数的供应商 / only providers supporting every request parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_parameters: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_collection: Option<DataCollection>,
+    /// 只用零数据保留的端点 / zero data retention endpoints only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zdr: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub only: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantizations: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<ProviderSort>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_price: Option<MaxPrice>,
+}
+
+impl ProviderPreferences {
+    pub fn validate(&self) -> Result<(), String> {
+        for (field, list) in [
+            ("order", &self.order),
+            ("only", &self.only),
+            ("ignore", &self.ignore),
+        ] {
+            if list.iter().flatten().any(|p| p.trim().is_empty()) {
+                return Err(format!("{} must not contain empty provider names", field));
+            }
+        }
+        if let (Some(only), Some(ignore)) = (&self.only, &self.ignore) {
+            if let Some(both) = only.iter().find(|p| ignore.contains(p)) {
+                return Err(format!("{} is both allowed and ignored", both));
+            }
+        }
+        if let Some(q) = self
+            .quantizations
+            .iter()
+            .flatten()
+            .find(|q| !QUANTIZATIONS.contains(&q.as_str()))
+        {
+            return Err(format!("Unknown quantization: {}", q));
+        }
+        if let Some(price) = &self.max_price {
+            let prices = [price.prompt, price.completion, price.request, price.image];
+            if prices.iter().flatten().any(|p| *p < 0.0 || !p.is_finite()) {
+                return Err("max_price values must be non-negative".into());
+            }
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        serde_json::to_value(self).map_or(true, |v| v.as_object().is_none_or(|o| o.is_empty()))
+    }
+
+    /// 以 `self` 为准，未设置的字段取自 `base`
+    /// Fields set in `self` win; the rest come from `base`.
+    fn over(self, base: &Self) -> Self {
+        let base = base.clone();
+        Self {
+            order: self.order.or(base.order),
+            allow_fallbacks: self.allow_fallbacks.or(base.allow_fallbacks),
+            require_parameters: self.require_parameters.or(base.require_parameters),
+            data_collection: self.data_collection.or(base.data_collection),
+            zdr: self.zdr.or(base.zdr),
+            only: self.only.or(base.only),
+            ignore: self.ignore.or(base.ignore),
+            quantizations: self.quantizations.or(base.quantizations),
+            sort: self.sort.or(base.sort),
+            max_price: self.max_price.or(base.max_price),
+        }
+    }
+}
+
+/// 供应商路由配置：全局默认与按会话覆盖
+/// Provider routing config: global defaults and per-conversation overrides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderRoutingConfig {
+    #[serde(default)]
+    pub default: ProviderPreferences,
+    /// 会话 → 覆盖项 / conversation → overrides
+    #[serde(default)]
+    pub conversations: HashMap<String, ProviderPreferences>,
+}
+
+fn load_config(app: &AppHandle) -> ProviderRoutingConfig {
+    read_setting(app, PROVIDER_ROUTING_FIELD).unwrap_or_default()
+}
+
+/// 写入请求体的 `provider` 对象
+/// Write the body's `provider` object.
+///
+/// 逐字段取值，优先级：请求体中已有的 > 单次覆盖 > 会话覆盖 > 全局默认。
+/// Each field comes from, in priority order: the body's own `provider` > the per-request
+/// override > the conversation override > the global defaults.
+pub fn apply_provider_routing(
+    app: &AppHandle,
+    body: &mut Value,
+    conversation_id: Option<&str>,
+    request: Option<ProviderPreferences>,
+) -> Result<(), String> {
+    apply_routing_config(load_config(app), body, conversation_id, request)
+}
+
+/// 按给定配置写入请求体的 `provider` 对象
+/// Write the body's `provider` object from a given config.
+fn apply_routing_config(
+    config: ProviderRoutingConfig,
+    body: &mut Value,
+    conversation_id: Option<&str>,
+    request: Option<ProviderPreferences>,
+) -> Result<(), String> {
+    let mut preferences = config.default;
+    if let Some(conversation) = conversation_id.and_then(|c| config.conversations.get(c)) {
+        preferences = conversation.clone().over(&preferences);
+    }
+    if let Some(request) = request {
+        preferences = request.over(&preferences);
+    }
+    preferences.validate()?;
+    if preferences.is_empty() {
+        return Ok(());
+    }
+    let object = body.as_object_mut().ok_or("Body is not a JSON object")?;
+    let Value::Object(fields) = serde_json::to_value(&preferences).map_err(|e| e.to_string())?
+    else {
+        return Ok(());
+    };
+    let provider = object
+        .entry("provider")
+        .or_insert_with(|| Value::Object(Default::default()));
+    let provider = provider
+        .as_object_mut()
+        .ok_or("provider must be a JSON object")?;
+    for (key, value) in fields {
+        provider.entry(key).or_insert(value);
+    }
+    Ok(())
+}
+
+/// 读取供应商路由配置
+/// Get the provider routing config.
+#[command]
+pub fn get_provider_routing(app: AppHandle) -> ProviderRoutingConfig {
+    load_config(&app)
+}
+
+/// 保存全局默认的供应商路由偏好
+/// Save the default provider routing preferences.
+#[command]
+pub fn save_provider_routing(
+    app: AppHandle,
+    preferences: ProviderPreferences,
+) -> Result<ProviderRoutingConfig, String> {
+    preferences.validate()?;
+    let mut config = load_config(&app);
+    config.default = preferences;
+    write_setting(&app, PROVIDER_ROUTING_FIELD, &config)?;
+    Ok(config)
+}
+
+/// 设置/清除某个会话的供应商路由覆盖
+/// Set or clear a conversation's provider routing overrides.
+#[command]
+pub fn set_conversation_provider_routing(
+    app: AppHandle,
+    conversation_id: String,
+    preferences: Option<ProviderPreferences>,
+) -> Result<ProviderRoutingConfig, String> {
+    let mut config = load_config(&app);
+    match preferences {
+        Some(preferences) => {
+            preferences.validate()?;
+            config.conversations.insert(conversation_id, preferences);
+        }
+        None => {
+            config.conversations.remove(&conversation_id);
+        }
+    }
+    write_setting(&app, PROVIDER_ROUTING_FIELD, &config)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn preferences(value: Value) -> ProviderPreferences {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn over_prefers_set_fields() {
+        let base = preferences(json!({
+            "order": ["a"], "allow_fallbacks": false, "sort": "price", "zdr": true,
+        }));
+        let top = preferences(json!({ "order": ["b", "c"], "sort": "latency", "only": ["b"] }));
+        let merged = serde_json::to_value(top.over(&base)).unwrap();
+        assert_eq!(
+            merged,
+            json!({
+                "order": ["b", "c"], "allow_fallbacks": false, "sort": "latency",
+                "zdr": true, "only": ["b"],
+            })
+        );
+        let empty = ProviderPreferences::default().over(&ProviderPreferences::default());
+        assert!(empty.is_empty());
+    }
+
+    fn config() -> ProviderRoutingConfig {
+        ProviderRoutingConfig {
+            default: preferences(json!({ "sort": "price", "data_collection": "deny" })),
+            conversations: HashMap::from([(
+                "c".to_string(),
+                preferences(json!({ "sort": "throughput", "order": ["x"] })),
+            )]),
+        }
+    }
+
+    #[test]
+    fn layers_apply_in_priority_order() {
+        let mut body = json!({ "model": "m" });
+        apply_routing_config(config(), &mut body, Some("c"), None).unwrap();
+        assert_eq!(
+            body["provider"],
+            json!({ "sort": "throughput", "data_collection": "deny", "order": ["x"] })
+        );
+
+        let mut body = json!({});
+        let request = preferences(json!({ "sort": "latency", "data_collection": "allow" }));
+        apply_routing_config(config(), &mut body, Some("c"), Some(request)).unwrap();
+        assert_eq!(
+            body["provider"],
+            json!({ "sort": "latency", "data_collection": "allow", "order": ["x"] })
+        );
+    }
+
+    #[test]
+    fn keys_already_in_the_body_win() {
+        let mut body = json!({ "provider": { "sort": "latency", "only": ["mine"], "custom": 1 } });
+        apply_routing_config(config(), &mut body, None, None).unwrap();
+        assert_eq!(
+            body["provider"],
+            json!({ "sort": "latency", "only": ["mine"], "custom": 1, "data_collection": "deny" })
+        );
+
+        let mut body = json!({ "provider": "openai" });
+        assert!(apply_routing_config(config(), &mut body, None, None).is_err());
+    }
+
+    #[test]
+    fn empty_preferences_leave_the_body_alone() {
+        let mut body = json!({ "model": "m" });
+        apply_routing_config(ProviderRoutingConfig::default(), &mut body, Some("c"), None).unwrap();
+        assert_eq!(body, json!({ "model": "m" }));
+
+        let invalid = preferences(json!({ "only": ["a"], "ignore": ["a"] }));
+        let result = apply_routing_config(config(), &mut body, None, Some(invalid));
+        assert_eq!(result.unwrap_err(), "a is both allowed and ignored");
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{de::DeserializeOwned, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_store::StoreExt;
+
+/// 前端与后端共用的设置文件
+/// Settings file shared by the frontend and the backend.
+pub const STORE_FILE: &str = "store.json";
+
+/// 读取设置字段；不存在或格式不符时返回 `None`
+/// Read a settings field; returns `None` when missing or malformed.
+pub fn read_setting<R: Runtime, T: DeserializeOwned>(app: &AppHandle<R>, key: &str) -> Option<T> {
+    let store = app.store(STORE_FILE).ok()?;
+    let value = store.get(key)?;
+    serde_json::from_value(value).ok()
+}
+
+/// 写入设置字段并立即落盘
+/// Write a settings field and persist it immediately.
+pub fn write_setting<R: Runtime, T: Serialize>(
+    app: &AppHandle<R>,
+    key: &str,
+    value: &T,
+) -> Result<(), String> {
+    let store = app.store(STORE_FILE).map_err(|e| e.to_string())?;
+    let json = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    store.set(key, json);
+    store.save().map_err(|e| e.to_string())
+}
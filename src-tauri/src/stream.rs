@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Manager, State};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+use crate::api::http_client;
+use crate::budget::{estimate_cost, load_budget_rules, BudgetManager, BudgetStatus};
+use crate::keys::ResolvedKey;
+use crate::logging::TraceStore;
+use crate::models::{pricing_of, ModelCatalog};
+use crate::routing::apply_provider_routing;
+use crate::tools::approval::ApprovalManager;
+use crate::usage::{RequestLog, UsageLog};
+
+/// SSE 事件
+/// An SSE event.
+#[derive(Debug, PartialEq)]
+pub enum SseEvent {
+    /// `data:` 负载 / `data:` payload
+    Data(String),
+    /// `[DONE]` 结束标记 / `[DONE]` terminator
+    Done,
+}
+
+/// 带缓冲的 SSE 解析器：按行切分，跨块拼接，忽略注释行
+/// Buffered SSE parser: splits by line, joins across chunks, ignores comments.
+#[derive(Default)]
+pub struct SseParser {
+    buf: Vec<u8>,
+    data: String,
+}
+
+impl SseParser {
+    fn take_event(&mut self) -> Option<SseEvent> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = std::mem::take(&mut self.data);
+        Some(if data == "[DONE]" {
+            SseEvent::Done
+        } else {
+            SseEvent::Data(data)
+        })
+    }
+
+    /// 推入一段字节，返回其中完整的事件
+    /// Push bytes and return the complete events they contain.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let raw: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                events.extend(self.take_event());
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !self.data.is_empty() {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            }
+        }
+        events
+    }
+
+    /// 流结束时取出残留事件
+    /// Flush the pending event at end of stream.
+    pub fn finish(&mut self) -> Vec<SseEvent> {
+        if !self.buf.is_empty() {
+            let rest = std::mem::take(&mut self.buf);
+            let mut events = self.push(&rest);
+            events.extend(self.push(b"\n\n"));
+            return events;
+        }
+        self.take_event().into_iter().collect()
+    }
+}
+
+/// 上游接口类型
+/// Upstream endpoint kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    /// `/chat/completions`
+    Chat,
+    /// `/completions`（原始提示词）/ `/completions` (raw prompt)
+    Completion,
+}
+
+impl Endpoint {
+    pub fn path(self) -> &'static str {
+        match self {
+            Endpoint::Chat => "/chat/completions",
+            Endpoint::Completion => "/completions",
+        }
+    }
+
+    /// 从流式块中取出文本增量
+    /// Extract the text delta from a stream chunk.
+    pub fn delta_text(self, chunk: &Value) -> Option<&str> {
+        let choice = chunk.get("choices")?.get(0)?;
+        match self {
+            Endpoint::Chat => choice.get("delta")?.get("content")?.as_str(),
+            Endpoint::Completion => choice.get("text")?.as_str(),
+        }
+    }
+}
+
+/// 流式请求失败原因
+/// Why a streaming request failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamFailure {
+    /// `budget_exceeded` / `request_failed` / `invalid_request`
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<BudgetStatus>,
+}
+
+impl StreamFailure {
+    fn request_failed(message: String) -> Self {
+        Self {
+            code: "request_failed".into(),
+            message,
+            budget: None,
+        }
+    }
+}
+
+/// 一次流式请求的结果
+/// Result of one streaming request.
+#[derive(Debug, Default)]
+pub struct StreamOutput {
+    /// 拼接后的完整文本 / assembled text
+    pub text: String,
+    pub usage: Option<Value>,
+    pub finish_reason: Option<String>,
+    /// 是否被用户取消 / whether the user cancelled it
+    pub cancelled: bool,
+    /// 请求记录 / request record
+    pub record: RequestLog,
+}
+
+/// 流式任务描述
+/// Description of a streaming job.
+///
+/// 创建时即在 `StreamRegistry` 中登记取消令牌，任务释放时移除，因此 `cancel_stream`
+/// 在连接前和多轮请求之间同样有效。
+/// The cancellation token is registered in `StreamRegistry` on creation and removed when the
+/// job is dropped, so `cancel_stream` also works before connecting and between rounds.
+pub struct StreamJob {
+    pub request_id: String,
+    pub key: ResolvedKey,
+    pub model: String,
+    pub conversation_id: Option<String>,
+    pub endpoint: Endpoint,
+    pub body: Value,
+    pub cancel: CancellationToken,
+    app: AppHandle,
+}
+
+impl StreamJob {
+    /// 构建任务：写入 model、stream 与 usage 字段，并套用供应商路由（请求体中已有的字段优先）
+    /// Build a job; fills in `model`, `stream` and `usage` and applies provider routing (fields
+    /// already in the body win).
+    pub fn new(
+        app: &AppHandle,
+        request_id: String,
+        key: ResolvedKey,
+        model: String,
+        conversation_id: Option<String>,
+        endpoint: Endpoint,
+        mut body: Value,
+    ) -> Result<Self, String> {
+        let obj = body
+            .as_object_mut()
+            .ok_or_else(|| "Body is not a JSON object".to_string())?;
+        obj.insert("model".to_string(), Value::String(model.clone()));
+        obj.insert("stream".to_string(), Value::Bool(true));
+        // 请求在最后一个块中返回 usage（含花费）/ ask for usage (with cost) in the final chunk
+        obj.entry("usage")
+            .or_insert_with(|| json!({ "include": true }));
+        apply_provider_routing(app, &mut body, conversation_id.as_deref(), None)?;
+        let cancel = app.state::<StreamRegistry>().register(&request_id);
+        Ok(Self {
+            request_id,
+            key,
+            model,
+            conversation_id,
+            endpoint,
+            body,
+            cancel,
+            app: app.clone(),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}{}", self.key.base_url, self.endpoint.path())
+    }
+}
+
+impl Drop for StreamJob {
+    fn drop(&mut self) {
+        self.app.state::<StreamRegistry>().remove(&self.request_id);
+    }
+}
+
+/// 进行中的流式请求：请求 ID → 取消令牌
+/// In-flight streams: request id → cancellation token.
+#[derive(Default)]
+pub struct StreamRegistry {
+    active: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl StreamRegistry {
+    pub fn register(&self, request_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.active
+            .lock()
+            .unwrap()
+            .insert(request_id.to_string(), token.clone());
+        token
+    }
+
+    pub fn remove(&self, request_id: &str) {
+        self.active.lock().unwrap().remove(request_id);
+    }
+
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.active.lock().unwrap().remove(request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 请求前预算检查：超出硬上限时返回失败，否则返回软警告
+/// Pre-request budget check: fails on a hard cap, otherwise returns soft warnings.
+pub async fn check_budget(
+    app: &AppHandle,
+    job: &StreamJob,
+) -> Result<Vec<BudgetStatus>, StreamFailure> {
+    let estimate = estimate_cost(&app.state::<ModelCatalog>(), &job.model, &job.body).await;
+    let check = app.state::<BudgetManager>().check(
+        &load_budget_rules(app),
+        &job.key.id,
+        &job.model,
+        estimate,
+    );
+    match check.blocked {
+        Some(blocked) => Err(StreamFailure {
+            code: "budget_exceeded".into(),
+            message: format!(
+                "Budget exceeded: spent ${:.4} of ${:.4} ({:?} limit)",
+                blocked.spent,
+                blocked.rule.hard_limit.unwrap_or(0.0),
+                blocked.rule.period
+            ),
+            budget: Some(blocked),
+        }),
+        None => Ok(check.warnings),
+    }
+}
+
+/// 执行一次流式请求，负责取消、请求日志、请求详情与记账
+/// Run one streaming request; handles cancellation, request log, trace capture and ledger.
+///
+/// 每个解析出的 JSON 块与其中的文本增量都会交给 `on_chunk`。
+/// Each parsed JSON chunk and its text delta are handed to `on_chunk`.
+pub async fn execute<F>(
+    app: &AppHandle,
+    job: &StreamJob,
+    mut on_chunk: F,
+) -> Result<StreamOutput, StreamFailure>
+where
+    F: FnMut(&Value, Option<&str>) -> Result<(), String>,
+{
+    // 连接前已取消：不发请求也不记账 / cancelled before connecting: no request, nothing logged
+    if job.cancel.is_cancelled() {
+        return Ok(StreamOutput {
+            cancelled: true,
+            ..Default::default()
+        });
+    }
+    let traces = app.state::<TraceStore>();
+    let url = job.url();
+    let cancel = &job.cancel;
+    let mut trace = traces.begin(&job.request_id, &job.model, &url, &job.body, &job.key.token);
+    let mut output = StreamOutput {
+        record: RequestLog::start(
+            &job.request_id,
+            &job.key.id,
+            &job.model,
+            job.conversation_id.clone(),
+        ),
+        ..Default::default()
+    };
+    info!(request_id = %job.request_id, model = %job.model, key_id = %job.key.id, "stream start");
+
+    let outcome: Result<(), String> = async {
+        let request = http_client()
+            .post(&url)
+            .bearer_auth(&job.key.token)
+            .json(&job.body)
+            .send();
+        let response = tokio::select! {
+            _ = cancel.cancelled() => {
+                output.cancelled = true;
+                return Ok(());
+            }
+            response = request => response.map_err(|e| e.to_string())?,
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "Request failed with status: {}, body: {}",
+                status, text
+            ));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut parser = SseParser::default();
+        loop {
+            let item = tokio::select! {
+                _ = cancel.cancelled() => {
+                    output.cancelled = true;
+                    break;
+                }
+                item = stream.next() => item,
+            };
+            let (events, mut done) = match item {
+                Some(chunk) => {
+                    let chunk = chunk.map_err(|e| e.to_string())?;
+                    if let Some(trace) = trace.as_mut() {
+                        trace.push_raw(&String::from_utf8_lossy(&chunk));
+                    }
+                    (parser.push(&chunk), false)
+                }
+                None => (parser.finish(), true),
+            };
+            for event in events {
+                let data = match event {
+                    SseEvent::Done => {
+                        done = true;
+                        break;
+                    }
+                    SseEvent::Data(data) => data,
+                };
+                let Ok(chunk) = serde_json::from_str::<Value>(&data) else {
+                    continue;
+                };
+                if let Some(message) = chunk.pointer("/error/message").and_then(|m| m.as_str()) {
+                    return Err(message.to_string());
+                }
+                output.record.observe_chunk(&chunk);
+                let text = job.endpoint.delta_text(&chunk);
+                if let Some(text) = text {
+                    output.record.mark_first_token();
+                    output.text.push_str(text);
+                }
+                if let Some(reason) = chunk
+                    .pointer("/choices/0/finish_reason")
+                    .and_then(|r| r.as_str())
+                {
+                    output.finish_reason = Some(reason.to_string());
+                }
+                if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+                    output.usage = Some(usage.clone());
+                }
+                on_chunk(&chunk, text)?;
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    output
+        .record
+        .finish(output.usage.as_ref(), outcome.as_ref().err());
+    if output.record.cached_tokens > 0 || output.record.cache_write_tokens > 0 {
+        if let Some(meta) = app.state::<ModelCatalog>().get(&job.model).await {
+            output.record.cache_savings = pricing_of(&meta).cache_savings(
+                output.record.cached_tokens,
+                output.record.cache_write_tokens,
+            );
+        }
+    }
+    app.state::<UsageLog>().append(&output.record);
+    traces.finish(trace, outcome.as_ref().err());
+    if let Some(cost) = output
+        .usage
+        .as_ref()
+        .and_then(|u| u.get("cost"))
+        .and_then(|c| c.as_f64())
+    {
+        app.state::<BudgetManager>()
+            .record(&job.key.id, &job.model, cost);
+    }
+
+    match outcome {
+        Ok(()) => {
+            debug!(
+                request_id = %job.request_id,
+                latency_ms = output.record.latency_ms,
+                ttft_ms = ?output.record.ttft_ms,
+                cost = output.record.cost,
+                cancelled = output.cancelled,
+                "stream done"
+            );
+            Ok(output)
+        }
+        Err(e) => {
+            error!(request_id = %job.request_id, "stream failed: {}", e);
+            Err(StreamFailure::request_failed(e))
+        }
+    }
+}
+
+/// 取消进行中的流式请求
+/// Cancel an in-flight streaming request.
+#[command]
+pub fn cancel_stream(
+    registry: State<'_, StreamRegistry>,
+    approvals: State<'_, ApprovalManager>,
+    request_id: String,
+) -> bool {
+    // 也取消等待审批的工具调用 / also cancels tool calls awaiting approval
+    let streaming = registry.cancel(&request_id);
+    approvals.cancel_request(&request_id) || streaming
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_events_join_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: {\"a\":").is_empty());
+        assert!(parser.push(b"1}\r\n").is_empty());
+        assert_eq!(
+            parser.push(b"\r\n: keep-alive\n\ndata: [DONE]\n\n"),
+            vec![SseEvent::Data("{\"a\":1}".into()), SseEvent::Done]
+        );
+    }
+
+    #[test]
+    fn sse_multi_line_data_and_trailing_event() {
+        let mut parser = SseParser::default();
+        assert_eq!(
+            parser.push(b"event: message\ndata: one\ndata:two\n\n"),
+            vec![SseEvent::Data("one\ntwo".into())]
+        );
+        assert!(parser.push(b"data: last").is_empty());
+        assert_eq!(parser.finish(), vec![SseEvent::Data("last".into())]);
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn delta_text_by_endpoint() {
+        let chat = json!({ "choices": [{ "delta": { "content": "hi" } }] });
+        let completion = json!({ "choices": [{ "text": "hi" }] });
+        assert_eq!(Endpoint::Chat.delta_text(&chat), Some("hi"));
+        assert_eq!(Endpoint::Completion.delta_text(&completion), Some("hi"));
+        assert_eq!(Endpoint::Chat.delta_text(&completion), None);
+    }
+}
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Manager};
+use tracing::info;
+
+use crate::models::{supported_parameters, ModelCatalog};
+
+/// 默认的 schema 名称
+/// Default schema name.
+const DEFAULT_SCHEMA_NAME: &str = "response";
+/// 回传给模型的校验错误条数上限
+/// Max validation errors reported back to the model.
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// 结构化输出请求参数
+/// Structured output parameters of a request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StructuredOutput {
+    /// JSON Schema
+    pub schema: Value,
+    /// schema 名称 / schema name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 严格模式（仅原生支持时生效）/ strict mode (native support only)
+    #[serde(default = "default_strict")]
+    pub strict: bool,
+    /// 校验失败后自动要求模型修复的次数 / automatic repair retries after a failed validation
+    #[serde(default)]
+    pub repair_attempts: u32,
+}
+
+fn default_strict() -> bool {
+    true
+}
+
+/// 单条校验错误
+/// A single validation error.
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaError {
+    /// 出错位置（JSON Pointer）/ failing location (JSON Pointer)
+    pub path: String,
+    pub message: String,
+}
+
+/// 最终回答的校验结果
+/// Validation result of the final answer.
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaCheck {
+    pub valid: bool,
+    /// 解析出的 JSON（可解析时）/ parsed JSON, when it parses
+    pub value: Option<Value>,
+    pub errors: Vec<SchemaError>,
+}
+
+/// 模型是否原生支持结构化输出（`supported_parameters` 含 `structured_outputs`）
+/// Whether the model supports structured outputs natively (`structured_outputs` in `supported_parameters`).
+async fn supports_structured_outputs(app: &AppHandle, model: &str) -> bool {
+    app.state::<ModelCatalog>()
+        .get(model)
+        .await
+        .and_then(|m| supported_parameters(&m))
+        .is_some_and(|params| params.iter().any(|p| p == "structured_outputs"))
+}
+
+/// 为请求加入结构化输出约束，返回模型是否原生支持
+/// Add the structured output constraint to a request; returns whether the model supports it natively.
+///
+/// 原生支持时发送 `response_format: {type: "json_schema"}`；否则在 system 消息中附上 schema，
+/// 回答仍在本地校验。
+/// Natively supported models get `response_format: {type: "json_schema"}`; other models get the
+/// schema in a system message, and the answer is still validated locally.
+pub async fn apply_structured_output(
+    app: &AppHandle,
+    body: &mut Value,
+    model: &str,
+    output: &StructuredOutput,
+) -> Result<bool, String> {
+    jsonschema::validator_for(&output.schema).map_err(|e| format!("Invalid JSON schema: {}", e))?;
+    let object = body.as_object_mut().ok_or("Body is not a JSON object")?;
+    let native = supports_structured_outputs(app, model).await;
+    if native {
+        object.insert(
+            "response_format".into(),
+            json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": output.name.as_deref().unwrap_or(DEFAULT_SCHEMA_NAME),
+                    "strict": output.strict,
+                    "schema": output.schema,
+                },
+            }),
+        );
+    } else {
+        object.remove("response_format");
+        let instruction = format!(
+            "Respond with a single JSON value that conforms to this JSON Schema. \
+             Output only the JSON, without code fences or commentary.\n\n{}",
+            serde_json::to_string_pretty(&output.schema).unwrap_or_default()
+        );
+        if let Some(messages) = object.get_mut("messages").and_then(|m| m.as_array_mut()) {
+            messages.insert(0, json!({ "role": "system", "content": instruction }));
+        }
+    }
+    info!(model = %model, native, "structured output requested");
+    Ok(native)
+}
+
+/// 去掉包裹 JSON 的 Markdown 代码块
+/// Strip a Markdown code fence around the JSON.
+fn strip_fence(text: &str) -> &str {
+    let text = text.trim();
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    let rest = rest.split_once('\n').map_or("", |(_, body)| body);
+    rest.trim_end().strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// 按 schema 校验模型的最终回答
+/// Validate the model's final answer against the schema.
+pub fn check_output(text: &str, schema: &Value) -> Result<SchemaCheck, String> {
+    let validator =
+        jsonschema::validator_for(schema).map_err(|e| format!("Invalid JSON schema: {}", e))?;
+    let value: Value = match serde_json::from_str(strip_fence(text)) {
+        Ok(value) => value,
+        Err(e) => {
+            return Ok(SchemaCheck {
+                valid: false,
+                value: None,
+                errors: vec![SchemaError {
+                    path: String::new(),
+                    message: format!("Response is not valid JSON: {}", e),
+                }],
+            })
+        }
+    };
+    let errors: Vec<SchemaError> = validator
+        .iter_errors(&value)
+        .map(|e| SchemaError {
+            path: e.instance_path().to_string(),
+            message: e.to_string(),
+        })
+        .collect();
+    Ok(SchemaCheck {
+        valid: errors.is_empty(),
+        value: Some(value),
+        errors,
+    })
+}
+
+/// 修复请求：把校验错误告诉模型并要求只返回修正后的 JSON
+/// Repair prompt: tell the model what failed and ask for the corrected JSON only.
+pub fn repair_message(check: &SchemaCheck) -> Value {
+    let errors = check
+        .errors
+        .iter()
+        .take(MAX_REPORTED_ERRORS)
+        .map(|e| {
+            if e.path.is_empty() {
+                format!("- {}", e.message)
+            } else {
+                format!("- {}: {}", e.path, e.message)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    json!({
+        "role": "user",
+        "content": format!(
+            "Your response did not match the required JSON Schema:\n{}\n\n\
+             Reply again with only the corrected JSON.",
+            errors
+        ),
+    })
+}
+
+/// 按 JSON Schema 校验文本
+/// Validate text against a JSON Schema.
+#[command]
+pub fn validate_structured_output(text: String, schema: Value) -> Result<SchemaCheck, String> {
+    check_output(&text, &schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" }, "age": { "type": "integer" } },
+            "required": ["name"],
+        })
+    }
+
+    #[test]
+    fn fences_are_stripped() {
+        assert_eq!(strip_fence("```json\n{\"a\":1}\n```"), "{\"a\":1}");
+        assert_eq!(strip_fence("```\n[1]\n```  "), "[1]");
+        assert_eq!(strip_fence("  {\"a\":1} "), "{\"a\":1}");
+    }
+
+    #[test]
+    fn valid_output_parses() {
+        let check = check_output("```json\n{\"name\":\"Ann\",\"age\":3}\n```", &schema()).unwrap();
+        assert!(check.valid);
+        assert_eq!(check.value, Some(json!({ "name": "Ann", "age": 3 })));
+    }
+
+    #[test]
+    fn schema_errors_have_paths() {
+        let check = check_output("{\"age\":\"old\"}", &schema()).unwrap();
+        assert!(!check.valid);
+        assert!(check.errors.iter().any(|e| e.path == "/age"));
+        assert!(check.errors.iter().any(|e| e.path.is_empty()));
+        let repair = repair_message(&check);
+        assert_eq!(repair["role"], "user");
+        assert!(repair["content"].as_str().unwrap().contains("- /age: "));
+    }
+
+    #[test]
+    fn non_json_and_bad_schemas() {
+        let check = check_output("Sure! Here it is.", &schema()).unwrap();
+        assert!(!check.valid && check.value.is_none());
+        assert!(check.errors[0]
+            .message
+            .starts_with("Response is not valid JSON"));
+        assert!(check_output("{}", &json!({ "type": 12 })).is_err());
+    }
+}
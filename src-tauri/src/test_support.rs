@@ -0,0 +1,116 @@
+//! 测试用的本地 HTTP 桩服务器
+//! Local HTTP stub server for tests.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// 桩服务器收到的一条请求
+/// One request seen by the stub server.
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    pub path: String,
+    /// 头名称已转为小写 / header names are lowercased
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// 运行中的桩服务器：地址与按顺序记录的请求
+/// A running stub server: its address and the requests it saw, in order.
+pub struct StubServer {
+    /// `http://127.0.0.1:<port>`
+    pub base: String,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+}
+
+impl StubServer {
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.requests().into_iter().map(|r| r.path).collect()
+    }
+}
+
+/// 拼出一个完整的 HTTP/1.1 响应（`headers` 每项以 `\r\n` 结尾）
+/// Build a full HTTP/1.1 response (each entry of `headers` ends with `\r\n`).
+pub fn response(status: &str, headers: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        headers,
+        body.len(),
+        body
+    )
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<StubRequest> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next().unwrap_or("/").to_string();
+    let mut headers = HashMap::new();
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+        header.clear();
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some(StubRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+/// 启动桩服务器，每个请求的响应由 `handler` 给出
+/// Start a stub server; `handler` produces the response to each request.
+pub fn serve(handler: impl Fn(&StubRequest) -> String + Send + 'static) -> StubServer {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let log = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Some(request) = read_request(&mut BufReader::new(&stream)) else {
+                continue;
+            };
+            let reply = handler(&request);
+            log.lock().unwrap().push(request);
+            let _ = (&stream).write_all(reply.as_bytes());
+        }
+    });
+    StubServer { base, requests }
+}
+
+/// 按路径返回固定响应，其余路径 404
+/// Canned responses by path; any other path is a 404.
+pub fn serve_routes(routes: Vec<(&'static str, String)>) -> StubServer {
+    let routes: HashMap<&str, String> = routes.into_iter().collect();
+    serve(move |request| {
+        routes
+            .get(request.path.as_str())
+            .cloned()
+            .unwrap_or_else(|| response("404 Not Found", "", ""))
+    })
+}
@@ -0,0 +1,510 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager, Window};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+pub mod approval;
+pub mod calc;
+pub mod clock;
+pub mod fs;
+pub mod shell;
+pub mod web;
+
+/// 工具调用循环的默认最大轮数
+/// Default max rounds of the tool-calling loop.
+pub const DEFAULT_MAX_TOOL_ITERATIONS: u32 = 8;
+
+/// 工具的权限范围
+/// Permission scope of a tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolScope {
+    /// 纯计算 / pure computation
+    Compute,
+    /// 读取系统时钟 / reads the system clock
+    Clock,
+    /// 读取授权目录中的文件 / reads files in approved folders
+    ReadFiles,
+    /// 外部服务器提供（MCP）/ provided by an external server (MCP)
+    External,
+    /// 执行本地程序 / runs local programs
+    Shell,
+    /// 访问网络 / reaches the network
+    Network,
+}
+
+/// 工具执行上下文
+/// Context handed to a tool handler.
+#[derive(Clone)]
+pub struct ToolContext {
+    pub app: AppHandle,
+    /// 发起请求的窗口，用于推送中间结果 / requesting window, for streaming partial results
+    pub window: Window,
+    pub request_id: String,
+    pub call_id: String,
+    /// 所属请求的取消令牌 / cancellation token of the owning request
+    pub cancel: CancellationToken,
+}
+
+/// 后端工具处理器
+/// A backend tool handler.
+#[async_trait]
+pub trait ToolHandler: Send + Sync {
+    /// 工具名（模型可见）/ tool name as seen by the model
+    fn name(&self) -> &str;
+    /// 工具说明 / tool description
+    fn description(&self) -> &str;
+    /// 权限范围 / permission scope
+    fn scope(&self) -> ToolScope;
+    /// 是否每次都需用户确认（忽略“始终允许”）/ whether every call needs confirmation (ignores "always allow")
+    fn always_confirm(&self) -> bool {
+        false
+    }
+    /// 参数 JSON Schema / JSON schema of the arguments
+    fn parameters(&self) -> Value;
+    /// 执行工具 / run the tool
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String>;
+}
+
+/// 生成 OpenAI 格式的工具定义
+/// Build an OpenAI-style tool definition.
+pub fn tool_definition(handler: &dyn ToolHandler) -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": handler.name(),
+            "description": handler.description(),
+            "parameters": handler.parameters(),
+        }
+    })
+}
+
+/// 已注册的工具
+/// Registered tools.
+#[derive(Default)]
+pub struct ToolRegistry {
+    handlers: RwLock<HashMap<String, Arc<dyn ToolHandler>>>,
+}
+
+impl ToolRegistry {
+    pub fn register(&self, handler: Arc<dyn ToolHandler>) {
+        self.handlers
+            .write()
+            .unwrap()
+            .insert(handler.name().to_string(), handler);
+    }
+
+    /// 移除名称以指定前缀开头的工具
+    /// Remove the tools whose name starts with `prefix`.
+    pub fn unregister_prefix(&self, prefix: &str) {
+        self.handlers
+            .write()
+            .unwrap()
+            .retain(|name, _| !name.starts_with(prefix));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ToolHandler>> {
+        self.handlers.read().unwrap().get(name).cloned()
+    }
+
+    /// 按名称取工具定义；`*` 表示全部
+    /// Tool definitions by name; `*` selects all.
+    pub fn definitions(&self, names: &[String]) -> Vec<Value> {
+        let handlers = self.handlers.read().unwrap();
+        let mut defs: Vec<(String, Value)> = handlers
+            .values()
+            .filter(|h| names.iter().any(|n| n == "*" || n == h.name()))
+            .map(|h| (h.name().to_string(), tool_definition(h.as_ref())))
+            .collect();
+        defs.sort_by(|a, b| a.0.cmp(&b.0));
+        defs.into_iter().map(|(_, d)| d).collect()
+    }
+
+    /// 所有工具的摘要，按名称排序
+    /// Summaries of all tools, sorted by name.
+    pub fn summaries(&self) -> Vec<ToolSummary> {
+        let mut list: Vec<ToolSummary> = self
+            .handlers
+            .read()
+            .unwrap()
+            .values()
+            .map(|h| ToolSummary {
+                name: h.name().to_string(),
+                description: h.description().to_string(),
+                scope: h.scope(),
+                parameters: h.parameters(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+}
+
+/// 注册内置的本地工具
+/// Register the built-in local tools.
+pub fn register_builtin_tools(registry: &ToolRegistry) {
+    registry.register(Arc::new(calc::CalculatorTool));
+    registry.register(Arc::new(clock::ClockTool));
+    registry.register(Arc::new(fs::ReadFileTool));
+    registry.register(Arc::new(fs::ListDirectoryTool));
+    registry.register(Arc::new(fs::SearchFileTool));
+    registry.register(Arc::new(shell::ShellTool));
+    registry.register(Arc::new(web::FetchUrlTool));
+}
+
+/// 前端可见的工具摘要
+/// Tool summary exposed to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolSummary {
+    pub name: String,
+    pub description: String,
+    pub scope: ToolScope,
+    pub parameters: Value,
+}
+
+/// 列出可用的后端工具
+/// List the available backend tools.
+#[tauri::command]
+pub fn list_tools(registry: tauri::State<'_, ToolRegistry>) -> Vec<ToolSummary> {
+    registry.summaries()
+}
+
+/// 一次完整的工具调用
+/// A fully assembled tool call.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// 原始参数字符串 / raw argument string
+    pub arguments: String,
+}
+
+impl ToolCall {
+    /// 转为 assistant 消息中的 `tool_calls` 项
+    /// Convert into an entry of the assistant message `tool_calls`.
+    pub fn to_message_part(&self) -> Value {
+        json!({
+            "id": self.id,
+            "type": "function",
+            "function": { "name": self.name, "arguments": self.arguments },
+        })
+    }
+
+    /// 解析参数；空字符串视为 `{}`
+    /// Parse the arguments; an empty string means `{}`.
+    pub fn parsed_arguments(&self) -> Result<Value, String> {
+        if self.arguments.trim().is_empty() {
+            return Ok(json!({}));
+        }
+        serde_json::from_str(&self.arguments)
+            .map_err(|e| format!("Invalid tool arguments for {}: {}", self.name, e))
+    }
+}
+
+/// 累积流式 `delta.tool_calls` 片段
+/// Accumulates streamed `delta.tool_calls` fragments.
+#[derive(Default)]
+pub struct ToolCallAccumulator {
+    calls: BTreeMap<u64, ToolCall>,
+    /// 最近写入的调用序号 / index of the call written last
+    current: Option<u64>,
+}
+
+impl ToolCallAccumulator {
+    /// 读取一个流式块中的工具调用片段
+    /// Feed the tool-call fragments of one stream chunk.
+    pub fn feed(&mut self, chunk: &Value) {
+        let Some(parts) = chunk
+            .pointer("/choices/0/delta/tool_calls")
+            .and_then(|t| t.as_array())
+        else {
+            return;
+        };
+        for part in parts {
+            let id = part
+                .get("id")
+                .and_then(|i| i.as_str())
+                .filter(|i| !i.is_empty());
+            // 缺少序号时续写当前调用，只有带 ID 的片段才开始新调用
+            // Without an index, continue the current call; only a fragment with an id opens a new one
+            let index = match (part.get("index").and_then(|i| i.as_u64()), self.current) {
+                (Some(index), _) => index,
+                (None, Some(current)) if id.is_none_or(|id| self.calls[&current].id == id) => {
+                    current
+                }
+                _ => self.calls.keys().next_back().map_or(0, |last| last + 1),
+            };
+            self.current = Some(index);
+            let call = self.calls.entry(index).or_insert_with(|| ToolCall {
+                id: String::new(),
+                name: String::new(),
+                arguments: String::new(),
+            });
+            if let Some(id) = id {
+                call.id = id.to_string();
+            }
+            if let Some(function) = part.get("function") {
+                // 有的供应商在每个片段里重复名称，不做拼接
+                // Some providers repeat the name in every fragment; never concatenate
+                if let Some(name) = function.get("name").and_then(|n| n.as_str()) {
+                    if call.name.is_empty() {
+                        call.name = name.to_string();
+                    }
+                }
+                if let Some(args) = function.get("arguments").and_then(|a| a.as_str()) {
+                    call.arguments.push_str(args);
+                }
+            }
+        }
+    }
+
+    /// 取出所有完整的调用，缺失的 ID 会自动补全
+    /// Take all assembled calls; missing ids are filled in.
+    pub fn take(&mut self, request_id: &str) -> Vec<ToolCall> {
+        self.current = None;
+        std::mem::take(&mut self.calls)
+            .into_iter()
+            .filter(|(_, c)| !c.name.is_empty())
+            .map(|(index, mut c)| {
+                if c.id.is_empty() {
+                    c.id = format!("call-{}-{}", request_id, index);
+                }
+                c
+            })
+            .collect()
+    }
+}
+
+/// 执行一次工具调用（需用户批准），并发送 `tool-call` / `tool-result` 事件
+/// Run one tool call (after user approval), emitting `tool-call` / `tool-result` events.
+///
+/// 返回写回模型的 `tool` 消息；请求被取消时工具随之中止。
+/// Returns the `tool` message to send back to the model; the tool is aborted when the request
+/// is cancelled.
+pub async fn run_tool_call(
+    app: &AppHandle,
+    window: &Window,
+    request_id: &str,
+    cancel: &CancellationToken,
+    call: &ToolCall,
+) -> Value {
+    let arguments = call.parsed_arguments();
+    let _ = window.emit(
+        "tool-call",
+        json!({
+            "request_id": request_id,
+            "call_id": call.id,
+            "name": call.name,
+            "arguments": arguments.as_ref().cloned().unwrap_or_else(|_| Value::String(call.arguments.clone())),
+        }),
+    );
+
+    let ctx = ToolContext {
+        app: app.clone(),
+        window: window.clone(),
+        request_id: request_id.to_string(),
+        call_id: call.id.clone(),
+        cancel: cancel.clone(),
+    };
+    let result = match arguments {
+        Ok(args) => match authorize(&ctx, &call.name, &args).await {
+            // 先轮询工具，让其自行清理（如结束子进程）/ poll the tool first so it can clean up (e.g. kill its child)
+            Ok(()) => tokio::select! {
+                biased;
+                result = dispatch(&ctx, &call.name, args) => result,
+                _ = cancel.cancelled() => Err("The request was cancelled".into()),
+            },
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match &result {
+        Ok(_) => info!(request_id = %request_id, tool = %call.name, "tool call succeeded"),
+        Err(e) => warn!(request_id = %request_id, tool = %call.name, "tool call failed: {}", e),
+    }
+    let _ = window.emit(
+        "tool-result",
+        json!({
+            "request_id": request_id,
+            "call_id": call.id,
+            "name": call.name,
+            "ok": result.is_ok(),
+            "result": result.as_ref().ok(),
+            "error": result.as_ref().err(),
+        }),
+    );
+
+    let content = match result {
+        Ok(Value::String(s)) => s,
+        Ok(value) => value.to_string(),
+        Err(e) => json!({ "error": e }).to_string(),
+    };
+    json!({ "role": "tool", "tool_call_id": call.id, "content": content })
+}
+
+/// 执行前的用户审批；未注册的工具交给 `dispatch` 报错
+/// User approval before running; unknown tools are left for `dispatch` to reject.
+async fn authorize(ctx: &ToolContext, name: &str, args: &Value) -> Result<(), String> {
+    let Some(handler) = ctx.app.state::<ToolRegistry>().get(name) else {
+        return Ok(());
+    };
+    approval::request_approval(ctx, name, handler.scope(), handler.always_confirm(), args).await
+}
+
+/// 按名称分发到已注册的处理器
+/// Dispatch to the registered handler by name.
+pub async fn dispatch(ctx: &ToolContext, name: &str, args: Value) -> Result<Value, String> {
+    debug!(request_id = %ctx.request_id, call_id = %ctx.call_id, tool = %name, "dispatch tool call");
+    let handler = ctx
+        .app
+        .state::<ToolRegistry>()
+        .get(name)
+        .ok_or_else(|| format!("Tool not available: {}", name))?;
+    handler.call(ctx, args).await
+}
+
+/// 把已注册工具的定义合并进请求体的 `tools`
+/// Merge registered tool definitions into the body's `tools`.
+pub fn attach_tools(app: &AppHandle, body: &mut Value, names: &[String]) {
+    let defs = app.state::<ToolRegistry>().definitions(names);
+    if defs.is_empty() {
+        return;
+    }
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    let tools = obj.entry("tools").or_insert_with(|| json!([]));
+    if let Some(list) = tools.as_array_mut() {
+        for def in defs {
+            let name = def.pointer("/function/name").cloned();
+            if !list
+                .iter()
+                .any(|t| t.pointer("/function/name") == name.as_ref())
+            {
+                list.push(def);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(acc: &mut ToolCallAccumulator, parts: Value) {
+        acc.feed(&json!({ "choices": [{ "delta": { "tool_calls": parts } }] }));
+    }
+
+    #[test]
+    fn arguments_join_across_fragments() {
+        let mut acc = ToolCallAccumulator::default();
+        feed(
+            &mut acc,
+            json!([{ "index": 0, "id": "a", "function": { "name": "calculator", "arguments": "{\"expr" } }]),
+        );
+        feed(
+            &mut acc,
+            json!([{ "index": 0, "function": { "arguments": "ession\":" } }]),
+        );
+        feed(
+            &mut acc,
+            json!([{ "index": 0, "function": { "arguments": "\"1+1\"}" } }]),
+        );
+        let calls = acc.take("r");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "calculator");
+        assert_eq!(
+            calls[0].parsed_arguments().unwrap(),
+            json!({ "expression": "1+1" })
+        );
+    }
+
+    #[test]
+    fn interleaved_indexes() {
+        let mut acc = ToolCallAccumulator::default();
+        feed(
+            &mut acc,
+            json!([
+                { "index": 1, "id": "b", "function": { "name": "second", "arguments": "{\"n\":" } },
+                { "index": 0, "id": "a", "function": { "name": "first", "arguments": "{" } },
+            ]),
+        );
+        feed(
+            &mut acc,
+            json!([
+                { "index": 0, "function": { "arguments": "}" } },
+                { "index": 1, "function": { "arguments": "2}" } },
+            ]),
+        );
+        let calls = acc.take("r");
+        let names: Vec<&str> = calls.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+        assert_eq!(calls[0].arguments, "{}");
+        assert_eq!(calls[1].arguments, "{\"n\":2}");
+    }
+
+    #[test]
+    fn missing_index_continues_the_current_call() {
+        let mut acc = ToolCallAccumulator::default();
+        feed(
+            &mut acc,
+            json!([{ "id": "a", "function": { "name": "first", "arguments": "{\"a\"" } }]),
+        );
+        feed(&mut acc, json!([{ "function": { "arguments": ":1}" } }]));
+        feed(
+            &mut acc,
+            json!([{ "id": "b", "function": { "name": "second", "arguments": "{}" } }]),
+        );
+        feed(
+            &mut acc,
+            json!([{ "id": "b", "function": { "arguments": "" } }]),
+        );
+        let calls = acc.take("r");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            (calls[0].id.as_str(), calls[0].arguments.as_str()),
+            ("a", "{\"a\":1}")
+        );
+        assert_eq!(
+            (calls[1].id.as_str(), calls[1].arguments.as_str()),
+            ("b", "{}")
+        );
+    }
+
+    #[test]
+    fn repeated_names_are_not_doubled() {
+        let mut acc = ToolCallAccumulator::default();
+        for args in ["{", "}"] {
+            feed(
+                &mut acc,
+                json!([{ "index": 0, "function": { "name": "current_time", "arguments": args } }]),
+            );
+        }
+        let calls = acc.take("r");
+        assert_eq!(calls[0].name, "current_time");
+        assert_eq!(calls[0].arguments, "{}");
+    }
+
+    #[test]
+    fn take_fills_ids_and_drops_nameless_calls() {
+        let mut acc = ToolCallAccumulator::default();
+        feed(
+            &mut acc,
+            json!([
+                { "index": 0, "function": { "name": "current_time" } },
+                { "index": 1, "function": { "arguments": "{}" } },
+                { "index": 2, "id": "given", "function": { "name": "calculator" } },
+            ]),
+        );
+        let calls = acc.take("req-1");
+        let ids: Vec<&str> = calls.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["call-req-1-0", "given"]);
+        assert!(acc.take("req-1").is_empty());
+    }
+}
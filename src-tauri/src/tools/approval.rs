@@ -0,0 +1,352 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use super::{ToolContext, ToolScope};
+use crate::settings::{read_setting, write_setting};
+
+/// 永久允许的工具在 store 中的字段名
+/// Store field holding the always-allowed tools.
+pub const ALWAYS_ALLOW_FIELD: &str = "tool_always_allow";
+
+/// 等待用户确认的超时，超时视为拒绝
+/// How long to wait for the user; a timeout counts as a denial.
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 批准的记忆范围
+/// How long an approval is remembered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalRemember {
+    /// 仅本次 / this call only
+    #[default]
+    Once,
+    /// 本次运行期间 / until the app exits
+    Session,
+    /// 写入设置，永久允许 / persisted in settings
+    Always,
+}
+
+enum Decision {
+    Approve(ApprovalRemember),
+    Deny(Option<String>),
+    Cancelled,
+}
+
+struct PendingApproval {
+    tool: String,
+    sender: oneshot::Sender<Decision>,
+}
+
+/// 待审批项的键：调用 ID 由模型生成，只在单次回答内唯一，需与请求 ID 组合
+/// Key of a pending approval: call ids come from the model and are only unique within one
+/// response, so they are paired with the request id.
+type ApprovalKey = (String, String);
+
+fn approval_key(request_id: &str, call_id: &str) -> ApprovalKey {
+    (request_id.to_string(), call_id.to_string())
+}
+
+/// 工具调用审批状态
+/// Tool-call approval state.
+#[derive(Default)]
+pub struct ApprovalManager {
+    /// (请求 ID, 调用 ID) → 等待中的审批 / (request id, call id) → pending approval
+    pending: Mutex<HashMap<ApprovalKey, PendingApproval>>,
+    /// 本次运行允许的工具 / tools allowed for this session
+    session_allowed: Mutex<HashSet<String>>,
+    /// 审批期间被取消的请求 / requests cancelled while awaiting approval
+    cancelled: Mutex<HashSet<String>>,
+}
+
+impl ApprovalManager {
+    fn is_allowed(&self, app: &AppHandle, tool: &str) -> bool {
+        self.session_allowed.lock().unwrap().contains(tool)
+            || always_allowed(app).iter().any(|t| t == tool)
+    }
+
+    fn resolve(&self, request_id: &str, call_id: &str, decision: Decision) -> Result<(), String> {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&approval_key(request_id, call_id))
+            .ok_or_else(|| {
+                format!(
+                    "No pending approval for {} in request {}",
+                    call_id, request_id
+                )
+            })?;
+        let _ = pending.sender.send(decision);
+        Ok(())
+    }
+
+    /// 取消某请求的所有待审批调用；返回是否存在待审批项
+    /// Cancel every pending approval of a request; returns whether any existed.
+    pub fn cancel_request(&self, request_id: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        let ids: Vec<ApprovalKey> = pending
+            .keys()
+            .filter(|(request, _)| request == request_id)
+            .cloned()
+            .collect();
+        if ids.is_empty() {
+            return false;
+        }
+        for id in ids {
+            if let Some(p) = pending.remove(&id) {
+                let _ = p.sender.send(Decision::Cancelled);
+            }
+        }
+        self.cancelled
+            .lock()
+            .unwrap()
+            .insert(request_id.to_string());
+        true
+    }
+
+    /// 请求是否在审批中被取消（读取后清除）
+    /// Whether the request was cancelled during approval (cleared on read).
+    pub fn take_cancelled(&self, request_id: &str) -> bool {
+        self.cancelled.lock().unwrap().remove(request_id)
+    }
+}
+
+/// 读取永久允许的工具
+/// Load the always-allowed tools.
+pub fn always_allowed(app: &AppHandle) -> Vec<String> {
+    read_setting(app, ALWAYS_ALLOW_FIELD).unwrap_or_default()
+}
+
+/// 执行前请求用户批准；已允许的工具直接通过
+/// Ask the user before running a tool; already-allowed tools pass through.
+///
+/// 发送 `tool-approval-request`，等待 `approve_tool_call` / `deny_tool_call`，
+/// 结束时发送 `tool-approval-resolved`。超时或取消均视为拒绝。
+/// Emits `tool-approval-request`, waits for `approve_tool_call` / `deny_tool_call`
+/// and emits `tool-approval-resolved`. A timeout or cancellation counts as a denial.
+pub async fn request_approval(
+    ctx: &ToolContext,
+    tool: &str,
+    scope: ToolScope,
+    always_confirm: bool,
+    arguments: &Value,
+) -> Result<(), String> {
+    let (app, window) = (&ctx.app, &ctx.window);
+    let (request_id, call_id) = (ctx.request_id.as_str(), ctx.call_id.as_str());
+    let manager = app.state::<ApprovalManager>();
+    if manager.cancelled.lock().unwrap().contains(request_id) {
+        return Err("The request was cancelled".into());
+    }
+    if !always_confirm && manager.is_allowed(app, tool) {
+        return Ok(());
+    }
+
+    let (tx, rx) = oneshot::channel();
+    manager.pending.lock().unwrap().insert(
+        approval_key(request_id, call_id),
+        PendingApproval {
+            tool: tool.to_string(),
+            sender: tx,
+        },
+    );
+    let _ = window.emit(
+        "tool-approval-request",
+        json!({
+            "request_id": request_id,
+            "call_id": call_id,
+            "name": tool,
+            "scope": scope,
+            "arguments": arguments,
+            "timeout_ms": APPROVAL_TIMEOUT.as_millis() as u64,
+        }),
+    );
+
+    let decision = match tokio::time::timeout(APPROVAL_TIMEOUT, rx).await {
+        Ok(Ok(decision)) => decision,
+        Ok(Err(_)) => Decision::Cancelled,
+        Err(_) => {
+            manager
+                .pending
+                .lock()
+                .unwrap()
+                .remove(&approval_key(request_id, call_id));
+            Decision::Deny(Some("Approval timed out".into()))
+        }
+    };
+
+    let result = match decision {
+        Decision::Approve(remember) => {
+            // 必须逐次确认的工具不记忆 / tools that always confirm are never remembered
+            let remember = if always_confirm {
+                ApprovalRemember::Once
+            } else {
+                remember
+            };
+            match remember {
+                ApprovalRemember::Once => {}
+                ApprovalRemember::Session => {
+                    manager
+                        .session_allowed
+                        .lock()
+                        .unwrap()
+                        .insert(tool.to_string());
+                }
+                ApprovalRemember::Always => {
+                    let mut tools = always_allowed(app);
+                    if !tools.iter().any(|t| t == tool) {
+                        tools.push(tool.to_string());
+                        if let Err(e) = write_setting(app, ALWAYS_ALLOW_FIELD, &tools) {
+                            warn!("failed to persist tool approval: {}", e);
+                        }
+                    }
+                }
+            }
+            info!(request_id = %request_id, tool = %tool, "tool call approved");
+            Ok(())
+        }
+        Decision::Deny(reason) => Err(match reason {
+            Some(reason) => format!("The user denied this tool call: {}", reason),
+            None => "The user denied this tool call".to_string(),
+        }),
+        Decision::Cancelled => Err("The request was cancelled".into()),
+    };
+
+    let _ = window.emit(
+        "tool-approval-resolved",
+        json!({
+            "request_id": request_id,
+            "call_id": call_id,
+            "approved": result.is_ok(),
+            "error": result.as_ref().err(),
+        }),
+    );
+    result
+}
+
+/// 批准一次工具调用
+/// Approve a tool call.
+#[command]
+pub fn approve_tool_call(
+    manager: State<'_, ApprovalManager>,
+    request_id: String,
+    call_id: String,
+    remember: Option<ApprovalRemember>,
+) -> Result<(), String> {
+    manager.resolve(
+        &request_id,
+        &call_id,
+        Decision::Approve(remember.unwrap_or_default()),
+    )
+}
+
+/// 拒绝一次工具调用，可附原因（会回传给模型）
+/// Deny a tool call, optionally with a reason (passed back to the model).
+#[command]
+pub fn deny_tool_call(
+    manager: State<'_, ApprovalManager>,
+    request_id: String,
+    call_id: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    manager.resolve(&request_id, &call_id, Decision::Deny(reason))
+}
+
+/// 列出待审批的调用 ID 与工具名（用于窗口重新加载后恢复）
+/// List pending approvals as call id and tool (to restore after a reload).
+#[command]
+pub fn list_pending_approvals(manager: State<'_, ApprovalManager>) -> Vec<Value> {
+    manager
+        .pending
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((request_id, call_id), p)| {
+            json!({ "call_id": call_id, "request_id": request_id, "name": p.tool })
+        })
+        .collect()
+}
+
+/// 列出已允许的工具（永久与本次运行）
+/// List the allowed tools (persisted and session).
+#[command]
+pub fn list_allowed_tools(app: AppHandle, manager: State<'_, ApprovalManager>) -> Value {
+    let mut session: Vec<String> = manager
+        .session_allowed
+        .lock()
+        .unwrap()
+        .iter()
+        .cloned()
+        .collect();
+    session.sort();
+    json!({ "always": always_allowed(&app), "session": session })
+}
+
+/// 撤销工具的允许（永久与本次运行）
+/// Revoke a tool's allowance (persisted and session).
+#[command]
+pub fn revoke_tool_approval(
+    app: AppHandle,
+    manager: State<'_, ApprovalManager>,
+    tool: String,
+) -> Result<(), String> {
+    manager.session_allowed.lock().unwrap().remove(&tool);
+    let mut tools = always_allowed(&app);
+    tools.retain(|t| *t != tool);
+    write_setting(&app, ALWAYS_ALLOW_FIELD, &tools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait(
+        manager: &ApprovalManager,
+        request_id: &str,
+        call_id: &str,
+    ) -> oneshot::Receiver<Decision> {
+        let (tx, rx) = oneshot::channel();
+        manager.pending.lock().unwrap().insert(
+            approval_key(request_id, call_id),
+            PendingApproval {
+                tool: "calculator".into(),
+                sender: tx,
+            },
+        );
+        rx
+    }
+
+    #[test]
+    fn same_call_id_in_two_requests() {
+        let manager = ApprovalManager::default();
+        let mut first = wait(&manager, "req-1", "call-0");
+        let mut second = wait(&manager, "req-2", "call-0");
+        manager
+            .resolve("req-2", "call-0", Decision::Deny(None))
+            .unwrap();
+        assert!(matches!(second.try_recv(), Ok(Decision::Deny(None))));
+        assert!(first.try_recv().is_err());
+        assert!(manager
+            .resolve("req-3", "call-0", Decision::Deny(None))
+            .is_err());
+    }
+
+    #[test]
+    fn cancel_only_touches_its_request() {
+        let manager = ApprovalManager::default();
+        let mut first = wait(&manager, "req-1", "call-0");
+        let _second = wait(&manager, "req-2", "call-0");
+        assert!(manager.cancel_request("req-1"));
+        assert!(matches!(first.try_recv(), Ok(Decision::Cancelled)));
+        assert_eq!(manager.pending.lock().unwrap().len(), 1);
+        assert!(manager.take_cancelled("req-1"));
+        assert!(!manager.take_cancelled("req-1"));
+        assert!(!manager.cancel_request("req-3"));
+    }
+}
@@ -0,0 +1,359 @@
+use async_trait::async_trait;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+use serde_json::{json, Value};
+
+use super::{ToolContext, ToolHandler, ToolScope};
+
+/// 表达式最大长度
+/// Max expression length.
+const MAX_EXPRESSION_LEN: usize = 1000;
+/// 指数上限，防止结果过大
+/// Exponent cap, keeps results bounded.
+const MAX_EXPONENT: i64 = 4096;
+/// 中间结果的位数上限，防止 `(9^4096)^4096` 之类耗尽内存
+/// Bit-length cap on intermediate results, so `(9^4096)^4096` can't exhaust memory.
+const MAX_RESULT_BITS: u64 = 65_536;
+/// 小数展开的位数
+/// Digits in the decimal rendering.
+const DECIMAL_DIGITS: usize = 30;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(BigRational),
+    Op(char),
+    LParen,
+    RParen,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\n' | '_' => i += 1,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_')
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+                tokens.push(Token::Num(parse_decimal(&text)?));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                // `**` 视为乘方 / `**` means power
+                if c == '*' && chars.get(i + 1) == Some(&'*') {
+                    tokens.push(Token::Op('^'));
+                    i += 2;
+                } else {
+                    tokens.push(Token::Op(c));
+                    i += 1;
+                }
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            other => return Err(format!("Unexpected character '{}'", other)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// 有理数的大小：分子与分母中较长的位数
+/// Size of a rational: the bit length of the longer of numerator and denominator.
+fn size_bits(value: &BigRational) -> u64 {
+    value.numer().bits().max(value.denom().bits())
+}
+
+/// 在计算前检查预估的结果位数
+/// Check the estimated result size before computing it.
+fn check_size(bits: u64) -> Result<(), String> {
+    if bits > MAX_RESULT_BITS {
+        return Err(format!("Result would exceed {} bits", MAX_RESULT_BITS));
+    }
+    Ok(())
+}
+
+/// 精确解析十进制数（`0.1` → 1/10）
+/// Parse a decimal exactly (`0.1` → 1/10).
+fn parse_decimal(text: &str) -> Result<BigRational, String> {
+    let (int_part, frac_part) = match text.split_once('.') {
+        Some((i, f)) => (i, f),
+        None => (text, ""),
+    };
+    if frac_part.contains('.') || (int_part.is_empty() && frac_part.is_empty()) {
+        return Err(format!("Invalid number '{}'", text));
+    }
+    let digits = format!("{}{}", int_part, frac_part);
+    let numer: BigInt = digits
+        .parse()
+        .map_err(|_| format!("Invalid number '{}'", text))?;
+    let denom = BigInt::from(10u32).pow(frac_part.len() as u32);
+    Ok(BigRational::new(numer, denom))
+}
+
+/// 递归下降解析器
+/// Recursive-descent parser.
+///
+/// expr   := term (('+' | '-') term)*
+/// term   := unary (('*' | '/' | '%') unary)*
+/// unary  := ('+' | '-') unary | power
+/// power  := atom ('^' unary)?
+/// atom   := number | '(' expr ')'
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expr(&mut self) -> Result<BigRational, String> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            check_size(size_bits(&value) + size_bits(&rhs))?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<BigRational, String> {
+        let mut value = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/' | '%'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            if op != '%' {
+                check_size(size_bits(&value) + size_bits(&rhs))?;
+            }
+            value = match op {
+                '*' => value * rhs,
+                _ if rhs.is_zero() => return Err("Division by zero".into()),
+                '/' => value / rhs,
+                _ => {
+                    // 取余：a - b * trunc(a / b) / remainder: a - b * trunc(a / b)
+                    let quotient = (&value / &rhs).trunc();
+                    value - rhs * quotient
+                }
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<BigRational, String> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                Ok(-self.unary()?)
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<BigRational, String> {
+        let base = self.atom()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            let exponent = self.unary()?;
+            if !exponent.is_integer() {
+                return Err("Only integer exponents are supported".into());
+            }
+            let exp = exponent
+                .to_integer()
+                .to_i64()
+                .filter(|e| e.abs() <= MAX_EXPONENT)
+                .ok_or_else(|| format!("Exponent must be within ±{}", MAX_EXPONENT))?;
+            if exp < 0 && base.is_zero() {
+                return Err("Division by zero".into());
+            }
+            check_size(size_bits(&base).saturating_mul(exp.unsigned_abs()))?;
+            return Ok(base.pow(exp as i32));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<BigRational, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(n),
+            Some(Token::LParen) => {
+                let value = self.expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err("Missing ')'".into()),
+                }
+            }
+            Some(other) => Err(format!("Unexpected token {:?}", other)),
+            None => Err("Unexpected end of expression".into()),
+        }
+    }
+}
+
+/// 精确计算算术表达式
+/// Evaluate an arithmetic expression exactly.
+pub fn evaluate(expr: &str) -> Result<BigRational, String> {
+    if expr.len() > MAX_EXPRESSION_LEN {
+        return Err(format!(
+            "Expression longer than {} characters",
+            MAX_EXPRESSION_LEN
+        ));
+    }
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+    };
+    let value = parser.expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err("Unexpected trailing input".into());
+    }
+    Ok(value)
+}
+
+/// 将有理数展开为十进制字符串（截断到指定位数）
+/// Render a rational as a decimal string (truncated to `digits`).
+pub fn to_decimal(value: &BigRational, digits: usize) -> (String, bool) {
+    let negative = value.is_negative();
+    let abs = value.abs();
+    let int_part = abs.numer() / abs.denom();
+    let mut remainder = abs.numer() % abs.denom();
+    let mut frac = String::new();
+    let ten = BigInt::from(10u32);
+    while !remainder.is_zero() && frac.len() < digits {
+        remainder *= &ten;
+        frac.push_str(&(&remainder / abs.denom()).to_string());
+        remainder %= abs.denom();
+    }
+    let exact = remainder.is_zero();
+    let sign = if negative { "-" } else { "" };
+    let text = if frac.is_empty() {
+        format!("{}{}", sign, int_part)
+    } else {
+        format!("{}{}.{}", sign, int_part, frac)
+    };
+    (text, exact)
+}
+
+/// 计算器工具
+/// Calculator tool.
+pub struct CalculatorTool;
+
+#[async_trait]
+impl ToolHandler for CalculatorTool {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+
+    fn description(&self) -> &str {
+        "Evaluate an arithmetic expression exactly using rational numbers. Supports + - * / % ^ (integer exponents) and parentheses."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::Compute
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "expression": { "type": "string", "description": "Expression such as (1/3 + 0.25) * 2^10" }
+            },
+            "required": ["expression"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn call(&self, _ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        let expr = args
+            .get("expression")
+            .and_then(|e| e.as_str())
+            .ok_or("Missing 'expression'")?
+            .to_string();
+        // 大数运算可能较慢，不占用异步执行器 / big-number work can be slow; keep it off the async executor
+        tauri::async_runtime::spawn_blocking(move || {
+            let value = evaluate(&expr)?;
+            let (decimal, exact) = to_decimal(&value, DECIMAL_DIGITS);
+            Ok(json!({
+                "expression": expr,
+                "fraction": value.to_string(),
+                "decimal": decimal,
+                "decimal_is_exact": exact,
+                "is_integer": value.is_integer(),
+            }))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> String {
+        evaluate(expr).unwrap().to_string()
+    }
+
+    #[test]
+    fn decimals_are_exact() {
+        assert_eq!(eval("0.1 + 0.2"), "3/10");
+        assert_eq!(eval("(1/3 + 0.25) * 2^10"), "1792/3");
+    }
+
+    #[test]
+    fn precedence_and_unary_minus() {
+        assert_eq!(eval("2 + 3 * 4"), "14");
+        assert_eq!(eval("-2^2"), "-4");
+        assert_eq!(eval("2^-2"), "1/4");
+        assert_eq!(eval("7 % 3"), "1");
+        assert_eq!(eval("-7 % 3"), "-1");
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("0^-1").is_err());
+        assert!(evaluate("2^0.5").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 2").is_err());
+        assert!(evaluate(&"1+".repeat(MAX_EXPRESSION_LEN)).is_err());
+    }
+
+    #[test]
+    fn results_are_bounded() {
+        assert!(evaluate("2^4096").is_ok());
+        assert!(evaluate("2^5000").is_err());
+        assert!(evaluate("((9^4096)^4096)^4096").is_err());
+        assert!(evaluate("(2^4000)^20").is_err());
+        assert!(evaluate("(1/3^4000)^20").is_err());
+    }
+
+    #[test]
+    fn decimal_rendering() {
+        let third = evaluate("1/3").unwrap();
+        assert_eq!(to_decimal(&third, 5), ("0.33333".into(), false));
+        let value = evaluate("-5/4").unwrap();
+        assert_eq!(to_decimal(&value, 5), ("-1.25".into(), true));
+    }
+}
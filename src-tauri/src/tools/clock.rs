@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde_json::{json, Value};
+
+use super::{ToolContext, ToolHandler, ToolScope};
+
+/// 描述某一时刻（本地时间、时区、UTC 偏移与 UTC 时间）
+/// Describe an instant (local time, timezone, UTC offset and UTC time).
+fn describe<Tz: TimeZone>(local: DateTime<Tz>, timezone: Option<String>) -> Value
+where
+    Tz::Offset: std::fmt::Display,
+{
+    json!({
+        "local": local.to_rfc3339(),
+        "date": local.format("%Y-%m-%d").to_string(),
+        "time": local.format("%H:%M:%S").to_string(),
+        "weekday": local.format("%A").to_string(),
+        "timezone": timezone,
+        "utc_offset": local.format("%:z").to_string(),
+        "utc": local.with_timezone(&Utc).to_rfc3339(),
+        "unix_ms": local.timestamp_millis(),
+    })
+}
+
+/// 当前日期时间与时区工具
+/// Current date, time and timezone tool.
+pub struct ClockTool;
+
+#[async_trait]
+impl ToolHandler for ClockTool {
+    fn name(&self) -> &str {
+        "current_time"
+    }
+
+    fn description(&self) -> &str {
+        "Get the current local date and time, the local timezone and UTC offset, and the current UTC time."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::Clock
+    }
+
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {}, "additionalProperties": false })
+    }
+
+    async fn call(&self, _ctx: &ToolContext, _args: Value) -> Result<Value, String> {
+        let timezone = iana_time_zone::get_timezone().ok();
+        Ok(describe(Local::now(), timezone))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn describes_local_and_utc_time() {
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let local = offset.with_ymd_and_hms(2024, 2, 29, 1, 30, 0).unwrap();
+        let info = describe(local, Some("Asia/Shanghai".into()));
+        assert_eq!(info["date"], "2024-02-29");
+        assert_eq!(info["time"], "01:30:00");
+        assert_eq!(info["weekday"], "Thursday");
+        assert_eq!(info["utc_offset"], "+08:00");
+        assert_eq!(info["timezone"], "Asia/Shanghai");
+        assert_eq!(info["utc"], "2024-02-28T17:30:00+00:00");
+        assert_eq!(info["unix_ms"], 1_709_141_400_000i64);
+    }
+}
@@ -0,0 +1,373 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use regex::RegexBuilder;
+use serde_json::{json, Value};
+use tauri::{command, AppHandle};
+
+use super::{ToolContext, ToolHandler, ToolScope};
+use crate::settings::{read_setting, write_setting};
+
+/// 用户授权目录在 store 中的字段名
+/// Store field holding the user-approved folders.
+pub const APPROVED_FOLDERS_FIELD: &str = "approved_folders";
+
+/// 单次读取的最大字节数
+/// Max bytes returned by one read.
+const MAX_READ_BYTES: u64 = 256 * 1024;
+/// 可搜索的最大文件大小
+/// Max size of a searchable file.
+const MAX_SEARCH_BYTES: u64 = 8 * 1024 * 1024;
+/// 目录列表的最大条目数
+/// Max entries in a directory listing.
+const MAX_LIST_ENTRIES: usize = 1000;
+/// 搜索结果默认条数上限
+/// Default cap of search matches.
+const DEFAULT_MAX_MATCHES: usize = 100;
+/// 搜索结果中单行的最大长度
+/// Max line length in search results.
+const MAX_LINE_CHARS: usize = 500;
+
+/// 读取用户授权目录
+/// Load the user-approved folders.
+pub fn approved_folders(app: &AppHandle) -> Vec<String> {
+    read_setting(app, APPROVED_FOLDERS_FIELD).unwrap_or_default()
+}
+
+/// 解析路径并确认其位于授权目录内（会解析符号链接）
+/// Resolve a path and make sure it lies inside an approved folder (symlinks resolved).
+pub fn resolve_in_approved(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let canonical = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot access {}: {}", path, e))?;
+    if is_within(&canonical, &approved_folders(app)) {
+        Ok(canonical)
+    } else {
+        Err(format!("{} is outside the approved folders", path))
+    }
+}
+
+/// 规范化路径是否位于某个授权目录内
+/// Whether a canonical path lies inside one of the folders.
+pub(super) fn is_within(canonical: &Path, folders: &[String]) -> bool {
+    folders
+        .iter()
+        .filter_map(|f| Path::new(f).canonicalize().ok())
+        .any(|root| canonical.starts_with(root))
+}
+
+/// 从列表中移除目录；按规范化路径比较，目录已不存在时按原样比较
+/// Remove a folder from the list, comparing canonical paths (or the raw path when the folder
+/// no longer exists).
+fn remove_folder(folders: &mut Vec<String>, path: &str) -> Result<(), String> {
+    let canonical = Path::new(path)
+        .canonicalize()
+        .map_or_else(|_| path.to_string(), |p| p.display().to_string());
+    let before = folders.len();
+    folders.retain(|f| *f != canonical && f != path);
+    if folders.len() == before {
+        return Err(format!("{} is not an approved folder", path));
+    }
+    Ok(())
+}
+
+fn path_arg(args: &Value) -> Result<&str, String> {
+    args.get("path")
+        .and_then(|p| p.as_str())
+        .ok_or_else(|| "Missing 'path'".to_string())
+}
+
+/// 粗略判断是否为二进制文件（前 8KB 含 NUL）
+/// Rough binary check (NUL within the first 8KB).
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8192).any(|b| *b == 0)
+}
+
+/// 读取文本文件工具
+/// Read text file tool.
+pub struct ReadFileTool;
+
+#[async_trait]
+impl ToolHandler for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_text_file"
+    }
+
+    fn description(&self) -> &str {
+        "Read a UTF-8 text file inside the user-approved folders. Large files are truncated; use offset to continue."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::ReadFiles
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Absolute file path" },
+                "offset": { "type": "integer", "minimum": 0, "description": "Byte offset to start reading from" }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        let path = resolve_in_approved(&ctx.app, path_arg(&args)?)?;
+        let offset = args.get("offset").and_then(|o| o.as_u64()).unwrap_or(0);
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+            let size = file.metadata().map_err(|e| e.to_string())?.len();
+            std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(offset.min(size)))
+                .map_err(|e| e.to_string())?;
+            let mut bytes = Vec::new();
+            file.take(MAX_READ_BYTES)
+                .read_to_end(&mut bytes)
+                .map_err(|e| e.to_string())?;
+            if looks_binary(&bytes) {
+                return Err(format!("{} looks like a binary file", path.display()));
+            }
+            let read = bytes.len() as u64;
+            Ok(json!({
+                "path": path.display().to_string(),
+                "size": size,
+                "offset": offset,
+                "content": String::from_utf8_lossy(&bytes),
+                "truncated": offset + read < size,
+            }))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+/// 列出目录工具
+/// List directory tool.
+pub struct ListDirectoryTool;
+
+#[async_trait]
+impl ToolHandler for ListDirectoryTool {
+    fn name(&self) -> &str {
+        "list_directory"
+    }
+
+    fn description(&self) -> &str {
+        "List the entries of a directory inside the user-approved folders."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::ReadFiles
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Absolute directory path" }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        let path = resolve_in_approved(&ctx.app, path_arg(&args)?)?;
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut entries = Vec::new();
+            let mut truncated = false;
+            for entry in std::fs::read_dir(&path).map_err(|e| e.to_string())? {
+                if entries.len() >= MAX_LIST_ENTRIES {
+                    truncated = true;
+                    break;
+                }
+                let Ok(entry) = entry else { continue };
+                let meta = entry.metadata().ok();
+                entries.push(json!({
+                    "name": entry.file_name().to_string_lossy(),
+                    "is_dir": meta.as_ref().is_some_and(|m| m.is_dir()),
+                    "size": meta.as_ref().map(|m| m.len()),
+                }));
+            }
+            entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+            Ok(json!({
+                "path": path.display().to_string(),
+                "entries": entries,
+                "truncated": truncated,
+            }))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+/// 文件内正则搜索工具
+/// Regex search inside a file tool.
+pub struct SearchFileTool;
+
+#[async_trait]
+impl ToolHandler for SearchFileTool {
+    fn name(&self) -> &str {
+        "search_file"
+    }
+
+    fn description(&self) -> &str {
+        "Search a text file inside the user-approved folders with a regular expression; returns matching lines with line numbers."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::ReadFiles
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Absolute file path" },
+                "pattern": { "type": "string", "description": "Regular expression (Rust regex syntax)" },
+                "case_insensitive": { "type": "boolean" },
+                "max_matches": { "type": "integer", "minimum": 1, "maximum": 1000 }
+            },
+            "required": ["path", "pattern"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        let path = resolve_in_approved(&ctx.app, path_arg(&args)?)?;
+        let pattern = args
+            .get("pattern")
+            .and_then(|p| p.as_str())
+            .ok_or("Missing 'pattern'")?;
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(
+                args.get("case_insensitive")
+                    .and_then(|c| c.as_bool())
+                    .unwrap_or(false),
+            )
+            .size_limit(1 << 20)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let max_matches = args
+            .get("max_matches")
+            .and_then(|m| m.as_u64())
+            .map(|m| m.clamp(1, 1000) as usize)
+            .unwrap_or(DEFAULT_MAX_MATCHES);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            let size = std::fs::metadata(&path).map_err(|e| e.to_string())?.len();
+            if size > MAX_SEARCH_BYTES {
+                return Err(format!("File is larger than {} bytes", MAX_SEARCH_BYTES));
+            }
+            let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+            if looks_binary(&bytes) {
+                return Err(format!("{} looks like a binary file", path.display()));
+            }
+            let text = String::from_utf8_lossy(&bytes);
+            let mut matches = Vec::new();
+            let mut truncated = false;
+            for (index, line) in text.lines().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+                if matches.len() >= max_matches {
+                    truncated = true;
+                    break;
+                }
+                matches.push(json!({
+                    "line": index + 1,
+                    "text": line.chars().take(MAX_LINE_CHARS).collect::<String>(),
+                }));
+            }
+            Ok(json!({
+                "path": path.display().to_string(),
+                "matches": matches,
+                "truncated": truncated,
+            }))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+/// 列出授权目录
+/// List the approved folders.
+#[command]
+pub fn list_approved_folders(app: AppHandle) -> Vec<String> {
+    approved_folders(&app)
+}
+
+/// 添加授权目录（保存规范化路径）
+/// Add an approved folder (stored canonicalized).
+#[command]
+pub fn add_approved_folder(app: AppHandle, path: String) -> Result<Vec<String>, String> {
+    let canonical = Path::new(&path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot access {}: {}", path, e))?;
+    if !canonical.is_dir() {
+        return Err(format!("{} is not a directory", path));
+    }
+    let canonical = canonical.display().to_string();
+    let mut folders = approved_folders(&app);
+    if !folders.contains(&canonical) {
+        folders.push(canonical);
+        write_setting(&app, APPROVED_FOLDERS_FIELD, &folders)?;
+    }
+    Ok(folders)
+}
+
+/// 移除授权目录
+/// Remove an approved folder.
+#[command]
+pub fn remove_approved_folder(app: AppHandle, path: String) -> Result<Vec<String>, String> {
+    let mut folders = approved_folders(&app);
+    remove_folder(&mut folders, &path)?;
+    write_setting(&app, APPROVED_FOLDERS_FIELD, &folders)?;
+    Ok(folders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sengine-fs-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn paths_must_be_inside_a_folder() {
+        let dir = temp_folder("within");
+        let folders = vec![dir.join("sub").display().to_string()];
+        assert!(is_within(&dir.join("sub"), &folders));
+        assert!(is_within(&dir.join("sub/a.txt"), &folders));
+        assert!(!is_within(&dir, &folders));
+        assert!(!is_within(&dir.join("subway"), &folders));
+    }
+
+    #[test]
+    fn remove_matches_non_canonical_paths() {
+        let dir = temp_folder("remove");
+        let canonical = dir.join("sub").display().to_string();
+        let mut folders = vec![canonical.clone()];
+        let messy = format!("{}/sub/../sub/", dir.display());
+        remove_folder(&mut folders, &messy).unwrap();
+        assert!(folders.is_empty());
+        assert!(remove_folder(&mut folders, &canonical).is_err());
+    }
+
+    #[test]
+    fn remove_accepts_deleted_folders() {
+        let mut folders = vec!["/no/such/folder".to_string()];
+        remove_folder(&mut folders, "/no/such/folder").unwrap();
+        assert!(folders.is_empty());
+    }
+
+    #[test]
+    fn binary_detection() {
+        assert!(looks_binary(b"abc\0def"));
+        assert!(!looks_binary("纯文本".as_bytes()));
+    }
+}
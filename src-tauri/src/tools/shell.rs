@@ -0,0 +1,418 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Emitter};
+use tauri_plugin_shell::process::CommandEvent;
+use tauri_plugin_shell::ShellExt;
+use tracing::{info, warn};
+
+use super::fs::{approved_folders, is_within, resolve_in_approved};
+use super::{ToolContext, ToolHandler, ToolScope};
+use crate::settings::{read_setting, write_setting};
+
+/// Shell 工具配置在 store 中的字段名
+/// Store field holding the shell tool config.
+pub const SHELL_TOOL_FIELD: &str = "shell_tool";
+
+/// 允许传入子进程的环境变量，其余全部清除
+/// Environment variables passed to the child; everything else is scrubbed.
+const SAFE_ENV_VARS: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USERPROFILE",
+    "SYSTEMROOT",
+    "WINDIR",
+    "TEMP",
+    "TMP",
+    "TMPDIR",
+    "LANG",
+    "LC_ALL",
+    "PATHEXT",
+    "COMSPEC",
+];
+/// 单个参数的最大长度
+/// Max length of a single argument.
+const MAX_ARG_LEN: usize = 4096;
+/// 参数个数上限
+/// Max number of arguments.
+const MAX_ARGS: usize = 64;
+
+/// Shell 工具配置
+/// Shell tool config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellToolConfig {
+    /// 允许执行的程序名（不含路径）/ program names allowed to run (no paths)
+    #[serde(default)]
+    pub allowed_binaries: Vec<String>,
+    /// 超时（秒）/ wall-clock timeout (seconds)
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// stdout + stderr 合计上限（字节）/ cap of stdout + stderr combined (bytes)
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_output_bytes() -> usize {
+    64 * 1024
+}
+
+impl Default for ShellToolConfig {
+    fn default() -> Self {
+        Self {
+            allowed_binaries: Vec::new(),
+            timeout_secs: default_timeout_secs(),
+            max_output_bytes: default_max_output_bytes(),
+        }
+    }
+}
+
+fn load_config(app: &AppHandle) -> ShellToolConfig {
+    read_setting(app, SHELL_TOOL_FIELD).unwrap_or_default()
+}
+
+/// 在 PATH 中查找程序的绝对路径（清空环境前解析）
+/// Find the program's absolute path on PATH (resolved before the env is scrubbed).
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".into())
+            .split(';')
+            .map(|e| e.to_string())
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    std::env::split_paths(&path)
+        .flat_map(|dir| {
+            extensions
+                .iter()
+                .map(move |ext| dir.join(format!("{}{}", program, ext)))
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+/// 只允许白名单中的裸程序名（不含路径）
+/// Only bare program names from the allowlist (no paths) may run.
+fn check_program(config: &ShellToolConfig, program: &str) -> Result<(), String> {
+    if program.contains(['/', '\\']) || !config.allowed_binaries.iter().any(|b| b == program) {
+        return Err(format!("Program not allowed: {}", program));
+    }
+    Ok(())
+}
+
+/// 参数中可能是路径的部分：参数本身及 `--opt=value` 的值
+/// The parts of an argument that may be paths: the argument itself and the value of `--opt=value`.
+fn path_candidates(arg: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(arg).chain(arg.split_once('=').map(|(_, value)| value))
+}
+
+/// 绝对路径或含 `..` 的参数视为路径
+/// Absolute arguments and arguments containing `..` are treated as paths.
+fn is_path_like(arg: &str) -> bool {
+    Path::new(arg).is_absolute() || arg.starts_with(['/', '\\']) || arg.contains("..")
+}
+
+/// 相对工作目录解析路径参数；尚不存在的路径按其父目录解析
+/// Resolve a path argument against the working directory; paths that don't exist yet resolve
+/// through their parent.
+fn resolve_arg(cwd: &Path, arg: &str) -> Option<PathBuf> {
+    let path = cwd.join(arg);
+    if let Ok(canonical) = path.canonicalize() {
+        return Some(canonical);
+    }
+    let parent = path.parent()?.canonicalize().ok()?;
+    Some(parent.join(path.file_name()?))
+}
+
+/// 检查参数个数与长度，并确保路径参数不逃出授权目录
+/// Check argument count and length, and keep path arguments inside the approved folders.
+fn check_args(argv: &[String], cwd: &Path, folders: &[String]) -> Result<(), String> {
+    if argv.len() > MAX_ARGS || argv.iter().any(|a| a.len() > MAX_ARG_LEN) {
+        return Err("Too many or too long arguments".into());
+    }
+    for arg in argv {
+        for candidate in path_candidates(arg).filter(|c| is_path_like(c)) {
+            let inside = resolve_arg(cwd, candidate).is_some_and(|p| is_within(&p, folders));
+            if !inside {
+                return Err(format!(
+                    "Argument '{}' is outside the approved folders",
+                    arg
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 运行 Shell 命令工具（不经过 shell 解释，直接执行程序）
+/// Shell command tool (runs the program directly, no shell interpretation).
+pub struct ShellTool;
+
+#[async_trait]
+impl ToolHandler for ShellTool {
+    fn name(&self) -> &str {
+        "run_command"
+    }
+
+    fn description(&self) -> &str {
+        "Run an allowlisted program with arguments inside an approved folder. Path arguments must stay inside the approved folders. No shell features (pipes, globbing, redirection). Returns exit code, stdout and stderr."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::Shell
+    }
+
+    fn always_confirm(&self) -> bool {
+        true
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "program": { "type": "string", "description": "Program name, e.g. git" },
+                "args": { "type": "array", "items": { "type": "string" } },
+                "cwd": { "type": "string", "description": "Working directory inside an approved folder" }
+            },
+            "required": ["program"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        let config = load_config(&ctx.app);
+        let program = args
+            .get("program")
+            .and_then(|p| p.as_str())
+            .ok_or("Missing 'program'")?;
+        check_program(&config, program)?;
+        let argv: Vec<String> = match args.get("args") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|_| "'args' must be an array of strings".to_string())?,
+        };
+        let folders = approved_folders(&ctx.app);
+        let cwd = match args.get("cwd").and_then(|c| c.as_str()) {
+            Some(dir) => resolve_in_approved(&ctx.app, dir)?,
+            None => folders
+                .first()
+                .map(PathBuf::from)
+                .ok_or("No approved folder to run in")?,
+        };
+        if !Path::new(&cwd).is_dir() {
+            return Err(format!("{} is not a directory", cwd.display()));
+        }
+        check_args(&argv, &cwd, &folders)?;
+        let binary =
+            find_on_path(program).ok_or_else(|| format!("{} not found on PATH", program))?;
+
+        let env: Vec<(String, String)> = SAFE_ENV_VARS
+            .iter()
+            .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+            .collect();
+        let (mut rx, child) = ctx
+            .app
+            .shell()
+            .command(&binary)
+            .args(&argv)
+            .current_dir(&cwd)
+            .env_clear()
+            .envs(env)
+            .set_raw_out(true)
+            .set_process_group(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+        info!(request_id = %ctx.request_id, program = %program, "shell tool started");
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut truncated = false;
+        let mut exit_code = None;
+        let run = async {
+            while let Some(event) = rx.recv().await {
+                let (stream, bytes) = match event {
+                    CommandEvent::Stdout(bytes) => ("stdout", bytes),
+                    CommandEvent::Stderr(bytes) => ("stderr", bytes),
+                    CommandEvent::Terminated(payload) => {
+                        exit_code = payload.code;
+                        break;
+                    }
+                    CommandEvent::Error(e) => return Err(e),
+                    _ => continue,
+                };
+                // 超出上限后继续排空管道，但不再保留或推送
+                // Past the cap keep draining the pipes without keeping or pushing output
+                let room = config
+                    .max_output_bytes
+                    .saturating_sub(stdout.len() + stderr.len());
+                if room == 0 {
+                    truncated = true;
+                    continue;
+                }
+                let kept = &bytes[..bytes.len().min(room)];
+                truncated |= kept.len() < bytes.len();
+                let _ = ctx.window.emit(
+                    "tool-result",
+                    json!({
+                        "request_id": ctx.request_id,
+                        "call_id": ctx.call_id,
+                        "partial": true,
+                        "stream": stream,
+                        "chunk": String::from_utf8_lossy(kept),
+                    }),
+                );
+                if stream == "stdout" {
+                    stdout.extend_from_slice(kept);
+                } else {
+                    stderr.extend_from_slice(kept);
+                }
+            }
+            Ok(())
+        };
+        let limit = Duration::from_secs(config.timeout_secs.max(1));
+        let outcome = tokio::select! {
+            outcome = tokio::time::timeout(limit, run) => Some(outcome),
+            _ = ctx.cancel.cancelled() => None,
+        };
+        let timed_out = match outcome {
+            Some(Ok(result)) => {
+                result?;
+                false
+            }
+            Some(Err(_)) => {
+                warn!(request_id = %ctx.request_id, program = %program, "shell tool timed out");
+                let _ = child.kill();
+                true
+            }
+            None => {
+                info!(request_id = %ctx.request_id, program = %program, "shell tool cancelled");
+                let _ = child.kill();
+                return Err("The request was cancelled".into());
+            }
+        };
+
+        Ok(json!({
+            "program": program,
+            "args": argv,
+            "cwd": cwd.display().to_string(),
+            "exit_code": exit_code,
+            "timed_out": timed_out,
+            "truncated": truncated,
+            "stdout": String::from_utf8_lossy(&stdout),
+            "stderr": String::from_utf8_lossy(&stderr),
+        }))
+    }
+}
+
+/// 读取 Shell 工具配置
+/// Get the shell tool config.
+#[command]
+pub fn get_shell_tool_config(app: AppHandle) -> ShellToolConfig {
+    load_config(&app)
+}
+
+/// 保存 Shell 工具配置
+/// Save the shell tool config.
+#[command]
+pub fn save_shell_tool_config(app: AppHandle, config: ShellToolConfig) -> Result<(), String> {
+    if let Some(bad) = config
+        .allowed_binaries
+        .iter()
+        .find(|b| b.is_empty() || b.contains(['/', '\\']))
+    {
+        return Err(format!("Invalid program name: '{}'", bad));
+    }
+    write_setting(&app, SHELL_TOOL_FIELD, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allowed: &[&str]) -> ShellToolConfig {
+        ShellToolConfig {
+            allowed_binaries: allowed.iter().map(|b| b.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// 授权目录 `<tmp>/approved`（含 `src` 子目录）及其旁边的 `outside`
+    /// Approved folder `<tmp>/approved` (with a `src` subfolder) next to an `outside` folder.
+    fn jail(name: &str) -> (PathBuf, Vec<String>) {
+        let dir =
+            std::env::temp_dir().join(format!("sengine-shell-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("approved/src")).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+        std::fs::write(dir.join("approved/src/main.rs"), "fn main() {}").unwrap();
+        let root = dir.join("approved").canonicalize().unwrap();
+        let folders = vec![root.display().to_string()];
+        (root, folders)
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn only_allowlisted_bare_programs() {
+        let config = config(&["git", "ls"]);
+        assert!(check_program(&config, "git").is_ok());
+        assert!(check_program(&config, "cat").is_err());
+        assert!(check_program(&config, "/usr/bin/git").is_err());
+        assert!(check_program(&config, "..\\git").is_err());
+        assert!(check_program(&config, "bin/ls").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_programs_on_path() {
+        let sh = find_on_path("sh").unwrap();
+        assert!(sh.is_absolute() && sh.is_file());
+        assert!(find_on_path("sengine-no-such-program").is_none());
+    }
+
+    #[test]
+    fn argument_limits() {
+        let (root, folders) = jail("limits");
+        let many = vec!["x".to_string(); MAX_ARGS + 1];
+        assert!(check_args(&many, &root, &folders).is_err());
+        let long = vec!["x".repeat(MAX_ARG_LEN + 1)];
+        assert!(check_args(&long, &root, &folders).is_err());
+        let ok = vec!["x".repeat(MAX_ARG_LEN); MAX_ARGS];
+        assert!(check_args(&ok, &root, &folders).is_ok());
+    }
+
+    #[test]
+    fn path_arguments_stay_in_the_jail() {
+        let (root, folders) = jail("escape");
+        let inside = root.join("src/main.rs").display().to_string();
+        let outside = root.join("../outside").display().to_string();
+        for ok in [
+            args(&["log", "--oneline", "main..feature"]),
+            args(&["src/main.rs", "src/../src/new.rs"]),
+            args(&[inside.as_str()]),
+            args(&["--output=src/out.txt"]),
+        ] {
+            assert!(check_args(&ok, &root, &folders).is_ok(), "{:?}", ok);
+        }
+        for bad in [
+            args(&["/etc/passwd"]),
+            args(&["-C", "/", "log"]),
+            args(&["../../.."]),
+            args(&["src/../../outside"]),
+            args(&[outside.as_str()]),
+            args(&["--git-dir=/"]),
+        ] {
+            assert!(check_args(&bad, &root, &folders).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{ToolContext, ToolHandler, ToolScope};
+use crate::web::fetch_page;
+
+/// 返回给模型的默认最大字符数
+/// Default max characters returned to the model.
+const DEFAULT_MAX_CHARS: usize = 20_000;
+
+/// 网页抓取工具
+/// Web page fetch tool.
+pub struct FetchUrlTool;
+
+#[async_trait]
+impl ToolHandler for FetchUrlTool {
+    fn name(&self) -> &str {
+        "fetch_url"
+    }
+
+    fn description(&self) -> &str {
+        "Download a web page and return its readable text as Markdown (navigation, scripts and styles removed)."
+    }
+
+    fn scope(&self) -> ToolScope {
+        ToolScope::Network
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "http(s) URL to fetch" },
+                "max_chars": { "type": "integer", "minimum": 500, "maximum": 200000 }
+            },
+            "required": ["url"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<Value, String> {
+        let url = args
+            .get("url")
+            .and_then(|u| u.as_str())
+            .ok_or("Missing 'url'")?;
+        let max_chars = args
+            .get("max_chars")
+            .and_then(|m| m.as_u64())
+            .map(|m| m as usize)
+            .unwrap_or(DEFAULT_MAX_CHARS);
+        let mut page = fetch_page(&ctx.app, url).await?;
+        page.limit_chars(max_chars);
+        serde_json::to_value(page).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{command, State};
+
+use crate::helpers::now_millis;
+
+/// 请求日志文件名（JSON Lines，位于应用数据目录）
+/// Request log file name (JSON Lines, inside the app data dir).
+const USAGE_LOG_FILE: &str = "usage_log.jsonl";
+
+static REQUEST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 生成进程内唯一的请求 ID
+/// Generate a request id unique within the process.
+pub fn next_request_id() -> String {
+    format!(
+        "req-{}-{}",
+        now_millis(),
+        REQUEST_SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// 单次请求的本地记录
+/// Local record of a single request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestLog {
+    pub id: String,
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    pub key_id: String,
+    pub model: String,
+    /// 实际提供方（来自流中的 `provider`）/ upstream provider (from `provider` in the stream)
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// 开始时间（毫秒）/ start time (ms)
+    pub started_at: u64,
+    /// 总耗时 / total latency
+    pub latency_ms: u64,
+    /// 首 Token 耗时 / time to first token
+    #[serde(default)]
+    pub ttft_ms: Option<u64>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 命中提示缓存的输入 Token / prompt tokens read from the prompt cache
+    #[serde(default)]
+    pub cached_tokens: u64,
+    /// 写入提示缓存的输入 Token / prompt tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    pub cost: f64,
+    /// 提示缓存节省的花费 / cost saved by the prompt cache
+    #[serde(default)]
+    pub cache_savings: f64,
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl RequestLog {
+    /// 请求开始时创建记录
+    /// Create a record when the request starts.
+    pub fn start(id: &str, key_id: &str, model: &str, conversation_id: Option<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            conversation_id,
+            key_id: key_id.to_string(),
+            model: model.to_string(),
+            started_at: now_millis(),
+            ..Default::default()
+        }
+    }
+
+    /// 记录首个 Token 到达时间（只记一次）
+    /// Mark the arrival of the first token (only once).
+    pub fn mark_first_token(&mut self) {
+        if self.ttft_ms.is_none() {
+            self.ttft_ms = Some(now_millis().saturating_sub(self.started_at));
+        }
+    }
+
+    /// 根据流中的块更新提供方
+    /// Update the provider from a stream chunk.
+    pub fn observe_chunk(&mut self, chunk: &Value) {
+        if self.provider.is_none() {
+            self.provider = chunk
+                .get("provider")
+                .and_then(|p| p.as_str())
+                .map(str::to_string);
+        }
+    }
+
+    /// 请求结束：写入耗时、Token 与花费
+    /// Finish the request: fill latency, tokens and cost.
+    pub fn finish(&mut self, usage: Option<&Value>, error: Option<&String>) {
+        self.latency_ms = now_millis().saturating_sub(self.started_at);
+        if let Some(usage) = usage {
+            let field = |name: &str| usage.get(name).and_then(|v| v.as_u64()).unwrap_or(0);
+            self.prompt_tokens = field("prompt_tokens");
+            self.completion_tokens = field("completion_tokens");
+            let detail = |name: &str| {
+                usage
+                    .get("prompt_tokens_details")
+                    .and_then(|d| d.get(name))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0)
+            };
+            self.cached_tokens = detail("cached_tokens");
+            self.cache_write_tokens = detail("cache_write_tokens");
+            self.cost = usage.get("cost").and_then(|c| c.as_f64()).unwrap_or(0.0);
+        }
+        self.success = error.is_none();
+        self.error = error.cloned();
+    }
+}
+
+/// 请求日志：追加写入 JSON Lines 文件
+/// Request log: appended to a JSON Lines file.
+pub struct UsageLog {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl UsageLog {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            path: data_dir.join(USAGE_LOG_FILE),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// 追加一条记录
+    /// Append one record.
+    pub fn append(&self, record: &RequestLog) {
+        let _guard = self.write_lock.lock().unwrap();
+        let result = (|| -> Result<(), String> {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .map_err(|e| e.to_string())?;
+            let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+            writeln!(file, "{}", line).map_err(|e| e.to_string())
+        })();
+        if let Err(e) = result {
+            tracing::warn!("Failed to append usage log: {}", e);
+        }
+    }
+
+    /// 读取时间范围内的记录（毫秒，闭区间）
+    /// Read records within a time range (ms, inclusive).
+    pub fn read_range(&self, from: Option<u64>, to: Option<u64>) -> Vec<RequestLog> {
+        let _guard = self.write_lock.lock().unwrap();
+        let Ok(file) = std::fs::File::open(&self.path) else {
+            return Vec::new();
+        };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<RequestLog>(&line).ok())
+            .filter(|r| {
+                from.is_none_or(|f| r.started_at >= f) && to.is_none_or(|t| r.started_at <= t)
+            })
+            .collect()
+    }
+}
+
+/// 一个分组的聚合结果
+/// Aggregate for one group.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageGroup {
+    pub key: String,
+    pub requests: u64,
+    pub failures: u64,
+    pub failure_rate: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    pub cost: f64,
+    pub cache_savings: f64,
+    pub avg_latency_ms: f64,
+    pub avg_ttft_ms: Option<f64>,
+    #[serde(skip)]
+    latency_sum: u64,
+    #[serde(skip)]
+    ttft_sum: u64,
+    #[serde(skip)]
+    ttft_count: u64,
+}
+
+impl UsageGroup {
+    fn add(&mut self, r: &RequestLog) {
+        self.requests += 1;
+        if !r.success {
+            self.failures += 1;
+        }
+        self.prompt_tokens += r.prompt_tokens;
+        self.completion_tokens += r.completion_tokens;
+        self.cached_tokens += r.cached_tokens;
+        self.cost += r.cost;
+        self.cache_savings += r.cache_savings;
+        self.latency_sum += r.latency_ms;
+        if let Some(ttft) = r.ttft_ms {
+            self.ttft_sum += ttft;
+            self.ttft_count += 1;
+        }
+    }
+
+    fn finalize(mut self) -> Self {
+        if self.requests > 0 {
+            self.failure_rate = self.failures as f64 / self.requests as f64;
+            self.avg_latency_ms = self.latency_sum as f64 / self.requests as f64;
+        }
+        if self.ttft_count > 0 {
+            self.avg_ttft_ms = Some(self.ttft_sum as f64 / self.ttft_count as f64);
+        }
+        self
+    }
+}
+
+/// 时间范围内的用量统计
+/// Usage statistics over a time range.
+#[derive(Debug, Default, Serialize)]
+pub struct UsageStats {
+    pub total: UsageGroup,
+    pub by_model: Vec<UsageGroup>,
+    pub by_provider: Vec<UsageGroup>,
+    pub by_key: Vec<UsageGroup>,
+    pub by_day: Vec<UsageGroup>,
+    pub by_conversation: Vec<UsageGroup>,
+}
+
+fn day_of(ms: u64) -> String {
+    Local
+        .timestamp_millis_opt(ms as i64)
+        .single()
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn group_by<F>(records: &[RequestLog], key_of: F) -> Vec<UsageGroup>
+where
+    F: Fn(&RequestLog) -> String,
+{
+    let mut groups: HashMap<String, UsageGroup> = HashMap::new();
+    for r in records {
+        let key = key_of(r);
+        groups
+            .entry(key.clone())
+            .or_insert_with(|| UsageGroup {
+                key,
+                ..Default::default()
+            })
+            .add(r);
+    }
+    let mut list: Vec<UsageGroup> = groups.into_values().map(UsageGroup::finalize).collect();
+    list.sort_by(|a, b| b.cost.total_cmp(&a.cost).then_with(|| a.key.cmp(&b.key)));
+    list
+}
+
+/// 聚合用量
+/// Aggregate usage.
+pub fn aggregate(records: &[RequestLog]) -> UsageStats {
+    let mut total = UsageGroup {
+        key: "total".into(),
+        ..Default::default()
+    };
+    records.iter().for_each(|r| total.add(r));
+
+    let mut by_day = group_by(records, |r| day_of(r.started_at));
+    by_day.sort_by(|a, b| a.key.cmp(&b.key));
+
+    UsageStats {
+        total: total.finalize(),
+        by_model: group_by(records, |r| r.model.clone()),
+        by_provider: group_by(records, |r| {
+            r.provider.clone().unwrap_or_else(|| "unknown".into())
+        }),
+        by_key: group_by(records, |r| r.key_id.clone()),
+        by_day,
+        by_conversation: group_by(records, |r| r.conversation_id.clone().unwrap_or_default()),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 将记录转换为 CSV 文本
+/// Render records as CSV text.
+pub fn to_csv(records: &[RequestLog]) -> String {
+    let mut out = String::from(
+        "id,started_at,day,conversation_id,key_id,model,provider,latency_ms,ttft_ms,prompt_tokens,completion_tokens,cached_tokens,cost,cache_savings,success,error\n",
+    );
+    for r in records {
+        let row = [
+            csv_field(&r.id),
+            r.started_at.to_string(),
+            day_of(r.started_at),
+            csv_field(r.conversation_id.as_deref().unwrap_or("")),
+            csv_field(&r.key_id),
+            csv_field(&r.model),
+            csv_field(r.provider.as_deref().unwrap_or("")),
+            r.latency_ms.to_string(),
+            r.ttft_ms.map(|t| t.to_string()).unwrap_or_default(),
+            r.prompt_tokens.to_string(),
+            r.completion_tokens.to_string(),
+            r.cached_tokens.to_string(),
+            format!("{:.8}", r.cost),
+            format!("{:.8}", r.cache_savings),
+            r.success.to_string(),
+            csv_field(r.error.as_deref().unwrap_or("")),
+        ];
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// 获取时间范围内的用量统计（毫秒时间戳，可省略）
+/// Get usage statistics over a time range (ms timestamps, optional).
+#[command]
+pub fn get_usage_stats(log: State<'_, UsageLog>, from: Option<u64>, to: Option<u64>) -> UsageStats {
+    aggregate(&log.read_range(from, to))
+}
+
+/// 获取某个会话的用量（含提示缓存节省）
+/// Get one conversation's usage, including prompt cache savings.
+#[command]
+pub fn get_conversation_usage(log: State<'_, UsageLog>, conversation_id: String) -> UsageGroup {
+    let mut group = UsageGroup {
+        key: conversation_id.clone(),
+        ..Default::default()
+    };
+    log.read_range(None, None)
+        .iter()
+        .filter(|r| r.conversation_id.as_deref() == Some(conversation_id.as_str()))
+        .for_each(|r| group.add(r));
+    group.finalize()
+}
+
+/// 获取时间范围内的原始请求记录
+/// Get raw request records over a time range.
+#[command]
+pub fn list_request_logs(
+    log: State<'_, UsageLog>,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Vec<RequestLog> {
+    log.read_range(from, to)
+}
+
+/// 导出 CSV 到指定路径，返回导出的行数
+/// Export CSV to the given path; returns the number of rows.
+#[command]
+pub fn export_usage_csv(
+    log: State<'_, UsageLog>,
+    from: Option<u64>,
+    to: Option<u64>,
+    path: String,
+) -> Result<usize, String> {
+    let records = log.read_range(from, to);
+    std::fs::write(&path, to_csv(&records)).map_err(|e| e.to_string())?;
+    Ok(records.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 本地时区某日中午的毫秒时间戳 / local noon of a day, in ms
+    fn noon(day: u32) -> u64 {
+        Local
+            .with_ymd_and_hms(2025, 3, day, 12, 0, 0)
+            .unwrap()
+            .timestamp_millis() as u64
+    }
+
+    fn record(day: u32, model: &str, key_id: &str, cost: f64, savings: f64) -> RequestLog {
+        RequestLog {
+            id: format!("req-{}-{}", day, model),
+            key_id: key_id.into(),
+            model: model.into(),
+            started_at: noon(day),
+            latency_ms: 100,
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            cost,
+            cache_savings: savings,
+            success: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aggregate_groups_by_day_model_and_key() {
+        let mut failed = record(2, "b/model", "k2", 0.0, 0.0);
+        failed.success = false;
+        failed.latency_ms = 300;
+        failed.ttft_ms = Some(40);
+        let records = [
+            record(2, "a/model", "k1", 0.5, 0.25),
+            record(1, "a/model", "k2", 0.25, 0.125),
+            failed,
+        ];
+        let stats = aggregate(&records);
+
+        assert_eq!(stats.total.requests, 3);
+        assert_eq!(stats.total.failures, 1);
+        assert_eq!(stats.total.prompt_tokens, 30);
+        assert_eq!(stats.total.cost, 0.75);
+        assert_eq!(stats.total.cache_savings, 0.375);
+        assert!((stats.total.avg_latency_ms - 500.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.total.avg_ttft_ms, Some(40.0));
+
+        // 按日期升序 / days in ascending order
+        let days: Vec<(&str, u64)> = stats
+            .by_day
+            .iter()
+            .map(|g| (g.key.as_str(), g.requests))
+            .collect();
+        assert_eq!(days, [("2025-03-01", 1), ("2025-03-02", 2)]);
+
+        // 其余分组按花费降序 / other groups by cost, highest first
+        let models: Vec<(&str, f64, f64)> = stats
+            .by_model
+            .iter()
+            .map(|g| (g.key.as_str(), g.cost, g.cache_savings))
+            .collect();
+        assert_eq!(models, [("a/model", 0.75, 0.375), ("b/model", 0.0, 0.0)]);
+        assert_eq!(stats.by_model[1].failure_rate, 1.0);
+
+        let keys: Vec<(&str, u64, f64)> = stats
+            .by_key
+            .iter()
+            .map(|g| (g.key.as_str(), g.requests, g.cache_savings))
+            .collect();
+        assert_eq!(keys, [("k1", 1, 0.25), ("k2", 2, 0.125)]);
+        assert_eq!(stats.by_provider[0].key, "unknown");
+    }
+
+    #[test]
+    fn aggregate_of_nothing_is_empty() {
+        let stats = aggregate(&[]);
+        assert_eq!(stats.total.requests, 0);
+        assert_eq!(stats.total.avg_latency_ms, 0.0);
+        assert_eq!(stats.total.avg_ttft_ms, None);
+        assert!(stats.by_day.is_empty());
+    }
+
+    #[test]
+    fn csv_fields_are_escaped() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn csv_rows_escape_model_and_error() {
+        let mut failed = record(1, "odd,\"model\"", "k", 0.5, 0.0);
+        failed.id = "req-1".into();
+        failed.success = false;
+        failed.error = Some("HTTP 500,\nretry".into());
+        let csv = to_csv(&[failed]);
+        let (header, row) = csv.split_once('\n').unwrap();
+        assert_eq!(header.split(',').count(), 16);
+        assert!(row.starts_with(&format!(
+            "req-1,{},2025-03-01,,k,\"odd,\"\"model\"\"\",,100,,10,5,0,0.50000000,0.00000000,false,",
+            noon(1)
+        )));
+        assert!(row.ends_with(",false,\"HTTP 500,\nretry\"\n"));
+    }
+}
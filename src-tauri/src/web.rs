@@ -0,0 +1,1034 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Manager, State};
+use tracing::{debug, info};
+
+use crate::helpers::now_millis;
+use crate::settings::{read_setting, write_setting};
+
+/// 网页抓取配置在 store 中的字段名
+/// Store field holding the web fetch config.
+pub const WEB_FETCH_FIELD: &str = "web_fetch";
+
+/// robots.txt 中匹配的产品名
+/// Product token matched in robots.txt.
+const ROBOTS_AGENT: &str = "sengine";
+/// 抓取请求的 User-Agent
+/// User-Agent of fetch requests.
+const USER_AGENT: &str = concat!("sengine/", env!("CARGO_PKG_VERSION"), " (+fetch_url)");
+/// robots.txt 缓存有效期（毫秒）
+/// How long a cached robots.txt stays fresh (ms).
+const ROBOTS_TTL_MS: u64 = 60 * 60 * 1000;
+/// robots.txt 的最大读取字节数
+/// Max bytes read from a robots.txt.
+const MAX_ROBOTS_BYTES: usize = 512 * 1024;
+/// 正文比整页短时仍采用正文的最小长度
+/// Min length for the main/article text to win over the whole page.
+const MIN_MAIN_CHARS: usize = 200;
+/// 最多跟随的重定向次数
+/// Max redirects followed.
+const MAX_REDIRECTS: usize = 10;
+
+/// 网页抓取配置
+/// Web fetch config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebFetchConfig {
+    /// 允许的域名（含子域名），为空表示不限 / allowed domains (incl. subdomains), empty means any
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// 禁止的域名（含子域名）/ blocked domains (incl. subdomains)
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+    /// 是否遵守 robots.txt / whether to honour robots.txt
+    #[serde(default = "default_true")]
+    pub respect_robots: bool,
+    /// 是否允许本机与内网地址 / whether loopback and private addresses are allowed
+    #[serde(default)]
+    pub allow_private_hosts: bool,
+    /// 下载上限（字节）/ download cap (bytes)
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+    /// 超时（秒）/ timeout (seconds)
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_timeout_secs() -> u64 {
+    20
+}
+
+impl Default for WebFetchConfig {
+    fn default() -> Self {
+        Self {
+            allowed_domains: Vec::new(),
+            blocked_domains: Vec::new(),
+            respect_robots: true,
+            allow_private_hosts: false,
+            max_bytes: default_max_bytes(),
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+fn load_config(app: &AppHandle) -> WebFetchConfig {
+    read_setting(app, WEB_FETCH_FIELD).unwrap_or_default()
+}
+
+/// 抓取结果
+/// A fetched page.
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchedPage {
+    pub url: String,
+    /// 跟随重定向后的地址 / address after redirects
+    pub final_url: String,
+    pub title: Option<String>,
+    pub content_type: String,
+    /// Markdown（HTML）或原文（纯文本）/ Markdown (HTML) or raw text (plain text)
+    pub content: String,
+    /// 下载或正文是否被截断 / whether the download or content was cut
+    pub truncated: bool,
+}
+
+impl FetchedPage {
+    /// 截断正文到指定字符数
+    /// Cut the content to `max_chars` characters.
+    pub fn limit_chars(&mut self, max_chars: usize) {
+        if let Some((index, _)) = self.content.char_indices().nth(max_chars) {
+            self.content.truncate(index);
+            self.truncated = true;
+        }
+    }
+
+    /// 作为消息上下文的 `text` 内容块
+    /// The page as a `text` content part to attach to a message.
+    pub fn to_context_part(&self) -> Value {
+        let title = self.title.as_deref().unwrap_or(&self.final_url);
+        json!({
+            "type": "text",
+            "text": format!(
+                "<source url=\"{}\" title=\"{}\">\n{}\n</source>",
+                self.final_url,
+                title.replace('"', "'"),
+                self.content
+            ),
+        })
+    }
+}
+
+/// 域名是否命中列表（含子域名）
+/// Whether the host matches a list entry (subdomains included).
+fn host_in(host: &str, domains: &[String]) -> bool {
+    domains.iter().any(|d| {
+        let d = d.trim().trim_start_matches("*.").to_ascii_lowercase();
+        !d.is_empty() && (host == d || host.ends_with(&format!(".{}", d)))
+    })
+}
+
+/// 是否为本机、内网或链路本地地址
+/// Whether the address is loopback, private or link-local.
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_private_ip(IpAddr::V4(v4)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || (ip.segments()[0] & 0xfe00) == 0xfc00
+                    || (ip.segments()[0] & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+/// 是否为本机或内网地址（仅检查字面量，域名由 `PublicOnlyResolver` 在解析后检查）
+/// Whether the host is loopback or private (literal check only; names are checked after
+/// resolution by `PublicOnlyResolver`).
+fn is_private_host(host: &str) -> bool {
+    if host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local") {
+        return true;
+    }
+    host.trim_matches(['[', ']'])
+        .parse::<IpAddr>()
+        .is_ok_and(is_private_ip)
+}
+
+/// 拒绝解析到本机或内网地址的域名
+/// DNS resolver that refuses names resolving to loopback or private addresses.
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|a| is_private_ip(a.ip())) {
+                return Err(
+                    format!("{} resolves to a private address: {}", host, addr.ip()).into(),
+                );
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// 抓取专用的客户端：每次重定向都按规则检查，禁止内网时也检查解析出的地址
+/// Client for fetching: every redirect hop is checked against the rules, and resolved
+/// addresses are checked too unless private hosts are allowed.
+fn fetch_client(config: &WebFetchConfig) -> Result<Client, String> {
+    let rules = config.clone();
+    let mut builder = Client::builder().redirect(Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("Too many redirects")
+        } else if let Err(e) = check_url(&rules, attempt.url()) {
+            attempt.error(e)
+        } else {
+            attempt.follow()
+        }
+    }));
+    if !config.allow_private_hosts {
+        builder = builder.dns_resolver(Arc::new(PublicOnlyResolver));
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// 错误信息连同底层原因（重定向与地址检查的拒绝原因在 source 中）
+/// Error message with its causes (redirect and address rejections are in the sources).
+fn error_chain(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+/// 校验地址的协议与域名规则
+/// Check the URL scheme and domain rules.
+fn check_url(config: &WebFetchConfig, url: &Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Unsupported scheme: {}", url.scheme()));
+    }
+    let host = url
+        .host_str()
+        .ok_or("URL has no host")?
+        .to_ascii_lowercase();
+    if !config.allow_private_hosts && is_private_host(&host) {
+        return Err(format!("Private address not allowed: {}", host));
+    }
+    if host_in(&host, &config.blocked_domains) {
+        return Err(format!("Domain is blocked: {}", host));
+    }
+    if !config.allowed_domains.is_empty() && !host_in(&host, &config.allowed_domains) {
+        return Err(format!("Domain is not in the allowlist: {}", host));
+    }
+    Ok(())
+}
+
+/// robots.txt 中适用于本程序的规则
+/// robots.txt rules that apply to this client.
+#[derive(Debug, Clone, Default)]
+struct RobotsRules {
+    /// (是否允许, 路径模式) / (allow, path pattern)
+    rules: Vec<(bool, String)>,
+}
+
+impl RobotsRules {
+    /// 解析 robots.txt：优先匹配本程序的组，否则使用 `*` 组
+    /// Parse robots.txt: our own group wins, else the `*` group.
+    fn parse(text: &str) -> Self {
+        let mut own = Vec::new();
+        let mut any = Vec::new();
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    // 空的 Disallow 表示全部允许 / an empty Disallow allows everything
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (key == "allow", value.to_string());
+                    if agents.iter().any(|a| a == ROBOTS_AGENT) {
+                        own.push(rule.clone());
+                    }
+                    if agents.iter().any(|a| a == "*") {
+                        any.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            rules: if own.is_empty() { any } else { own },
+        }
+    }
+
+    /// 最长匹配规则决定结果，等长时 Allow 优先
+    /// The longest matching rule decides; Allow wins ties.
+    fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| robots_match(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+/// robots 路径模式匹配，支持 `*` 与结尾 `$`
+/// robots path pattern match with `*` and a trailing `$`.
+fn robots_match(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or("")) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() {
+        return !anchored || rest.is_empty();
+    }
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// 按站点缓存的 robots.txt 规则
+/// robots.txt rules cached per origin.
+#[derive(Default)]
+pub struct RobotsCache {
+    entries: Mutex<HashMap<String, (u64, RobotsRules)>>,
+}
+
+impl RobotsCache {
+    async fn rules(&self, client: &Client, url: &Url, timeout: Duration) -> RobotsRules {
+        let origin = url.origin().ascii_serialization();
+        if let Some((fetched_at, rules)) = self.entries.lock().unwrap().get(&origin) {
+            if now_millis().saturating_sub(*fetched_at) < ROBOTS_TTL_MS {
+                return rules.clone();
+            }
+        }
+        // 取不到 robots.txt 时视为全部允许 / a missing robots.txt allows everything
+        let robots_url = format!("{}/robots.txt", origin);
+        let rules = match download(client, &robots_url, MAX_ROBOTS_BYTES, timeout).await {
+            Ok(res) if res.status.is_success() => {
+                RobotsRules::parse(&String::from_utf8_lossy(&res.bytes))
+            }
+            _ => RobotsRules::default(),
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(origin, (now_millis(), rules.clone()));
+        rules
+    }
+}
+
+struct Download {
+    status: reqwest::StatusCode,
+    final_url: Url,
+    content_type: String,
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+/// 下载到字节上限为止
+/// Download up to a byte cap.
+async fn download(
+    client: &Client,
+    url: &str,
+    max_bytes: usize,
+    timeout: Duration,
+) -> Result<Download, String> {
+    let res = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .header(
+            "Accept",
+            "text/html,application/xhtml+xml,text/plain;q=0.9,*/*;q=0.5",
+        )
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| error_chain(&e))?;
+    let status = res.status();
+    let final_url = res.url().clone();
+    let content_type = res
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let mut bytes = Vec::new();
+    let mut truncated = false;
+    let mut stream = res.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        let room = max_bytes - bytes.len();
+        if chunk.len() > room {
+            bytes.extend_from_slice(&chunk[..room]);
+            truncated = true;
+            break;
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(Download {
+        status,
+        final_url,
+        content_type,
+        bytes,
+        truncated,
+    })
+}
+
+/// 按 Content-Type 的 charset 解码，默认 UTF-8
+/// Decode by the Content-Type charset, UTF-8 by default.
+fn decode_text(bytes: &[u8], content_type: &str) -> String {
+    let label = content_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("charset="))
+        .next()
+        .map(|c| c.trim_matches('"'));
+    let encoding = label
+        .and_then(|l| encoding_rs::Encoding::for_label(l.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// 抓取网页并提取可读正文
+/// Fetch a page and extract its readable text.
+pub async fn fetch_page(app: &AppHandle, url: &str) -> Result<FetchedPage, String> {
+    fetch_with(&load_config(app), &app.state::<RobotsCache>(), url).await
+}
+
+async fn fetch_with(
+    config: &WebFetchConfig,
+    robots: &RobotsCache,
+    url: &str,
+) -> Result<FetchedPage, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    check_url(config, &parsed)?;
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    let client = fetch_client(config)?;
+
+    if config.respect_robots {
+        let rules = robots.rules(&client, &parsed, timeout).await;
+        let path = match parsed.query() {
+            Some(q) => format!("{}?{}", parsed.path(), q),
+            None => parsed.path().to_string(),
+        };
+        if !rules.allows(&path) {
+            return Err(format!("Disallowed by robots.txt: {}", parsed));
+        }
+    }
+
+    let res = download(&client, parsed.as_str(), config.max_bytes, timeout).await?;
+    if !res.status.is_success() {
+        return Err(format!("HTTP {} for {}", res.status, res.final_url));
+    }
+    let mime = res
+        .content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let text = decode_text(&res.bytes, &res.content_type);
+    let is_html = (mime.is_empty() && text.trim_start().starts_with('<'))
+        || mime == "text/html"
+        || mime == "application/xhtml+xml";
+    let (title, content) = if is_html {
+        html_to_markdown(&text, &res.final_url)
+    } else if mime.starts_with("text/")
+        || mime.ends_with("json")
+        || mime.ends_with("xml")
+        || mime.is_empty()
+    {
+        (None, text)
+    } else {
+        return Err(format!("Unsupported content type: {}", mime));
+    };
+    info!(url = %res.final_url, bytes = res.bytes.len(), "fetched page");
+
+    Ok(FetchedPage {
+        url: url.to_string(),
+        final_url: res.final_url.to_string(),
+        title,
+        content_type: mime,
+        content,
+        truncated: res.truncated,
+    })
+}
+
+/// 不输出内容的元素
+/// Elements whose content is dropped.
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "nav",
+    "header", "footer", "aside", "form", "button", "select", "dialog", "menu",
+];
+/// 块级元素，前后换段
+/// Block elements, separated by blank lines.
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "figure",
+    "figcaption",
+    "table",
+    "dl",
+    "dd",
+    "dt",
+    "address",
+    "details",
+    "summary",
+];
+
+/// 转换状态
+/// Conversion state.
+#[derive(Default)]
+struct MarkdownState {
+    base: Option<Url>,
+    out: String,
+    /// `<main>` / `<article>` 内的输出 / output inside `<main>` / `<article>`
+    main: String,
+    main_depth: usize,
+    title: Option<String>,
+    in_title: bool,
+    /// 被跳过的元素栈 / stack of skipped elements
+    skip: Vec<String>,
+    pre_depth: usize,
+    /// 列表栈：有序列表记录序号 / list stack: ordered lists keep a counter
+    lists: Vec<Option<usize>>,
+    /// 链接栈：`None` 表示无 href / link stack: `None` means no href
+    links: Vec<Option<String>>,
+}
+
+impl MarkdownState {
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+        if self.main_depth > 0 {
+            self.main.push_str(text);
+        }
+    }
+
+    /// 确保以 `n` 个换行结尾（开头不输出）
+    /// Make sure the output ends with `n` newlines (nothing at the start).
+    fn newlines(&mut self, n: usize) {
+        if self.out.is_empty() {
+            return;
+        }
+        let have = self.out.len() - self.out.trim_end_matches('\n').len();
+        if have < n {
+            let add = "\n".repeat(n - have);
+            self.write(&add);
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if !self.skip.is_empty() {
+            return;
+        }
+        if self.in_title {
+            self.title.get_or_insert_with(String::new).push_str(text);
+            return;
+        }
+        if self.pre_depth > 0 {
+            self.write(text);
+            return;
+        }
+        let collapsed: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let at_line_start = self.out.is_empty() || self.out.ends_with(['\n', ' ', '[', '(']);
+        if text.starts_with(char::is_whitespace) && !at_line_start && !collapsed.is_empty() {
+            self.write(" ");
+        }
+        self.write(&collapsed);
+        if text.ends_with(char::is_whitespace) && !collapsed.is_empty() {
+            self.write(" ");
+        }
+    }
+
+    fn attr(tag: &Tag, name: &str) -> Option<String> {
+        tag.attrs
+            .iter()
+            .find(|a| &*a.name.local == name)
+            .map(|a| a.value.to_string())
+    }
+
+    fn resolve(&self, href: &str) -> Option<String> {
+        let url = match &self.base {
+            Some(base) => base.join(href).ok()?,
+            None => Url::parse(href).ok()?,
+        };
+        matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+    }
+
+    fn start(&mut self, tag: &Tag) -> TokenSinkResult<()> {
+        let name = &*tag.name;
+        let raw = match name {
+            "script" => Some(RawKind::ScriptData),
+            "style" | "noscript" | "iframe" | "noembed" | "noframes" | "xmp" => {
+                Some(RawKind::Rawtext)
+            }
+            "textarea" | "title" => Some(RawKind::Rcdata),
+            _ => None,
+        };
+        if SKIPPED_TAGS.contains(&name) {
+            if !tag.self_closing {
+                self.skip.push(name.to_string());
+            }
+        } else if self.skip.is_empty() {
+            match name {
+                "title" => self.in_title = self.title.is_none(),
+                "main" | "article" => {
+                    self.newlines(2);
+                    self.main_depth += 1;
+                }
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    self.newlines(2);
+                    let level = name[1..].parse::<usize>().unwrap_or(1);
+                    self.write(&format!("{} ", "#".repeat(level)));
+                }
+                "br" => self.write("\n"),
+                "hr" => {
+                    self.newlines(2);
+                    self.write("---");
+                    self.newlines(2);
+                }
+                "pre" => {
+                    self.newlines(2);
+                    self.write("```\n");
+                    self.pre_depth += 1;
+                }
+                "code" if self.pre_depth == 0 => self.write("`"),
+                "strong" | "b" => self.write("**"),
+                "em" | "i" => self.write("*"),
+                "blockquote" => {
+                    self.newlines(2);
+                    self.write("> ");
+                }
+                "ul" | "ol" => {
+                    self.newlines(1);
+                    self.lists.push((name == "ol").then_some(0));
+                }
+                "li" => {
+                    self.newlines(1);
+                    let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                    let marker = match self.lists.last_mut() {
+                        Some(Some(n)) => {
+                            *n += 1;
+                            format!("{}. ", n)
+                        }
+                        _ => "- ".to_string(),
+                    };
+                    self.write(&format!("{}{}", indent, marker));
+                }
+                "tr" => {
+                    self.newlines(1);
+                    self.write("|");
+                }
+                "td" | "th" => self.write(" "),
+                "a" => {
+                    let href = Self::attr(tag, "href").and_then(|h| self.resolve(&h));
+                    if href.is_some() {
+                        self.write("[");
+                    }
+                    self.links.push(href);
+                }
+                "img" => {
+                    let alt = Self::attr(tag, "alt").unwrap_or_default();
+                    let src = Self::attr(tag, "src").and_then(|s| self.resolve(&s));
+                    if let (false, Some(src)) = (alt.trim().is_empty(), src) {
+                        self.write(&format!("![{}]({})", alt.trim(), src));
+                    }
+                }
+                _ if BLOCK_TAGS.contains(&name) => self.newlines(2),
+                _ => {}
+            }
+        }
+        match raw {
+            Some(kind) if !tag.self_closing => TokenSinkResult::RawData(kind),
+            _ => TokenSinkResult::Continue,
+        }
+    }
+
+    fn end(&mut self, tag: &Tag) {
+        let name = &*tag.name;
+        if let Some(pos) = self.skip.iter().rposition(|s| s == name) {
+            self.skip.truncate(pos);
+            return;
+        }
+        if !self.skip.is_empty() {
+            return;
+        }
+        match name {
+            "title" => self.in_title = false,
+            "main" | "article" => {
+                self.newlines(2);
+                self.main_depth = self.main_depth.saturating_sub(1);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" => self.newlines(2),
+            "pre" => {
+                self.newlines(1);
+                self.write("```");
+                self.newlines(2);
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+            }
+            "code" if self.pre_depth == 0 => self.write("`"),
+            "strong" | "b" => self.write("**"),
+            "em" | "i" => self.write("*"),
+            "td" | "th" => self.write(" |"),
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.newlines(if self.lists.is_empty() { 2 } else { 1 });
+            }
+            "a" => {
+                if let Some(Some(href)) = self.links.pop() {
+                    // 无文字的链接直接去掉 / drop links without text
+                    if self.out.ends_with('[') {
+                        self.out.pop();
+                        if self.main_depth > 0 {
+                            self.main.pop();
+                        }
+                    } else {
+                        self.write(&format!("]({})", href));
+                    }
+                }
+            }
+            _ if BLOCK_TAGS.contains(&name) => self.newlines(2),
+            _ => {}
+        }
+    }
+}
+
+struct MarkdownSink {
+    state: RefCell<MarkdownState>,
+}
+
+impl TokenSink for MarkdownSink {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        let mut state = self.state.borrow_mut();
+        match token {
+            Token::TagToken(tag) => match tag.kind {
+                TagKind::StartTag => return state.start(&tag),
+                TagKind::EndTag => state.end(&tag),
+            },
+            Token::CharacterTokens(text) => state.text(&text),
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+/// 清理多余空行与行尾空白
+/// Tidy up blank lines and trailing spaces.
+fn tidy(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank += 1;
+            if blank > 1 {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+/// HTML 转 Markdown，返回标题与正文；有 `<main>`/`<article>` 时优先取其内容
+/// Convert HTML to Markdown, returning title and body; `<main>`/`<article>` wins when present.
+pub fn html_to_markdown(html: &str, base: &Url) -> (Option<String>, String) {
+    let sink = MarkdownSink {
+        state: RefCell::new(MarkdownState {
+            base: Some(base.clone()),
+            ..Default::default()
+        }),
+    };
+    let tokenizer = Tokenizer::new(sink, TokenizerOpts::default());
+    let queue = BufferQueue::default();
+    queue.push_back(StrTendril::from(html));
+    let _ = tokenizer.feed(&queue);
+    tokenizer.end();
+
+    let state = tokenizer.sink.state.into_inner();
+    let main = tidy(&state.main);
+    let content = if main.chars().count() >= MIN_MAIN_CHARS {
+        main
+    } else {
+        tidy(&state.out)
+    };
+    debug!(chars = content.len(), "converted HTML to Markdown");
+    let title = state
+        .title
+        .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|t| !t.is_empty());
+    (title, content)
+}
+
+/// 抓取网页，返回 Markdown 正文
+/// Fetch a page and return its Markdown text.
+#[command]
+pub async fn fetch_url(
+    app: AppHandle,
+    url: String,
+    max_chars: Option<usize>,
+) -> Result<FetchedPage, String> {
+    let mut page = fetch_page(&app, &url).await?;
+    if let Some(max_chars) = max_chars {
+        page.limit_chars(max_chars);
+    }
+    Ok(page)
+}
+
+/// 抓取网页并返回可直接附加到消息的内容块
+/// Fetch a page and return a content part ready to attach to a message.
+#[command]
+pub async fn fetch_url_context(
+    app: AppHandle,
+    url: String,
+    max_chars: Option<usize>,
+) -> Result<Value, String> {
+    let page = fetch_url(app, url, max_chars).await?;
+    Ok(page.to_context_part())
+}
+
+/// 读取网页抓取配置
+/// Get the web fetch config.
+#[command]
+pub fn get_web_fetch_config(app: AppHandle) -> WebFetchConfig {
+    load_config(&app)
+}
+
+/// 保存网页抓取配置，并清空 robots 缓存
+/// Save the web fetch config and clear the robots cache.
+#[command]
+pub fn save_web_fetch_config(
+    app: AppHandle,
+    robots: State<'_, RobotsCache>,
+    config: WebFetchConfig,
+) -> Result<(), String> {
+    write_setting(&app, WEB_FETCH_FIELD, &config)?;
+    robots.entries.lock().unwrap().clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{response, serve_routes};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+
+    fn local_config() -> WebFetchConfig {
+        WebFetchConfig {
+            allow_private_hosts: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_readable_markdown() {
+        let html = "<html><head><title>Fixture</title></head><body>\
+            <nav>Menu</nav><h1>Heading</h1><p>Some <b>bold</b> text and \
+            a <a href=\"/next\">link</a>.</p><script>alert(1)</script></body></html>";
+        let fixture = serve_routes(vec![(
+            "/page",
+            response("200 OK", "Content-Type: text/html\r\n", html),
+        )]);
+        let url = format!("{}/page", fixture.base);
+        let page = fetch_with(&local_config(), &RobotsCache::default(), &url)
+            .await
+            .unwrap();
+        assert_eq!(page.title.as_deref(), Some("Fixture"));
+        assert!(page.content.contains("# Heading"));
+        assert!(page.content.contains(&format!(
+            "**bold** text and a [link]({}/next).",
+            fixture.base
+        )));
+        assert!(!page.content.contains("Menu"));
+        assert!(!page.content.contains("alert"));
+    }
+
+    #[tokio::test]
+    async fn honours_robots_txt() {
+        let fixture = serve_routes(vec![
+            (
+                "/robots.txt",
+                response("200 OK", "", "User-agent: *\nDisallow: /private\n"),
+            ),
+            ("/private/page", response("200 OK", "", "secret")),
+        ]);
+        let url = format!("{}/private/page", fixture.base);
+        let err = fetch_with(&local_config(), &RobotsCache::default(), &url)
+            .await
+            .unwrap_err();
+        assert!(err.contains("robots.txt"));
+        assert_eq!(fixture.paths(), vec!["/robots.txt"]);
+    }
+
+    #[tokio::test]
+    async fn download_is_capped() {
+        let body = "x".repeat(10_000);
+        let fixture = serve_routes(vec![(
+            "/big",
+            response("200 OK", "Content-Type: text/plain\r\n", &body),
+        )]);
+        let config = WebFetchConfig {
+            max_bytes: 1000,
+            respect_robots: false,
+            ..local_config()
+        };
+        let url = format!("{}/big", fixture.base);
+        let page = fetch_with(&config, &RobotsCache::default(), &url)
+            .await
+            .unwrap();
+        assert!(page.truncated);
+        assert_eq!(page.content.len(), 1000);
+    }
+
+    #[tokio::test]
+    async fn redirect_hops_are_checked_before_fetching() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let location = format!("Location: http://localhost:{}/secret\r\n", port);
+        let fixture = serve_routes(vec![
+            ("/start", response("302 Found", &location, "")),
+            ("/secret", response("200 OK", "", "secret")),
+        ]);
+        let config = WebFetchConfig {
+            blocked_domains: vec!["localhost".into()],
+            respect_robots: false,
+            ..local_config()
+        };
+        let url = format!("{}/start", fixture.base);
+        let err = fetch_with(&config, &RobotsCache::default(), &url)
+            .await
+            .unwrap_err();
+        assert!(err.contains("Domain is blocked"), "{}", err);
+        assert_eq!(fixture.paths(), vec!["/start"]);
+    }
+
+    #[tokio::test]
+    async fn private_hosts_are_refused() {
+        let fixture = serve_routes(vec![("/page", response("200 OK", "", "secret"))]);
+        let url = format!("{}/page", fixture.base);
+        let err = fetch_with(&WebFetchConfig::default(), &RobotsCache::default(), &url)
+            .await
+            .unwrap_err();
+        assert!(err.contains("Private address"));
+        assert!(fixture.paths().is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolver_refuses_private_addresses() {
+        let name = Name::from_str("localhost").unwrap();
+        let err = PublicOnlyResolver.resolve(name).await.err().unwrap();
+        assert!(err.to_string().contains("private address"));
+    }
+
+    #[test]
+    fn private_ip_ranges() {
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.0.1", "169.254.1.1", "::1"] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::ffff:127.0.0.1", "fd00::1", "fe80::1"] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_private_host("[::1]"));
+        assert!(is_private_host("printer.local"));
+        assert!(!is_private_host("example.com"));
+    }
+
+    #[test]
+    fn robots_longest_match_wins() {
+        let rules = RobotsRules::parse(
+            "User-agent: other\nDisallow: /\n\n\
+             User-agent: *\nDisallow: /docs\nAllow: /docs/public\nDisallow: /*.pdf$\n",
+        );
+        assert!(!rules.allows("/docs/private"));
+        assert!(rules.allows("/docs/public/page"));
+        assert!(!rules.allows("/files/a.pdf"));
+        assert!(rules.allows("/files/a.pdf?x=1"));
+        assert!(rules.allows("/"));
+    }
+
+    #[test]
+    fn domain_lists_include_subdomains() {
+        let config = WebFetchConfig {
+            allowed_domains: vec!["example.com".into()],
+            blocked_domains: vec!["*.ads.example.com".into()],
+            ..Default::default()
+        };
+        let check = |url: &str| check_url(&config, &Url::parse(url).unwrap());
+        assert!(check("https://docs.example.com/a").is_ok());
+        assert!(check("https://x.ads.example.com/").is_err());
+        assert!(check("https://example.org/").is_err());
+        assert!(check("ftp://example.com/").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use tauri::{command, AppHandle, Emitter, LogicalPosition, LogicalSize, Manager, Size};
+use tracing::{error, info, warn};
 
 use crate::helpers::{get_monitor_and_scale, monitor_size_in_dip, outer_size_in_dip};
 
@@ -8,21 +9,21 @@ use crate::helpers::{get_monitor_and_scale, monitor_size_in_dip, outer_size_in_d
 pub async fn exit(app_handle: AppHandle) -> Result<String, String> {
     if let Some(chat_window) = app_handle.get_webview_window("chat") {
         if let Err(e) = chat_window.close() {
-            eprintln!("Failed to close chat window: {}", e);
+            error!("Failed to close chat window: {}", e);
         }
     } else {
-        eprintln!("Chat window not found.");
+        warn!("Chat window not found.");
     }
 
     if let Some(main_window) = app_handle.get_webview_window("main") {
         if let Err(e) = main_window.close() {
-            eprintln!("Failed to close main window: {}", e);
+            error!("Failed to close main window: {}", e);
         }
     } else {
-        eprintln!("Main window not found.");
+        warn!("Main window not found.");
     }
 
-    println!("Windows closed successfully");
+    info!("Windows closed successfully");
     Ok("Exit completed successfully".to_string())
 }
 
@@ -38,7 +39,7 @@ pub async fn show_chat_window(app_handle: AppHandle) -> Result<(), String> {
             Ok(_) => {
                 chat_window.set_focus().map_err(|e| e.to_string())?;
                 if let Err(e) = chat_window.emit("chat-window-shown", ()) {
-                    eprintln!("Failed to emit chat-window-shown event: {}", e);
+                    error!("Failed to emit chat-window-shown event: {}", e);
                 }
                 Ok(())
             }